@@ -0,0 +1,64 @@
+/*
+ * Codegen throughput on a program of a million commands, parsing excluded:
+ *
+ *   cargo bench --bench codegen
+ */
+use std::{fmt::Write, io, time::Instant};
+
+use hackvm::{OptLevel, Parser, TranslationOptions, VMTranslator};
+
+const COMMANDS: usize = 1_000_000;
+const RUNS: usize = 5;
+
+// a function of 20 commands touching every segment, repeated. Calls and
+// comparisons stay rare enough for their labels to fit in the u16 counters
+fn program() -> String {
+    let mut source = String::new();
+    for n in 0..COMMANDS / 20 {
+        let _ = write!(
+            source,
+            "function Bench.f{n} 2\n\
+             push argument 0\npush constant 17\nadd\npop local 0\n\
+             push local 0\npush static 3\nsub\npop that 1\n\
+             push pointer 1\npush temp 2\nlt\nif-goto END\n\
+             push this 4\nneg\npop temp 0\n\
+             call Bench.f0 1\n\
+             label END\npush constant 0\nreturn\n"
+        );
+    }
+    source
+}
+
+fn main() {
+    let source = program();
+    let commands = Parser::new(&source)
+        .parse_all()
+        .expect("the benchmark program parses");
+
+    for opt_level in [OptLevel::None, OptLevel::Safe] {
+        let options = TranslationOptions {
+            opt_level,
+            ..TranslationOptions::default()
+        };
+
+        let mut best = f64::MAX;
+        for _ in 0..RUNS {
+            let commands = commands.clone();
+            let started = Instant::now();
+            let mut translator = VMTranslator::with_writer(io::sink(), "Bench", options.clone());
+            translator
+                .write_commands(commands)
+                .expect("the benchmark program translates");
+            translator.flush().expect("the sink never fails");
+            best = best.min(started.elapsed().as_secs_f64());
+        }
+
+        println!(
+            "-O{}: {} commands in {:.0} ms, {:.2}M commands/s",
+            opt_level as u8,
+            commands.len(),
+            best * 1000.0,
+            commands.len() as f64 / best / 1e6
+        );
+    }
+}
@@ -0,0 +1,37 @@
+/*
+ * C interface to the hackvm translator, built with
+ *
+ *   cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * Error codes above zero are the number of the diagnostic code, 4 for E0004,
+ * and stay the same across releases.
+ */
+#ifndef HACKVM_H
+#define HACKVM_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define HACKVM_OK 0
+/* a null pointer or a string that isn't UTF-8 */
+#define HACKVM_INVALID_ARGUMENT (-1)
+
+/*
+ * Translates the NUL terminated VM source, filestem names its statics. A
+ * bootstrap of 0 leaves out the SP setup and call to Sys.init, opt_level is 0,
+ * 1 or 2 like -O. On success *asm points to the assembly, otherwise *error to
+ * a message, both freed with hackvm_free. Either may be NULL when the caller
+ * doesn't want it.
+ */
+int hackvm_translate(const char *source, const char *filestem, int bootstrap, int opt_level,
+                     char **asm, char **error);
+
+/* frees a string returned by any hackvm_ function, NULL is ignored */
+void hackvm_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
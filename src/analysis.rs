@@ -0,0 +1,640 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    diagnostics::Severity,
+    memory::{self, StaticAllocator},
+    optimize, Command, MemorySegment, Span, Spanned, TranslationOptions, VmError,
+};
+
+/* A parsed input file */
+#[derive(Debug, Clone)]
+pub struct Unit {
+    pub path: PathBuf,
+    pub commands: Vec<Spanned<Command>>,
+}
+
+impl Unit {
+    pub fn new(path: impl Into<PathBuf>, commands: Vec<Spanned<Command>>) -> Self {
+        Unit {
+            path: path.into(),
+            commands,
+        }
+    }
+
+    /* prefix of the unit's static symbols */
+    pub fn filestem(&self) -> &str {
+        self.path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default()
+    }
+}
+
+/* Problem found by a pass, errors carry the span of the offending command */
+#[derive(Debug)]
+pub struct Finding {
+    pub path: PathBuf,
+    pub severity: Severity,
+    pub error: VmError,
+}
+
+impl Finding {
+    fn new(path: &Path, severity: Severity, error: VmError) -> Self {
+        Finding {
+            path: path.to_path_buf(),
+            severity,
+            error,
+        }
+    }
+}
+
+/* Functions provided by the Jack OS, callable without being defined in the inputs */
+pub const OS_FUNCTIONS: [&str; 46] = [
+    "Math.init",
+    "Math.abs",
+    "Math.multiply",
+    "Math.divide",
+    "Math.min",
+    "Math.max",
+    "Math.sqrt",
+    "String.new",
+    "String.dispose",
+    "String.length",
+    "String.charAt",
+    "String.setCharAt",
+    "String.appendChar",
+    "String.eraseLastChar",
+    "String.intValue",
+    "String.setInt",
+    "String.backSpace",
+    "String.doubleQuote",
+    "String.newLine",
+    "Array.new",
+    "Array.dispose",
+    "Output.init",
+    "Output.moveCursor",
+    "Output.printChar",
+    "Output.printString",
+    "Output.printInt",
+    "Output.println",
+    "Output.backSpace",
+    "Screen.init",
+    "Screen.clearScreen",
+    "Screen.setColor",
+    "Screen.drawPixel",
+    "Screen.drawLine",
+    "Screen.drawRectangle",
+    "Screen.drawCircle",
+    "Keyboard.init",
+    "Keyboard.keyPressed",
+    "Keyboard.readChar",
+    "Keyboard.readLine",
+    "Keyboard.readInt",
+    "Memory.init",
+    "Memory.peek",
+    "Memory.poke",
+    "Memory.alloc",
+    "Memory.deAlloc",
+    "Sys.halt",
+];
+
+/*
+ * Runs every pass over the program. Checks that need every file, like
+ * undefined calls, only run when units is the whole program
+ */
+pub fn analyze(units: &[Unit], whole_program: bool, options: &TranslationOptions) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    // a lone file missing Sys.init is likely a project 7 test translated with the bootstrap
+    if let (Some(entry), Some(first)) = (options.bootstrap.entry(), units.first()) {
+        if !defines(units, entry) {
+            let severity = if whole_program {
+                Severity::Error
+            } else {
+                Severity::Warning
+            };
+            let err = VmError::MissingEntry(entry.to_owned());
+            findings.push(Finding::new(&first.path, severity, err));
+        }
+    }
+
+    for unit in units {
+        findings.extend(
+            unresolved_gotos(&unit.commands)
+                .into_iter()
+                .chain(missing_returns(&unit.commands))
+                .map(|err| Finding::new(&unit.path, Severity::Error, err)),
+        );
+    }
+
+    for unit in units {
+        findings.extend(
+            unreachable_code(&unit.commands)
+                .into_iter()
+                .map(|err| Finding::new(&unit.path, Severity::Warning, err)),
+        );
+    }
+
+    if let Err((path, err)) = allocate_statics(units) {
+        findings.push(Finding::new(path, Severity::Error, err));
+    }
+
+    findings.extend(
+        inconsistent_calls(units)
+            .into_iter()
+            .map(|(path, err)| Finding::new(path, Severity::Warning, err)),
+    );
+
+    if whole_program {
+        findings.extend(
+            undefined_calls(units)
+                .into_iter()
+                .map(|(path, err)| Finding::new(path, Severity::Error, err)),
+        );
+        findings.extend(
+            argument_overreads(units)
+                .into_iter()
+                .map(|(path, err)| Finding::new(path, Severity::Warning, err)),
+        );
+
+        let bootstrap = &options.bootstrap;
+        if let (Some(entry), Some(stack_base)) = (bootstrap.entry(), bootstrap.stack_base()) {
+            // reported against the file with the entry, the deepest calls start there
+            let defined_in = units.iter().find(|unit| {
+                unit.commands.iter().any(
+                    |command| matches!(&command.node, Command::Function(name, _) if name == entry),
+                )
+            });
+            let overflow = stack_overflow(units, entry, stack_base);
+            if let (Some(unit), Some(err)) = (defined_in, overflow) {
+                findings.push(Finding::new(&unit.path, Severity::Warning, err));
+            }
+        }
+    }
+
+    findings
+}
+
+/*
+ * Splits a file into the commands before the first function and the body of
+ * each function, labels are scoped to these
+ */
+fn scopes(commands: &[Spanned<Command>]) -> Vec<(Option<&str>, &[Spanned<Command>])> {
+    let mut scopes = Vec::new();
+    let mut function = None;
+    let mut start = 0;
+
+    for (idx, command) in commands.iter().enumerate() {
+        if let Command::Function(name, _) = &command.node {
+            if idx > start {
+                scopes.push((function, &commands[start..idx]));
+            }
+            function = Some(name.as_str());
+            start = idx;
+        }
+    }
+    if start < commands.len() {
+        scopes.push((function, &commands[start..]));
+    }
+
+    scopes
+}
+
+/*
+ * Functions whose end is reachable, so they fall through into whatever follows.
+ * Ending on an unconditional goto is fine, Sys.init usually spins that way
+ */
+pub fn missing_returns(commands: &[Spanned<Command>]) -> Vec<VmError> {
+    scopes(commands)
+        .into_iter()
+        .filter_map(|(function, body)| {
+            let function = function?;
+            let terminated = body
+                .iter()
+                .fold(false, |terminated, command| match &command.node {
+                    Command::Return | Command::Goto(_) => true,
+                    Command::Label(_) => false,
+                    _ => terminated,
+                });
+
+            (!terminated).then(|| VmError::MissingReturn(function.to_owned()).at(body[0].span))
+        })
+        .collect()
+}
+
+/* runs of commands after a return or goto that no label makes reachable again */
+pub fn unreachable_code(commands: &[Spanned<Command>]) -> Vec<VmError> {
+    let mut warnings = Vec::new();
+    let mut dead: Option<(&str, Span, Span)> = None;
+
+    let mut flush = |dead: &mut Option<(&str, Span, Span)>| {
+        if let Some((after, first, last)) = dead.take() {
+            let err = VmError::UnreachableCode {
+                after: after.to_owned(),
+                first_line: first.line,
+                last_line: last.line,
+            };
+            warnings.push(err.at(first.to(last)));
+        }
+    };
+
+    let mut after = None;
+    for command in commands {
+        match &command.node {
+            Command::Label(_) | Command::Function(..) => {
+                flush(&mut dead);
+                after = None;
+            }
+            _ => {
+                if let Some(keyword) = after {
+                    match &mut dead {
+                        Some((_, _, last)) => *last = command.span,
+                        None => dead = Some((keyword, command.span, command.span)),
+                    }
+                }
+                after = match &command.node {
+                    Command::Return => Some("return"),
+                    Command::Goto(_) => Some("goto"),
+                    _ => after,
+                };
+            }
+        }
+    }
+    flush(&mut dead);
+
+    warnings
+}
+
+/* static addresses of the whole program, fails on the first variable past the budget */
+pub fn allocate_statics(units: &[Unit]) -> Result<StaticAllocator, (&Path, VmError)> {
+    let mut statics = StaticAllocator::new();
+
+    for unit in units {
+        for command in &unit.commands {
+            if let Command::Push(MemorySegment::Static, index)
+            | Command::Pop(MemorySegment::Static, index) = &command.node
+            {
+                statics
+                    .allocate(unit.filestem(), *index)
+                    .map_err(|err| (unit.path.as_path(), err.at(command.span)))?;
+            }
+        }
+    }
+
+    Ok(statics)
+}
+
+fn defines(units: &[Unit], function: &str) -> bool {
+    units
+        .iter()
+        .flat_map(|unit| &unit.commands)
+        .any(|command| matches!(&command.node, Command::Function(name, _) if name == function))
+}
+
+/* calls to functions that aren't declared in any unit nor part of the OS */
+pub fn undefined_calls(units: &[Unit]) -> Vec<(&Path, VmError)> {
+    let defined: HashSet<&str> = units
+        .iter()
+        .flat_map(|unit| &unit.commands)
+        .filter_map(|command| match &command.node {
+            Command::Function(name, _) => Some(name.as_str()),
+            _ => None,
+        })
+        .chain(OS_FUNCTIONS.iter().copied())
+        .collect();
+
+    let mut errors = Vec::new();
+    for unit in units {
+        for command in &unit.commands {
+            if let Command::Call(name, _) = &command.node {
+                if !defined.contains(name.as_str()) {
+                    let err = VmError::UndefinedFunction(name.clone()).at(command.span);
+                    errors.push((unit.path.as_path(), err));
+                }
+            }
+        }
+    }
+
+    errors
+}
+
+/* call sites passing a different number of arguments than the first call to the same function */
+pub fn inconsistent_calls(units: &[Unit]) -> Vec<(&Path, VmError)> {
+    let mut first_seen: HashMap<&str, u16> = HashMap::new();
+    let mut warnings = Vec::new();
+
+    for unit in units {
+        for command in &unit.commands {
+            if let Command::Call(name, n_args) = &command.node {
+                let expected = *first_seen.entry(name.as_str()).or_insert(*n_args);
+                if expected != *n_args {
+                    let err = VmError::InconsistentArity {
+                        function: name.clone(),
+                        args: *n_args,
+                        other: expected,
+                    };
+                    warnings.push((unit.path.as_path(), err.at(command.span)));
+                }
+            }
+        }
+    }
+
+    warnings
+}
+
+/* `argument n` accesses past the most arguments any call site passes to the function */
+pub fn argument_overreads(units: &[Unit]) -> Vec<(&Path, VmError)> {
+    let mut max_args: HashMap<&str, u16> = HashMap::new();
+    for command in units.iter().flat_map(|unit| &unit.commands) {
+        if let Command::Call(name, n_args) = &command.node {
+            let max = max_args.entry(name.as_str()).or_default();
+            *max = (*max).max(*n_args);
+        }
+    }
+
+    let mut warnings = Vec::new();
+    for unit in units {
+        for (function, body) in scopes(&unit.commands) {
+            // functions nobody calls, like Sys.init, have nothing to compare against
+            let Some(max) = function.and_then(|function| max_args.get(function)) else {
+                continue;
+            };
+
+            for command in body {
+                if let Command::Push(MemorySegment::Argument, offset)
+                | Command::Pop(MemorySegment::Argument, offset) = &command.node
+                {
+                    if offset >= max {
+                        let err = VmError::ArgumentOverread {
+                            function: function.unwrap_or_default().to_owned(),
+                            offset: *offset,
+                            max_args: *max,
+                        };
+                        warnings.push((unit.path.as_path(), err.at(command.span)));
+                    }
+                }
+            }
+        }
+    }
+
+    warnings
+}
+
+/* goto / if-goto targets with no matching label in the same function */
+pub fn unresolved_gotos(commands: &[Spanned<Command>]) -> Vec<VmError> {
+    let mut errors = Vec::new();
+
+    for (function, body) in scopes(commands) {
+        let labels: HashSet<&str> = body
+            .iter()
+            .filter_map(|command| match &command.node {
+                Command::Label(label) => Some(label.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        for command in body {
+            if let Command::Goto(label) | Command::IfGoto(label) = &command.node {
+                if !labels.contains(label.as_str()) {
+                    let err = VmError::UnresolvedLabel {
+                        label: label.clone(),
+                        function: function.map(str::to_owned),
+                    };
+                    errors.push(err.at(command.span));
+                }
+            }
+        }
+    }
+
+    errors
+}
+
+/* functions each function calls, in the order the calls appear */
+pub fn call_graph(units: &[Unit]) -> BTreeMap<&str, Vec<&str>> {
+    let mut graph: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+
+    for unit in units {
+        for (function, body) in scopes(&unit.commands) {
+            let Some(function) = function else {
+                continue;
+            };
+            let callees = graph.entry(function).or_default();
+            for command in body {
+                if let Command::Call(callee, _) = &command.node {
+                    if !callees.contains(&callee.as_str()) {
+                        callees.push(callee);
+                    }
+                }
+            }
+        }
+    }
+
+    graph
+}
+
+/* functions the program can get to from entry, entry included */
+pub fn reachable_functions<'a>(units: &'a [Unit], entry: &'a str) -> HashSet<&'a str> {
+    let graph = call_graph(units);
+    let mut reachable = HashSet::from([entry]);
+    let mut pending = vec![entry];
+
+    while let Some(function) = pending.pop() {
+        for callee in graph.get(function).into_iter().flatten() {
+            if reachable.insert(callee) {
+                pending.push(callee);
+            }
+        }
+    }
+
+    reachable
+}
+
+/*
+ * How far the stack can grow from the entry point: each function's locals and
+ * the most values it keeps on the stack at once, plus the frame and the depth
+ * of every call it makes. Recursion can't be bounded, so a recursive call only
+ * counts its frame and words is then a lower bound
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StackDepth {
+    /* words above the stack base, the frame the bootstrap pushes included */
+    pub words: u32,
+    /* the calls reaching that depth, starting with the entry */
+    pub calls: Vec<String>,
+    /* first function found calling itself, directly or through others */
+    pub recursive: Option<String>,
+    /* function pushing more on every round of a loop, words is then meaningless */
+    pub growing: Option<String>,
+}
+
+/* what a function body needs of the stack, not counting its callees */
+#[derive(Debug, Default)]
+struct FrameUse<'a> {
+    n_locals: u16,
+    peak: u32,
+    /* some loop leaves values behind every round */
+    grows: bool,
+    /* each call with the stack height before it, its arguments included */
+    calls: Vec<(&'a str, u32)>,
+}
+
+// a loop pushing more every round is capped here, enough to always overflow
+const UNBOUNDED: u32 = memory::HEAP_END as u32;
+
+pub fn stack_depth(units: &[Unit], entry: &str) -> StackDepth {
+    let mut frames = HashMap::new();
+    for unit in units {
+        for (function, body) in scopes(&unit.commands) {
+            if let Some(function) = function {
+                frames.insert(function, frame_use(body));
+            }
+        }
+    }
+
+    let mut memo = HashMap::new();
+    let mut found = Found::default();
+    let (words, calls) = deepest(entry, &frames, &mut memo, &mut Vec::new(), &mut found);
+
+    StackDepth {
+        words: words
+            .saturating_add(u32::from(memory::FRAME_SIZE))
+            .min(UNBOUNDED),
+        calls: calls.into_iter().map(str::to_owned).collect(),
+        recursive: found.recursive.map(str::to_owned),
+        growing: found.growing.map(str::to_owned),
+    }
+}
+
+// what deepest ran into on the way, only the first of each is kept
+#[derive(Debug, Default)]
+struct Found<'a> {
+    recursive: Option<&'a str>,
+    growing: Option<&'a str>,
+}
+
+/*
+ * Walks every path through the body tracking the stack height before each
+ * command. Heights only go up and are capped, so this always settles
+ */
+fn frame_use(body: &[Spanned<Command>]) -> FrameUse<'_> {
+    let labels: HashMap<&str, usize> = body
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, command)| match &command.node {
+            Command::Label(label) => Some((label.as_str(), idx)),
+            _ => None,
+        })
+        .collect();
+    let mut frame = FrameUse::default();
+    let mut heights: Vec<Option<u32>> = vec![None; body.len()];
+    let mut pending = vec![(0, 0)];
+
+    while let Some((idx, height)) = pending.pop() {
+        let Some(command) = body.get(idx) else {
+            continue;
+        };
+        if heights[idx].is_some_and(|seen| seen >= height) {
+            continue;
+        }
+        heights[idx] = Some(height);
+
+        let (pops, pushes) = optimize::stack_effect(&command.node);
+        let after = (height.saturating_sub(pops.into()) + u32::from(pushes)).min(UNBOUNDED);
+        frame.peak = frame.peak.max(height).max(after);
+        frame.grows |= after == UNBOUNDED;
+
+        let target = |label: &String| labels.get(label.as_str()).map(|idx| (*idx, after));
+        match &command.node {
+            Command::Function(_, n_locals) => {
+                frame.n_locals = *n_locals;
+                pending.push((idx + 1, after));
+            }
+            Command::Goto(label) => pending.extend(target(label)),
+            Command::IfGoto(label) => {
+                pending.extend(target(label));
+                pending.push((idx + 1, after));
+            }
+            Command::Return => {}
+            _ => pending.push((idx + 1, after)),
+        }
+    }
+
+    for (command, height) in body.iter().zip(&heights) {
+        if let (Command::Call(callee, _), Some(height)) = (&command.node, height) {
+            frame.calls.push((callee.as_str(), *height));
+        }
+    }
+
+    frame
+}
+
+// depth of function with everything it calls and the deepest chain of calls in it
+fn deepest<'a>(
+    function: &'a str,
+    frames: &HashMap<&'a str, FrameUse<'a>>,
+    memo: &mut HashMap<&'a str, (u32, Vec<&'a str>)>,
+    active: &mut Vec<&'a str>,
+    found: &mut Found<'a>,
+) -> (u32, Vec<&'a str>) {
+    if let Some(known) = memo.get(function) {
+        return known.clone();
+    }
+    if active.contains(&function) {
+        found.recursive.get_or_insert(function);
+        return (0, Vec::new());
+    }
+    // OS functions without a body only get their frame counted
+    let Some(frame) = frames.get(function) else {
+        return (0, vec![function]);
+    };
+
+    if frame.grows {
+        found.growing.get_or_insert(function);
+    }
+    active.push(function);
+    let mut words = frame.peak;
+    let mut chain = Vec::new();
+    for (callee, height) in &frame.calls {
+        let (depth, calls) = deepest(callee, frames, memo, active, found);
+        let during = height
+            .saturating_add(u32::from(memory::FRAME_SIZE))
+            .saturating_add(depth);
+        if during > words {
+            words = during;
+            chain = calls;
+        }
+    }
+    active.pop();
+
+    chain.insert(0, function);
+    let words = words
+        .saturating_add(u32::from(frame.n_locals))
+        .min(UNBOUNDED);
+    memo.insert(function, (words, chain.clone()));
+
+    (words, chain)
+}
+
+/* the stack from the bootstrap's base growing past the stack region at its deepest */
+pub fn stack_overflow(units: &[Unit], entry: &str, stack_base: u16) -> Option<VmError> {
+    let depth = stack_depth(units, entry);
+    let end = u32::from(stack_base) + depth.words;
+
+    let region = if stack_base < memory::STACK_BASE && end > u32::from(memory::STATIC_BASE) {
+        "static variables"
+    } else if stack_base < memory::HEAP_BASE && end > u32::from(memory::HEAP_BASE) {
+        "heap"
+    } else {
+        return None;
+    };
+
+    Some(VmError::StackOverflow {
+        words: depth.growing.is_none().then_some(depth.words),
+        stack_base,
+        region,
+        calls: depth.calls,
+    })
+}
@@ -0,0 +1,302 @@
+use alloc::collections::BTreeMap;
+use core::fmt::{self, Write};
+
+use crate::{
+    hack::{at, Address, Comp, Dest, HackInstr, Jump},
+    memory::{self, ROM_SIZE, STATIC_BASE},
+    prelude::*,
+};
+
+/* Symbols every program starts out with */
+pub const PREDEFINED_SYMBOLS: [(&str, u16); 23] = [
+    ("SP", memory::SP),
+    ("LCL", memory::LCL),
+    ("ARG", memory::ARG),
+    ("THIS", memory::THIS),
+    ("THAT", memory::THAT),
+    ("R0", 0),
+    ("R1", 1),
+    ("R2", 2),
+    ("R3", 3),
+    ("R4", 4),
+    ("R5", 5),
+    ("R6", 6),
+    ("R7", 7),
+    ("R8", 8),
+    ("R9", 9),
+    ("R10", 10),
+    ("R11", 11),
+    ("R12", 12),
+    ("R13", 13),
+    ("R14", 14),
+    ("R15", 15),
+    ("SCREEN", memory::SCREEN),
+    ("KBD", memory::KBD),
+];
+
+/* Every computation with the a bit and c1 to c6 it is encoded as */
+const COMP_CODES: [(Comp, u16); 28] = [
+    (Comp::Zero, 0b0101010),
+    (Comp::One, 0b0111111),
+    (Comp::MinusOne, 0b0111010),
+    (Comp::D, 0b0001100),
+    (Comp::A, 0b0110000),
+    (Comp::M, 0b1110000),
+    (Comp::NotD, 0b0001101),
+    (Comp::NotA, 0b0110001),
+    (Comp::NotM, 0b1110001),
+    (Comp::NegD, 0b0001111),
+    (Comp::NegA, 0b0110011),
+    (Comp::NegM, 0b1110011),
+    (Comp::DPlusOne, 0b0011111),
+    (Comp::APlusOne, 0b0110111),
+    (Comp::MPlusOne, 0b1110111),
+    (Comp::DMinusOne, 0b0001110),
+    (Comp::AMinusOne, 0b0110010),
+    (Comp::MMinusOne, 0b1110010),
+    (Comp::DPlusA, 0b0000010),
+    (Comp::DPlusM, 0b1000010),
+    (Comp::DMinusA, 0b0010011),
+    (Comp::DMinusM, 0b1010011),
+    (Comp::AMinusD, 0b0000111),
+    (Comp::MMinusD, 0b1000111),
+    (Comp::DAndA, 0b0000000),
+    (Comp::DAndM, 0b1000000),
+    (Comp::DOrA, 0b0010101),
+    (Comp::DOrM, 0b1010101),
+];
+
+const JUMP_CODES: [(Jump, u16); 7] = [
+    (Jump::Jgt, 0b001),
+    (Jump::Jeq, 0b010),
+    (Jump::Jge, 0b011),
+    (Jump::Jlt, 0b100),
+    (Jump::Jne, 0b101),
+    (Jump::Jle, 0b110),
+    (Jump::Jmp, 0b111),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /* 1-based line in the assembly, 0 for whole program errors */
+    pub line: usize,
+    pub message: String,
+}
+
+/*
+ * What every symbol stands for: the predefined ones, labels once the first
+ * pass declared them, and variables, given the next free RAM address from 16
+ * on as they are first used
+ */
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    symbols: BTreeMap<String, u16>,
+    next_variable: u16,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        SymbolTable {
+            symbols: PREDEFINED_SYMBOLS
+                .iter()
+                .map(|(name, address)| ((*name).to_owned(), *address))
+                .collect(),
+            next_variable: STATIC_BASE,
+        }
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable::default()
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<u16> {
+        self.symbols.get(symbol).copied()
+    }
+
+    pub fn define_label(&mut self, name: &str, rom: u16) -> Result<(), String> {
+        if PREDEFINED_SYMBOLS.iter().any(|(symbol, _)| *symbol == name) {
+            return Err(format!("Label {} shadows a predefined symbol", name));
+        }
+        if self.symbols.insert(name.to_owned(), rom).is_some() {
+            return Err(format!("Label {} is declared more than once", name));
+        }
+
+        Ok(())
+    }
+
+    /* the address of symbol, allocating a variable for one seen the first time */
+    pub fn resolve(&mut self, symbol: &str) -> Result<u16, String> {
+        if let Some(address) = self.get(symbol) {
+            return Ok(address);
+        }
+        // variables run up to the screen, past it they would clobber what's shown
+        if self.next_variable >= memory::SCREEN {
+            return Err(format!("No RAM left for variable {}", symbol));
+        }
+
+        let address = self.next_variable;
+        self.symbols.insert(symbol.to_owned(), address);
+        self.next_variable += 1;
+        Ok(address)
+    }
+}
+
+/*
+ * Assembles Hack assembly into machine code, one word per instruction. The
+ * first pass gives every (label) the ROM address of the instruction after it,
+ * the second encodes the instructions, resolving symbols as it goes
+ */
+pub fn assemble(asm: &str) -> Result<Vec<u16>, AsmError> {
+    let mut instrs = Vec::new();
+    let mut symbols = SymbolTable::new();
+
+    for (n, line) in asm.lines().enumerate() {
+        let code = match line.find("//") {
+            Some(idx) => &line[..idx],
+            None => line,
+        }
+        .trim();
+        if code.is_empty() {
+            continue;
+        }
+
+        let instr: HackInstr = code.parse().map_err(|err| error(n + 1, err))?;
+        match instr {
+            HackInstr::Label(name) => {
+                // too many instructions are reported once they are all counted
+                let rom = u16::try_from(instrs.len()).unwrap_or(u16::MAX);
+                symbols
+                    .define_label(&name, rom)
+                    .map_err(|err| error(n + 1, err))?;
+            }
+            instr => instrs.push((n + 1, instr)),
+        }
+    }
+    if instrs.len() > ROM_SIZE {
+        return Err(error(
+            0,
+            format!(
+                "{} instructions don't fit in the {} words of ROM",
+                instrs.len(),
+                ROM_SIZE
+            ),
+        ));
+    }
+
+    instrs
+        .iter()
+        .map(|(line, instr)| encode(instr, &mut symbols).map_err(|err| error(*line, err)))
+        .collect()
+}
+
+/* The word an instruction assembles to, labels are resolved through symbols */
+pub fn encode(instr: &HackInstr, symbols: &mut SymbolTable) -> Result<u16, String> {
+    match instr {
+        HackInstr::AInstr(Address::Value(value)) => Ok(*value & 0x7fff),
+        HackInstr::AInstr(Address::Symbol(symbol)) => symbols.resolve(symbol),
+        HackInstr::CInstr { dest, comp, jump } => {
+            let comp = COMP_CODES
+                .iter()
+                .find(|(code, _)| code == comp)
+                .map(|(_, bits)| *bits)
+                .unwrap_or_default();
+            Ok(0b111 << 13 | comp << 6 | dest_bits(*dest) << 3 | jump_bits(*jump))
+        }
+        HackInstr::Label(name) => Err(format!("Label {} has no encoding", name)),
+    }
+}
+
+/* The instruction a word encodes, with plain addresses for A-instructions */
+pub fn decode(word: u16) -> Result<HackInstr, String> {
+    if word & 0x8000 == 0 {
+        return Ok(at(word));
+    }
+    if word >> 13 != 0b111 {
+        return Err(format!(
+            "{:016b} is neither an A- nor a C-instruction",
+            word
+        ));
+    }
+
+    let bits = word >> 6 & 0b111_1111;
+    let comp = COMP_CODES
+        .iter()
+        .find(|(_, code)| *code == bits)
+        .map(|(comp, _)| *comp)
+        .ok_or_else(|| format!("{:016b} computes nothing the assembly can name", word))?;
+    let dest = Dest::new(
+        word & 0b100000 != 0,
+        word & 0b001000 != 0,
+        word & 0b010000 != 0,
+    );
+    let jump = JUMP_CODES
+        .iter()
+        .find(|(_, code)| *code == word & 0b111)
+        .map(|(jump, _)| *jump);
+
+    Ok(HackInstr::CInstr { dest, comp, jump })
+}
+
+/* The words of a .hack file, one line of 16 binary digits each */
+pub fn from_hack(hack: &str) -> Result<Vec<u16>, AsmError> {
+    let mut words = Vec::new();
+    for (n, line) in hack.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.len() != 16 {
+            return Err(error(
+                n + 1,
+                format!("Expected 16 binary digits, found {}", line),
+            ));
+        }
+        let word = u16::from_str_radix(line, 2)
+            .map_err(|_| error(n + 1, format!("Expected 16 binary digits, found {}", line)))?;
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+/* The .hack file: every word in binary on a line of its own */
+pub fn to_hack(words: &[u16]) -> String {
+    let mut hack = String::with_capacity(words.len() * 17);
+    for word in words {
+        let _ = writeln!(hack, "{:016b}", word);
+    }
+
+    hack
+}
+
+// d1 d2 d3 are A, D and M
+fn dest_bits(dest: Dest) -> u16 {
+    u16::from(dest.a) << 2 | u16::from(dest.d) << 1 | u16::from(dest.m)
+}
+
+// no jump is 000
+fn jump_bits(jump: Option<Jump>) -> u16 {
+    JUMP_CODES
+        .iter()
+        .find(|(code, _)| Some(*code) == jump)
+        .map(|(_, bits)| *bits)
+        .unwrap_or_default()
+}
+
+fn error(line: usize, message: String) -> AsmError {
+    AsmError { line, message }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "asm line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl core::error::Error for AsmError {}
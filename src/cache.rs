@@ -0,0 +1,98 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    analysis::Unit,
+    memory::StaticAllocator,
+    optimize::InlineFunction,
+    parts::{Ids, TranslatedFile},
+    provenance::content_hash,
+    TranslationOptions,
+};
+
+pub const CACHE_DIR: &str = ".hackvm-cache";
+
+/*
+ * Translated files kept between runs, one entry per input named after the
+ * hash of its path. An entry is only used while its key matches, which covers
+ * everything the code of a file depends on: the parsed commands, the options,
+ * the ids reserved for it, the functions inlined into it and the static
+ * addresses of the program
+ */
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+    /* entries looked up or stored this run, prune removes the others */
+    used: BTreeSet<PathBuf>,
+}
+
+impl Cache {
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        Ok(Cache {
+            dir: dir.to_path_buf(),
+            used: BTreeSet::new(),
+        })
+    }
+
+    pub fn key(
+        unit: &Unit,
+        options: &TranslationOptions,
+        inlines: &BTreeMap<String, InlineFunction>,
+        statics: &StaticAllocator,
+        first: Ids,
+    ) -> u64 {
+        // Debug output is stable within a version, which is part of the key
+        let key = format!(
+            "{}\n{}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}",
+            env!("CARGO_PKG_VERSION"),
+            unit.path.display(),
+            options,
+            first,
+            inlines,
+            statics.entries().collect::<Vec<_>>(),
+            unit.commands
+        );
+        content_hash(key.as_bytes())
+    }
+
+    pub fn get(&mut self, path: &Path, key: u64) -> Option<TranslatedFile> {
+        let entry = self.entry(path);
+        self.used.insert(entry.clone());
+
+        let text = fs::read_to_string(entry).ok()?;
+        let (stored, file) = text.split_once('\n')?;
+        if stored != format!("{:016x}", key) {
+            return None;
+        }
+        TranslatedFile::decode(file)
+    }
+
+    pub fn put(&mut self, path: &Path, key: u64, file: &TranslatedFile) -> io::Result<()> {
+        let entry = self.entry(path);
+        self.used.insert(entry.clone());
+
+        fs::write(entry, format!("{:016x}\n{}", key, file.encode()))
+    }
+
+    /* removes the entries of files that are no longer inputs */
+    pub fn prune(&self) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "chunk") && !self.used.contains(&path) {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn entry(&self, path: &Path) -> PathBuf {
+        let name = content_hash(path.display().to_string().as_bytes());
+        self.dir.join(format!("{:016x}.chunk", name))
+    }
+}
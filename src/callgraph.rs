@@ -0,0 +1,212 @@
+use std::{collections::BTreeSet, fmt::Write as _};
+
+use crate::{analysis::Unit, diagnostics::json_string, Command};
+
+/*
+ * Which functions call which across every input, from their function and
+ * call commands. Calls made outside of any function aren't in it
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CallGraph {
+    /* defined ones in the order they appear, then the ones only called, by name */
+    pub functions: Vec<Function>,
+    /* by caller in the order of functions, then in the order the calls first appear */
+    pub calls: Vec<Call>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    /* the file defining it and its locals, None for one no input defines */
+    pub file: Option<String>,
+    pub n_locals: Option<u16>,
+}
+
+/* Every call from caller to callee passing n_args, a different count is another edge */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    pub caller: String,
+    pub callee: String,
+    pub n_args: u16,
+    /* call commands making it */
+    pub sites: usize,
+}
+
+impl CallGraph {
+    pub fn build(units: &[Unit]) -> Self {
+        let mut graph = CallGraph::default();
+        let mut called: BTreeSet<&str> = BTreeSet::new();
+
+        for unit in units {
+            let file = unit
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned());
+            let mut caller: Option<&str> = None;
+            for command in &unit.commands {
+                match &command.node {
+                    Command::Function(name, n_locals) => {
+                        caller = Some(name);
+                        graph.functions.push(Function {
+                            name: name.clone(),
+                            file: file.clone(),
+                            n_locals: Some(*n_locals),
+                        });
+                    }
+                    Command::Call(callee, n_args) => {
+                        let Some(caller) = caller else {
+                            continue;
+                        };
+                        called.insert(callee);
+                        let existing = graph.calls.iter_mut().find(|call| {
+                            call.caller == caller
+                                && call.callee == *callee
+                                && call.n_args == *n_args
+                        });
+                        match existing {
+                            Some(call) => call.sites += 1,
+                            None => graph.calls.push(Call {
+                                caller: caller.to_owned(),
+                                callee: callee.clone(),
+                                n_args: *n_args,
+                                sites: 1,
+                            }),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for callee in called {
+            if !graph
+                .functions
+                .iter()
+                .any(|function| function.name == callee)
+            {
+                graph.functions.push(Function {
+                    name: callee.to_owned(),
+                    file: None,
+                    n_locals: None,
+                });
+            }
+        }
+
+        graph
+    }
+
+    /*
+     * Graphviz, a cluster per file and the functions no input defines dashed:
+     *
+     * digraph calls {
+     *   subgraph "cluster_Main.vm" {
+     *     label = "Main.vm";
+     *     "Main.main";
+     *   }
+     *   "Math.multiply" [style = dashed];
+     *   "Main.main" -> "Math.multiply" [label = "2 args, 3 sites"];
+     * }
+     */
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph calls {\n  node [shape = box];\n");
+
+        let mut files: Vec<&str> = Vec::new();
+        for file in self
+            .functions
+            .iter()
+            .filter_map(|function| function.file.as_deref())
+        {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+        for file in files {
+            let _ = writeln!(
+                out,
+                "  subgraph {} {{",
+                json_string(&format!("cluster_{}", file))
+            );
+            let _ = writeln!(out, "    label = {};", json_string(file));
+            for function in &self.functions {
+                if function.file.as_deref() == Some(file) {
+                    let _ = writeln!(out, "    {};", json_string(&function.name));
+                }
+            }
+            out.push_str("  }\n");
+        }
+        for function in self
+            .functions
+            .iter()
+            .filter(|function| function.file.is_none())
+        {
+            let _ = writeln!(out, "  {} [style = dashed];", json_string(&function.name));
+        }
+
+        for call in &self.calls {
+            let _ = writeln!(
+                out,
+                "  {} -> {} [label = \"{}\"];",
+                json_string(&call.caller),
+                json_string(&call.callee),
+                call.label()
+            );
+        }
+        out.push_str("}\n");
+
+        out
+    }
+
+    /*
+     * {"functions":[{"name":"Main.main","file":"Main.vm","locals":0}, ...],
+     *  "calls":[{"caller":"Main.main","callee":"Math.multiply","args":2,"sites":3}, ...]}
+     */
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"functions\":[");
+        for (idx, function) in self.functions.iter().enumerate() {
+            let file = function
+                .file
+                .as_deref()
+                .map_or("null".to_owned(), json_string);
+            let locals = function
+                .n_locals
+                .map_or("null".to_owned(), |n| n.to_string());
+            let _ = write!(
+                out,
+                "{}\n  {{\"name\":{},\"file\":{},\"locals\":{}}}",
+                if idx == 0 { "" } else { "," },
+                json_string(&function.name),
+                file,
+                locals
+            );
+        }
+        out.push_str("\n],\"calls\":[");
+        for (idx, call) in self.calls.iter().enumerate() {
+            let _ = write!(
+                out,
+                "{}\n  {{\"caller\":{},\"callee\":{},\"args\":{},\"sites\":{}}}",
+                if idx == 0 { "" } else { "," },
+                json_string(&call.caller),
+                json_string(&call.callee),
+                call.n_args,
+                call.sites
+            );
+        }
+        out.push_str("\n]}\n");
+
+        out
+    }
+}
+
+impl Call {
+    // 1 arg, 2 sites
+    fn label(&self) -> String {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        format!(
+            "{} arg{}, {} site{}",
+            self.n_args,
+            plural(usize::from(self.n_args)),
+            self.sites,
+            plural(self.sites)
+        )
+    }
+}
@@ -0,0 +1,275 @@
+use std::{collections::BTreeMap, fs, io, path::Path, str::FromStr};
+
+use toml::{Table, Value};
+
+use crate::{diagnostics, Bootstrap, OptLevel, VmError};
+
+pub const CONFIG_FILE: &str = "hackvm.toml";
+
+/* warnings whose level a project can change, by name */
+pub const LINTS: [(&str, &str); 6] = [
+    ("trailing-tokens", "E0007"),
+    ("inconsistent-arity", "E0012"),
+    ("argument-overread", "E0013"),
+    ("unreachable-code", "E0015"),
+    ("missing-entry", "E0017"),
+    ("stack-overflow", "E0020"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LintLevel {
+    /* not reported at all */
+    Allow,
+    #[default]
+    Warn,
+    /* reported as an error, failing the build */
+    Deny,
+}
+
+impl FromStr for LintLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(LintLevel::Allow),
+            "warn" => Ok(LintLevel::Warn),
+            "deny" => Ok(LintLevel::Deny),
+            _ => Err(format!(
+                "Unknown lint level {} (expected allow, warn or deny)",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lints {
+    levels: BTreeMap<&'static str, LintLevel>,
+}
+
+impl Lints {
+    pub fn set(&mut self, lint: &str, level: LintLevel) -> Result<(), String> {
+        let Some((name, _)) = LINTS.iter().find(|(name, _)| *name == lint) else {
+            let names: Vec<&str> = LINTS.iter().map(|(name, _)| *name).collect();
+            let mut msg = format!("Unknown lint {}", lint);
+            if let Some(help) = diagnostics::suggest(lint, &names) {
+                msg.push_str(&format!(", {}", help));
+            }
+            return Err(msg);
+        };
+
+        self.levels.insert(name, level);
+        Ok(())
+    }
+
+    /* level of the lint a warning belongs to, warnings outside of LINTS always warn */
+    pub fn level(&self, warning: &VmError) -> LintLevel {
+        LINTS
+            .iter()
+            .find(|(_, code)| *code == warning.code())
+            .and_then(|(name, _)| self.levels.get(name))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/*
+ * Settings read from hackvm.toml. Everything is optional, unset values leave
+ * the command line defaults alone:
+ *
+ * [project]
+ * name = "Pong"
+ *
+ * [build]
+ * inputs = ["src", "os"]
+ * output = "Pong.asm"
+ * bootstrap = true
+ * entry = "Sys.init"
+ * stack-base = 256
+ * opt-level = 0
+ * strict = false
+ *
+ * [lints]
+ * unreachable-code = "allow"
+ *
+ * [os]
+ * bundled = true
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    pub name: Option<String>,
+    /* paths and glob patterns, relative to the directory holding the file */
+    pub inputs: Vec<String>,
+    pub output: Option<String>,
+    pub bootstrap: Option<bool>,
+    pub entry: Option<String>,
+    pub stack_base: Option<u16>,
+    pub opt_level: Option<OptLevel>,
+    pub strict: Option<bool>,
+    pub lints: Lints,
+    /* translated after the inputs, the program calls into it */
+    pub os: Option<Os>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Os {
+    /* the one built into hackvm, see os::FILES */
+    Bundled,
+    /* a directory of OS .vm files, like the nand2tetris tools/OS */
+    Path(String),
+}
+
+impl Config {
+    /* reads the file, resolving its relative paths against the directory it's in */
+    pub fn load(path: &Path) -> io::Result<Config> {
+        let text = fs::read_to_string(path).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("Could not read {}: {}", path.display(), err),
+            )
+        })?;
+        let mut config = text.parse::<Config>().map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid {}: {}", path.display(), err),
+            )
+        })?;
+
+        let root = path.parent().unwrap_or(Path::new(""));
+        let resolve = |input: &str| root.join(input).to_string_lossy().into_owned();
+        config.inputs = config.inputs.iter().map(|input| resolve(input)).collect();
+        config.output = config.output.as_deref().map(resolve);
+        if let Some(Os::Path(dir)) = &mut config.os {
+            *dir = resolve(dir);
+        }
+
+        Ok(config)
+    }
+
+    /* None when the file says nothing about the bootstrap */
+    pub fn bootstrap(&self) -> Option<Bootstrap> {
+        if self.bootstrap == Some(false) {
+            return Some(Bootstrap::None);
+        }
+        if self.entry.is_none() && self.stack_base.is_none() {
+            return self.bootstrap.map(|_| Bootstrap::Standard);
+        }
+
+        Some(Bootstrap::Custom {
+            stack_base: self.stack_base.unwrap_or(256),
+            entry: self.entry.clone().unwrap_or_else(|| "Sys.init".to_owned()),
+        })
+    }
+}
+
+impl FromStr for Config {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let table: Table = s
+            .parse()
+            .map_err(|err: toml::de::Error| err.message().to_owned())?;
+        let mut config = Config::default();
+
+        for (section, value) in &table {
+            let fields = value
+                .as_table()
+                .ok_or_else(|| format!("[{}] should be a table", section))?;
+
+            match section.as_str() {
+                "project" => {
+                    for (key, value) in fields {
+                        match key.as_str() {
+                            "name" => config.name = Some(string(section, key, value)?),
+                            // recorded by `hackvm new`, nothing to do with it when building
+                            "template" => (),
+                            _ => return Err(unknown_key(section, key)),
+                        }
+                    }
+                }
+                "build" => {
+                    for (key, value) in fields {
+                        match key.as_str() {
+                            "inputs" => config.inputs = strings(section, key, value)?,
+                            "output" => config.output = Some(string(section, key, value)?),
+                            "bootstrap" => config.bootstrap = Some(boolean(section, key, value)?),
+                            "entry" => config.entry = Some(string(section, key, value)?),
+                            "stack-base" => {
+                                config.stack_base = Some(integer(section, key, value, 0x7fff)?)
+                            }
+                            "opt-level" => {
+                                let level: u8 = integer(section, key, value, 2)?;
+                                config.opt_level = Some(level.try_into()?)
+                            }
+                            "strict" => config.strict = Some(boolean(section, key, value)?),
+                            _ => return Err(unknown_key(section, key)),
+                        }
+                    }
+                }
+                "lints" => {
+                    for (key, value) in fields {
+                        let level = string(section, key, value)?.parse()?;
+                        config.lints.set(key, level)?;
+                    }
+                }
+                "os" => {
+                    for (key, value) in fields {
+                        let os = match key.as_str() {
+                            "bundled" if boolean(section, key, value)? => Os::Bundled,
+                            "bundled" => continue,
+                            "path" => Os::Path(string(section, key, value)?),
+                            _ => return Err(unknown_key(section, key)),
+                        };
+                        if config.os.replace(os).is_some() {
+                            return Err("[os] takes either bundled = true or a path".to_owned());
+                        }
+                    }
+                }
+                _ => return Err(format!("Unknown section [{}]", section)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn unknown_key(section: &str, key: &str) -> String {
+    format!("Unknown key {} in [{}]", key, section)
+}
+
+fn string(section: &str, key: &str, value: &Value) -> Result<String, String> {
+    value
+        .as_str()
+        .map(str::to_owned)
+        .ok_or_else(|| format!("{}.{} should be a string", section, key))
+}
+
+fn strings(section: &str, key: &str, value: &Value) -> Result<Vec<String>, String> {
+    let invalid = || format!("{}.{} should be a list of strings", section, key);
+
+    value
+        .as_array()
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|item| item.as_str().map(str::to_owned).ok_or_else(invalid))
+        .collect()
+}
+
+fn boolean(section: &str, key: &str, value: &Value) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| format!("{}.{} should be true or false", section, key))
+}
+
+fn integer<T: TryFrom<i64>>(
+    section: &str,
+    key: &str,
+    value: &Value,
+    max: i64,
+) -> Result<T, String> {
+    value
+        .as_integer()
+        .filter(|n| (0..=max).contains(n))
+        .and_then(|n| T::try_from(n).ok())
+        .ok_or_else(|| format!("{}.{} should be a number from 0 to {}", section, key, max))
+}
@@ -0,0 +1,169 @@
+use alloc::collections::BTreeMap;
+use core::{fmt, fmt::Write as _};
+
+use crate::{interpreter::Interpreter, prelude::*, Command};
+
+/*
+ * Which VM source lines ran and how often, from the interpreter's hits, see
+ * Interpreter::count_hits. Files are named Stem.vm, callers writing a
+ * tracefile put the path in instead
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    /* in the order they were loaded */
+    pub files: Vec<FileCoverage>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileCoverage {
+    pub file: String,
+    /* times each line holding a command ran */
+    pub lines: BTreeMap<usize, u64>,
+    /* name, line of the function command and times the function was entered */
+    pub functions: Vec<(String, usize, u64)>,
+}
+
+impl Coverage {
+    pub fn from_interpreter(vm: &Interpreter) -> Self {
+        let hits = vm.hits();
+        let mut files: Vec<FileCoverage> = Vec::new();
+
+        let mut pc = 0;
+        while let Some((filestem, command)) = vm.command_at(pc) {
+            let count = hits.get(pc).copied().unwrap_or_default();
+            let file = format!("{}.vm", filestem);
+            if files.last().is_none_or(|current| current.file != file) {
+                files.push(FileCoverage {
+                    file,
+                    ..FileCoverage::default()
+                });
+            }
+
+            if let Some(current) = files.last_mut() {
+                let line = command.span.line;
+                let ran = current.lines.entry(line).or_default();
+                *ran = (*ran).max(count);
+                if let Command::Function(name, _) = command.node {
+                    current.functions.push((name.clone(), line, count));
+                }
+            }
+            pc += 1;
+        }
+
+        Coverage { files }
+    }
+
+    /* adds the counts of other, files with the same name being the same file */
+    pub fn merge(&mut self, other: &Coverage) {
+        for theirs in &other.files {
+            let Some(ours) = self.files.iter_mut().find(|ours| ours.file == theirs.file) else {
+                self.files.push(theirs.clone());
+                continue;
+            };
+            for (line, count) in &theirs.lines {
+                *ours.lines.entry(*line).or_default() += count;
+            }
+            for (name, line, count) in &theirs.functions {
+                match ours.functions.iter_mut().find(|(ours, ..)| ours == name) {
+                    Some((_, _, ours)) => *ours += count,
+                    None => ours.functions.push((name.clone(), *line, *count)),
+                }
+            }
+        }
+    }
+
+    /* lines holding a command and how many of them ran, over every file */
+    pub fn lines(&self) -> (usize, usize) {
+        self.files.iter().fold((0, 0), |(found, hit), file| {
+            let (file_found, file_hit) = file.lines();
+            (found + file_found, hit + file_hit)
+        })
+    }
+
+    /* an lcov tracefile, which genhtml and most editors' coverage views read */
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for file in &self.files {
+            let _ = writeln!(out, "TN:\nSF:{}", file.file);
+            for (name, line, _) in &file.functions {
+                let _ = writeln!(out, "FN:{},{}", line, name);
+            }
+            for (name, _, count) in &file.functions {
+                let _ = writeln!(out, "FNDA:{},{}", count, name);
+            }
+            let entered = file.functions.iter().filter(|(.., count)| *count > 0);
+            let _ = writeln!(out, "FNF:{}", file.functions.len());
+            let _ = writeln!(out, "FNH:{}", entered.count());
+            for (line, count) in &file.lines {
+                let _ = writeln!(out, "DA:{},{}", line, count);
+            }
+            let (found, hit) = file.lines();
+            let _ = writeln!(out, "LF:{}\nLH:{}\nend_of_record", found, hit);
+        }
+
+        out
+    }
+}
+
+impl FileCoverage {
+    pub fn lines(&self) -> (usize, usize) {
+        let hit = self.lines.values().filter(|count| **count > 0).count();
+        (self.lines.len(), hit)
+    }
+
+    /*
+     * The file's source with how often each line ran in front of it, gcov
+     * style. Lines that never ran stand out with #####, those without a
+     * command have nothing:
+     *
+     *         3 | push argument 0
+     *     ##### | goto END
+     *           | // done
+     */
+    pub fn annotate(&self, source: &str) -> String {
+        let mut out = String::new();
+        for (idx, text) in source.lines().enumerate() {
+            let _ = match self.lines.get(&(idx + 1)) {
+                Some(0) => writeln!(out, "{:>9} | {}", "#####", text),
+                Some(count) => writeln!(out, "{:>9} | {}", count, text),
+                None => writeln!(out, "{:>9} | {}", "", text),
+            };
+        }
+
+        out
+    }
+}
+
+/*
+ * file                      lines     run       %
+ * Main.vm                      15      12   80.0%
+ * ...
+ * total                        52      40   76.9%
+ */
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .files
+            .iter()
+            .map(|file| file.file.len())
+            .fold(24, usize::max);
+        let row = |f: &mut fmt::Formatter<'_>, name: &str, (found, hit): (usize, usize)| {
+            let share = hit as f64 * 100.0 / found.max(1) as f64;
+            writeln!(
+                f,
+                "{:<width$} {:>7} {:>7} {:>6.1}%",
+                name, found, hit, share
+            )
+        };
+
+        writeln!(
+            f,
+            "{:<width$} {:>7} {:>7} {:>7}",
+            "file", "lines", "run", "%"
+        )?;
+        for file in &self.files {
+            row(f, &file.file, file.lines())?;
+        }
+        row(f, "total", self.lines())
+    }
+}
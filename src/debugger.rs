@@ -0,0 +1,581 @@
+use core::{fmt, fmt::Write as _, str::FromStr};
+
+use crate::{
+    interpreter::{Interpreter, State},
+    memory::{self, RAM_SIZE},
+    prelude::*,
+    MemorySegment, VmError,
+};
+
+/*
+ * Steps a program on the interpreter a command at a time, stopping where the
+ * user asked to. Commands are read the way hackvm debug takes them:
+ *
+ *   let mut debugger = Debugger::new(vm, 10_000_000);
+ *   debugger.execute(&"break Main.vm:12".parse()?)?;
+ *   let stopped = debugger.execute(&DebugCommand::Continue)?;
+ *
+ * and what they did comes back as text for the prompt to print. Going
+ * backwards takes an interpreter keeping a journal, see Interpreter::record
+ */
+#[derive(Debug, Clone)]
+pub struct Debugger {
+    vm: Interpreter,
+    /* with the command each stops at, numbered from 1 in the order they were set */
+    breakpoints: Vec<(Breakpoint, usize)>,
+    /* numbered the same way, with the address each watches and the value last seen there */
+    watchpoints: Vec<(Watch, u16, u16)>,
+    /* how far continue goes before handing control back */
+    max_steps: u64,
+}
+
+/* Main.vm:12 or a function's name */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    Line { filestem: String, line: usize },
+    Function(String),
+}
+
+/* 300, RAM[300], local 2 or static Foo.3, segments are resolved when it's set */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Watch {
+    Address(u16),
+    Slot(MemorySegment, u16),
+    /* the file's static, the current file's without one */
+    Static(Option<String>, u16),
+}
+
+/* Why stepping stopped before it ran out of steps */
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Hit {
+    Breakpoint(usize),
+    /* the value it had, the command that changed it and whether that was undone */
+    Watchpoint {
+        n: usize,
+        old: u16,
+        pc: usize,
+        function: Option<String>,
+        undone: bool,
+    },
+    /* nothing left in the journal to go back with */
+    JournalStart,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugCommand {
+    Step(u64),
+    Continue,
+    StepBack(u64),
+    ReverseContinue,
+    Break(Breakpoint),
+    /* the breakpoint's number */
+    Delete(usize),
+    Watch(Watch),
+    /* the watchpoint's number */
+    Unwatch(usize),
+    Breakpoints,
+    /* SP and the segment pointers */
+    Registers,
+    /* how many values from the top */
+    Stack(u16),
+    /* the command run next */
+    Where,
+    /* the calls being run, innermost first */
+    Backtrace,
+    Help,
+    Quit,
+}
+
+const HELP: &str = "\
+step [N]          run the next command, or the next N (s)
+continue          run until a breakpoint or the program halts (c)
+step-back [N]     undo the last command, or the last N (sb)
+reverse-continue  undo commands back to a breakpoint or watched change (rc)
+break LOCATION    stop at File.vm:LINE or the start of a function (b)
+delete N          remove breakpoint N
+watch LOCATION    stop when RAM[N], local 2, static Foo.3 and the like changes
+unwatch N         remove watchpoint N
+breakpoints       list the breakpoints and watchpoints
+registers         show SP, LCL, ARG, THIS and THAT (r)
+stack [N]         show the top N values of the stack, 8 by default
+where             show the command run next (w)
+backtrace         show the calls being run with their arguments (bt)
+quit              stop debugging (q)
+";
+
+impl Debugger {
+    /* vm should be started, continue gives up after max_steps commands */
+    pub fn new(vm: Interpreter, max_steps: u64) -> Self {
+        Debugger {
+            vm,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            max_steps,
+        }
+    }
+
+    pub fn vm(&self) -> &Interpreter {
+        &self.vm
+    }
+
+    /*
+     * Runs a command and returns what to show for it. A command that fails
+     * leaves the program on it, to be looked at before going on. Quit is left
+     * to the caller and does nothing
+     */
+    pub fn execute(&mut self, command: &DebugCommand) -> Result<String, VmError> {
+        let mut out = String::new();
+        match command {
+            DebugCommand::Step(count) => {
+                let hit = self.resume(*count)?;
+                self.stopped(&mut out, hit);
+            }
+            DebugCommand::Continue => {
+                let hit = self.resume(self.max_steps)?;
+                self.stopped(&mut out, hit);
+            }
+            DebugCommand::StepBack(count) => {
+                let hit = self.rewind(*count);
+                self.stopped(&mut out, hit);
+            }
+            DebugCommand::ReverseContinue => {
+                let hit = self.rewind(self.max_steps);
+                self.stopped(&mut out, hit);
+            }
+            DebugCommand::Break(breakpoint) => {
+                let pc = match breakpoint {
+                    Breakpoint::Line { filestem, line } => self.vm.find_line(filestem, *line),
+                    Breakpoint::Function(name) => self.vm.function_start(name),
+                };
+                match pc {
+                    Some(pc) => {
+                        self.breakpoints.push((breakpoint.clone(), pc));
+                        let _ = write!(out, "Breakpoint {} at ", self.breakpoints.len());
+                        self.command(&mut out, pc);
+                    }
+                    None => {
+                        let _ = writeln!(out, "No command at {}", breakpoint);
+                    }
+                }
+            }
+            DebugCommand::Delete(n) => match n.checked_sub(1) {
+                Some(idx) if idx < self.breakpoints.len() => {
+                    let (breakpoint, _) = self.breakpoints.remove(idx);
+                    let _ = writeln!(out, "Deleted breakpoint {}, {}", n, breakpoint);
+                }
+                _ => {
+                    let _ = writeln!(out, "No breakpoint {}", n);
+                }
+            },
+            DebugCommand::Watch(watch) => match self.address(watch) {
+                Ok(address) => {
+                    // named after the file it's in for when another one runs
+                    let watch = match watch {
+                        Watch::Static(None, index) => {
+                            let current = self.vm.command_at(self.vm.pc());
+                            let filestem = current.map(|(filestem, _)| filestem.to_owned());
+                            Watch::Static(filestem, *index)
+                        }
+                        watch => watch.clone(),
+                    };
+                    let value = self.vm.ram(address);
+                    self.watchpoints.push((watch.clone(), address, value));
+                    let _ = writeln!(
+                        out,
+                        "Watchpoint {} on {}, RAM[{}] = {}",
+                        self.watchpoints.len(),
+                        watch,
+                        address,
+                        value as i16
+                    );
+                }
+                Err(err) => {
+                    let _ = writeln!(out, "{}", err);
+                }
+            },
+            DebugCommand::Unwatch(n) => match n.checked_sub(1) {
+                Some(idx) if idx < self.watchpoints.len() => {
+                    let (watch, _, _) = self.watchpoints.remove(idx);
+                    let _ = writeln!(out, "Deleted watchpoint {}, {}", n, watch);
+                }
+                _ => {
+                    let _ = writeln!(out, "No watchpoint {}", n);
+                }
+            },
+            DebugCommand::Breakpoints => {
+                if self.breakpoints.is_empty() && self.watchpoints.is_empty() {
+                    out.push_str("No breakpoints\n");
+                }
+                for (n, (breakpoint, pc)) in self.breakpoints.iter().enumerate() {
+                    let _ = write!(out, "Breakpoint {}  {}  ", n + 1, breakpoint);
+                    self.command(&mut out, *pc);
+                }
+                for (n, (watch, address, value)) in self.watchpoints.iter().enumerate() {
+                    let _ = writeln!(
+                        out,
+                        "Watchpoint {}  {}  RAM[{}] = {}",
+                        n + 1,
+                        watch,
+                        address,
+                        *value as i16
+                    );
+                }
+            }
+            DebugCommand::Registers => {
+                let frame = self.vm.frame();
+                let _ = writeln!(
+                    out,
+                    "SP {}  LCL {}  ARG {}  THIS {}  THAT {}",
+                    self.vm.sp(),
+                    frame.lcl,
+                    frame.arg,
+                    frame.this,
+                    frame.that
+                );
+            }
+            DebugCommand::Stack(count) => {
+                let sp = self.vm.sp();
+                let bottom = sp.saturating_sub(*count).max(memory::STACK_BASE);
+                if sp <= bottom {
+                    out.push_str("The stack is empty\n");
+                }
+                for address in (bottom..sp).rev() {
+                    let _ = writeln!(out, "RAM[{}] = {}", address, self.vm.ram(address) as i16);
+                }
+            }
+            DebugCommand::Where => self.stopped(&mut out, None),
+            DebugCommand::Backtrace => self.backtrace(&mut out),
+            DebugCommand::Help => out.push_str(HELP),
+            DebugCommand::Quit => {}
+        }
+
+        Ok(out)
+    }
+
+    /* runs up to steps commands, stopping early at a breakpoint, a watched change or a halt */
+    fn resume(&mut self, steps: u64) -> Result<Option<Hit>, VmError> {
+        for _ in 0..steps {
+            let pc = self.vm.pc();
+            let function = self.vm.calls().last().map(|call| call.function.clone());
+            if self.vm.step()? == State::Halted {
+                return Ok(None);
+            }
+            if let Some(hit) = self.check(pc, function, false) {
+                return Ok(Some(hit));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /* resume backwards, undoing up to steps commands */
+    fn rewind(&mut self, steps: u64) -> Option<Hit> {
+        for _ in 0..steps {
+            if !self.vm.step_back() {
+                return Some(Hit::JournalStart);
+            }
+            // the command undone is the one run next now
+            let function = self.vm.calls().last().map(|call| call.function.clone());
+            if let Some(hit) = self.check(self.vm.pc(), function, true) {
+                return Some(hit);
+            }
+        }
+
+        None
+    }
+
+    /*
+     * What stops the program after the command at pc in function ran or was
+     * undone. Every watched value is brought up to date, the first that
+     * changed is reported
+     */
+    fn check(&mut self, pc: usize, function: Option<String>, undone: bool) -> Option<Hit> {
+        let mut changed = None;
+        for (n, (_, address, value)) in self.watchpoints.iter_mut().enumerate() {
+            let now = self.vm.ram(*address);
+            if now != *value {
+                changed.get_or_insert(Hit::Watchpoint {
+                    n: n + 1,
+                    old: *value,
+                    pc,
+                    function: function.clone(),
+                    undone,
+                });
+                *value = now;
+            }
+        }
+        if changed.is_some() {
+            return changed;
+        }
+
+        let pc = self.vm.pc();
+        let n = self.breakpoints.iter().position(|(_, at)| *at == pc)?;
+        Some(Hit::Breakpoint(n + 1))
+    }
+
+    fn stopped(&self, out: &mut String, hit: Option<Hit>) {
+        match hit {
+            Some(Hit::Breakpoint(n)) => {
+                let _ = write!(out, "Breakpoint {}, ", n);
+            }
+            // Watchpoint 1, static Foo.3 RAM[17] 3 -> 4 by pop static 3 in Foo.bar at Foo.vm:12
+            Some(Hit::Watchpoint {
+                n,
+                old,
+                pc,
+                function,
+                undone,
+            }) => {
+                let (watch, address, new) = &self.watchpoints[n - 1];
+                let _ = write!(
+                    out,
+                    "Watchpoint {}, {} RAM[{}] {} -> {} by ",
+                    n, watch, address, old as i16, *new as i16
+                );
+                if undone {
+                    out.push_str("undoing ");
+                }
+                if let Some((_, command)) = self.vm.command_at(pc) {
+                    let _ = write!(out, "{} ", command.node);
+                }
+                let _ = write!(
+                    out,
+                    "in {} at ",
+                    function.as_deref().unwrap_or("no function")
+                );
+                self.location(out, pc);
+                out.push('\n');
+            }
+            Some(Hit::JournalStart) => {
+                let _ = writeln!(out, "No earlier commands recorded");
+            }
+            None => {}
+        }
+
+        if self.vm.state() == State::Halted {
+            let _ = writeln!(out, "Halted after {} commands", self.vm.steps());
+            return;
+        }
+        self.command(out, self.vm.pc());
+    }
+
+    /* where a watch looks, segments under the frame and file run now */
+    fn address(&self, watch: &Watch) -> Result<u16, String> {
+        let address = match watch {
+            Watch::Address(address) => Some(*address),
+            Watch::Slot(segment, index) => segment.address_of(*index, &self.vm.frame()),
+            Watch::Static(filestem, index) => {
+                let current = self
+                    .vm
+                    .command_at(self.vm.pc())
+                    .map(|(filestem, _)| filestem);
+                let filestem = filestem.as_deref().or(current).unwrap_or_default();
+                self.vm.statics().address(filestem, *index)
+            }
+        };
+
+        match watch {
+            Watch::Static(..) if address.is_none() => Err(format!("No file uses {}", watch)),
+            _ => address
+                .filter(|address| usize::from(*address) < RAM_SIZE)
+                .ok_or_else(|| format!("{} is not in RAM", watch)),
+        }
+    }
+
+    /*
+     * A line for each call, the innermost at the command run next and the
+     * others at the call they're in, each with its arguments as they are now:
+     *
+     *   #0  Main.fibonacci(3)  at Main.vm:15  ARG 261  LCL 267
+     *   #1  Main.main()  at Main.vm:4  ARG 256  LCL 261
+     */
+    fn backtrace(&self, out: &mut String) {
+        let calls = self.vm.calls();
+        if calls.is_empty() {
+            let _ = write!(out, "#0  no function  at ");
+            self.location(out, self.vm.pc());
+            out.push('\n');
+        }
+
+        let mut pc = self.vm.pc();
+        for (n, call) in calls.iter().rev().enumerate() {
+            let args: Vec<String> = (0..call.n_args)
+                .map(|idx| (self.vm.ram(call.arg.wrapping_add(idx)) as i16).to_string())
+                .collect();
+            let _ = write!(out, "#{}  {}({})  at ", n, call.function, args.join(", "));
+            self.location(out, pc);
+            let _ = writeln!(out, "  ARG {}  LCL {}", call.arg, call.lcl);
+            // the caller is at the call, the command before the one returned to
+            pc = call.return_to.unwrap_or_default().saturating_sub(1);
+        }
+    }
+
+    fn location(&self, out: &mut String, pc: usize) {
+        match self.vm.command_at(pc) {
+            Some((filestem, command)) => {
+                let _ = write!(out, "{}.vm:{}", filestem, command.span.line);
+            }
+            None => out.push_str("the end"),
+        }
+    }
+
+    // Main.vm:12  push local 0
+    fn command(&self, out: &mut String, pc: usize) {
+        if let Some((filestem, command)) = self.vm.command_at(pc) {
+            let _ = writeln!(
+                out,
+                "{}.vm:{}  {}",
+                filestem, command.span.line, command.node
+            );
+        }
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((file, line)) = s.split_once(':') else {
+            return Ok(Breakpoint::Function(s.to_owned()));
+        };
+
+        let filestem = file.strip_suffix(".vm").unwrap_or(file);
+        // a path works too, files are told apart by their stem
+        let filestem = filestem.rsplit(['/', '\\']).next().unwrap_or(filestem);
+        let line = line
+            .parse()
+            .ok()
+            .filter(|line| *line > 0)
+            .ok_or_else(|| format!("Invalid line {} in {}", line, s))?;
+
+        Ok(Breakpoint::Line {
+            filestem: filestem.to_owned(),
+            line,
+        })
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Line { filestem, line } => write!(f, "{}.vm:{}", filestem, line),
+            Breakpoint::Function(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl FromStr for Watch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid watch location {}, try 300, RAM[300], local 2 or static Foo.3",
+                s
+            )
+        };
+        let Some((segment, index)) = s.split_once(char::is_whitespace) else {
+            let address = s
+                .strip_prefix("RAM[")
+                .and_then(|s| s.strip_suffix(']'))
+                .unwrap_or(s);
+            return address.parse().map(Watch::Address).map_err(|_| invalid());
+        };
+
+        let index = index.trim();
+        let segment: MemorySegment = segment.parse().map_err(|_| invalid())?;
+        if segment == MemorySegment::Static {
+            if let Some((filestem, index)) = index.rsplit_once('.') {
+                let index = index.parse().map_err(|_| invalid())?;
+                return Ok(Watch::Static(Some(filestem.to_owned()), index));
+            }
+            return Ok(Watch::Static(None, index.parse().map_err(|_| invalid())?));
+        }
+        if segment == MemorySegment::Constant {
+            return Err(invalid());
+        }
+
+        Ok(Watch::Slot(segment, index.parse().map_err(|_| invalid())?))
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Watch::Address(address) => write!(f, "RAM[{}]", address),
+            Watch::Slot(segment, index) => write!(f, "{} {}", segment, index),
+            Watch::Static(Some(filestem), index) => write!(f, "static {}.{}", filestem, index),
+            Watch::Static(None, index) => write!(f, "static {}", index),
+        }
+    }
+}
+
+fn count<T: FromStr>(arg: Option<&str>, default: T) -> Result<T, String> {
+    match arg {
+        Some(n) => n.parse().map_err(|_| format!("Invalid count {}", n)),
+        None => Ok(default),
+    }
+}
+
+// the first word names the command, gdb's one letter short forms included
+impl FromStr for DebugCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        // the location is a word or two, taken whole
+        if name == "watch" {
+            return Ok(DebugCommand::Watch(rest.trim().parse()?));
+        }
+
+        let mut words = rest.split_whitespace();
+        let arg = words.next();
+        if let Some(extra) = words.next() {
+            return Err(format!("Unexpected {} after {}", extra, name));
+        }
+        let command = match name {
+            "step" | "s" => DebugCommand::Step(count(arg, 1)?),
+            "continue" | "c" => DebugCommand::Continue,
+            "step-back" | "sb" => DebugCommand::StepBack(count(arg, 1)?),
+            "reverse-continue" | "rc" => DebugCommand::ReverseContinue,
+            "break" | "b" => {
+                let location = arg.ok_or_else(|| "break needs a location".to_owned())?;
+                DebugCommand::Break(location.parse()?)
+            }
+            "delete" | "d" => {
+                let n = arg.ok_or_else(|| "delete needs a breakpoint number".to_owned())?;
+                DebugCommand::Delete(n.parse().map_err(|_| format!("Invalid breakpoint {}", n))?)
+            }
+            "unwatch" => {
+                let n = arg.ok_or_else(|| "unwatch needs a watchpoint number".to_owned())?;
+                DebugCommand::Unwatch(n.parse().map_err(|_| format!("Invalid watchpoint {}", n))?)
+            }
+            "breakpoints" => DebugCommand::Breakpoints,
+            "registers" | "r" => DebugCommand::Registers,
+            "stack" => DebugCommand::Stack(count(arg, 8)?),
+            "where" | "w" => DebugCommand::Where,
+            "backtrace" | "bt" => DebugCommand::Backtrace,
+            "help" | "h" => DebugCommand::Help,
+            "quit" | "q" => DebugCommand::Quit,
+            _ => return Err(format!("Unknown command {}, try help", name)),
+        };
+        if arg.is_some()
+            && matches!(
+                command,
+                DebugCommand::Continue
+                    | DebugCommand::ReverseContinue
+                    | DebugCommand::Breakpoints
+                    | DebugCommand::Registers
+                    | DebugCommand::Where
+                    | DebugCommand::Backtrace
+                    | DebugCommand::Help
+                    | DebugCommand::Quit
+            )
+        {
+            return Err(format!("{} takes no arguments", name));
+        }
+
+        Ok(command)
+    }
+}
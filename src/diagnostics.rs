@@ -0,0 +1,301 @@
+use core::{
+    fmt::{self, Write},
+    str::FromStr,
+};
+
+use crate::{prelude::*, Span, VmError};
+
+const KEYWORDS: [&str; 17] = [
+    "push", "pop", "add", "sub", "neg", "not", "or", "and", "eq", "lt", "gt", "label", "goto",
+    "if-goto", "function", "call", "return",
+];
+
+const SEGMENTS: [&str; 8] = [
+    "local", "argument", "this", "that", "constant", "static", "temp", "pointer",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiagnosticFormat {
+    #[default]
+    Human,
+    /* one JSON object per line */
+    Json,
+}
+
+impl FromStr for DiagnosticFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(DiagnosticFormat::Human),
+            "json" => Ok(DiagnosticFormat::Json),
+            _ => Err(format!(
+                "Unknown diagnostics format {} (expected human or json)",
+                s
+            )),
+        }
+    }
+}
+
+// codes are &'static str, so diagnostics can only be written out
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /* stable identifier of the kind of problem, e.g. E0001 */
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span: None,
+            help: None,
+        }
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(code, message)
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn from_error(err: &VmError) -> Self {
+        let kind = err.kind();
+        let mut diagnostic = Diagnostic::error(kind.code(), kind.to_string());
+        diagnostic.span = err.span();
+        diagnostic.help = match kind {
+            VmError::UnknownCommand(command) => suggest(command, &KEYWORDS),
+            VmError::BadSegment { segment, .. } if segment == "constant" => {
+                Some("constants can only be pushed".to_owned())
+            }
+            VmError::BadSegment { segment, .. } => suggest(segment, &SEGMENTS),
+            VmError::MissingEntry(_) => Some(
+                "define it or pass --no-bootstrap for programs that set up the stack themselves"
+                    .to_owned(),
+            ),
+            VmError::TrailingTokens { .. } => {
+                Some("remove the extra tokens or comment them out with `//`".to_owned())
+            }
+            VmError::RomOverflow { largest, .. } => {
+                let largest: Vec<String> = largest
+                    .iter()
+                    .map(|(function, size)| format!("{} ({})", function, size))
+                    .collect();
+                Some(format!("the largest functions are {}", largest.join(", ")))
+            }
+            VmError::StackOverflow { calls, .. } => {
+                Some(format!("the deepest calls are {}", calls.join(" -> ")))
+            }
+            VmError::ConstantOutOfRange(_) => {
+                Some("larger values have to be computed, e.g. with `add` or `neg`".to_owned())
+            }
+            _ => None,
+        };
+
+        diagnostic
+    }
+
+    pub fn from_warning(err: &VmError) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::from_error(err)
+        }
+    }
+
+    /*
+     * Renders the diagnostic rustc style:
+     *
+     * error[E0002]: Invalid memory segment arguments: unknown memory segment
+     *  --> Main.vm:2:6
+     *   |
+     * 2 | push arguments 2
+     *   |      ^^^^^^^^^
+     *   |
+     *   = help: did you mean `argument`?
+     */
+    pub fn render(&self, filename: &str, source: &str) -> String {
+        let mut out = format!("{}[{}]: {}\n", self.severity, self.code, self.message);
+
+        let Some(span) = self.span else {
+            let _ = writeln!(out, " --> {}", filename);
+            if let Some(help) = &self.help {
+                let _ = writeln!(out, "  = help: {}", help);
+            }
+            return out;
+        };
+
+        let gutter = " ".repeat(span.line.to_string().len());
+        let _ = writeln!(
+            out,
+            "{}--> {}:{}:{}",
+            gutter, filename, span.line, span.column
+        );
+        let _ = writeln!(out, "{} |", gutter);
+
+        if let Some(line) = source_line(source, span) {
+            let carets = source
+                .get(span.start..span.end.min(span.start + line.len()))
+                .map_or(0, |text| text.chars().count())
+                .max(1);
+            let _ = writeln!(out, "{} | {}", span.line, line.trim_end());
+            let _ = writeln!(
+                out,
+                "{} | {}{}",
+                gutter,
+                " ".repeat(span.column - 1),
+                "^".repeat(carets)
+            );
+        }
+
+        if let Some(help) = &self.help {
+            let _ = writeln!(out, "{} |", gutter);
+            let _ = writeln!(out, "{} = help: {}", gutter, help);
+        }
+
+        out
+    }
+
+    /* {"file":..,"line":..,"column":..,"code":..,"severity":..,"message":..,"help":..} */
+    pub fn to_json(&self, filename: &str) -> String {
+        let (line, column) = match self.span {
+            Some(span) => (span.line.to_string(), span.column.to_string()),
+            None => ("null".to_owned(), "null".to_owned()),
+        };
+        let help = match &self.help {
+            Some(help) => json_string(help),
+            None => "null".to_owned(),
+        };
+
+        format!(
+            "{{\"file\":{},\"line\":{},\"column\":{},\"code\":{},\"severity\":{},\"message\":{},\"help\":{}}}",
+            json_string(filename),
+            line,
+            column,
+            json_string(self.code),
+            json_string(&self.severity.to_string()),
+            json_string(&self.message),
+            help
+        )
+    }
+
+    pub fn emit(&self, format: DiagnosticFormat, filename: &str, source: &str) -> String {
+        match format {
+            DiagnosticFormat::Human => self.render(filename, source),
+            DiagnosticFormat::Json => format!("{}\n", self.to_json(filename)),
+        }
+    }
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+
+    out
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(
+                f,
+                "{}[{}] at {}: {}",
+                self.severity, self.code, span, self.message
+            ),
+            None => write!(f, "{}[{}]: {}", self.severity, self.code, self.message),
+        }
+    }
+}
+
+// text of the line the span starts on, without its terminator
+fn source_line(source: &str, span: Span) -> Option<&str> {
+    if span.start > source.len() {
+        return None;
+    }
+
+    let start = source[..span.start].rfind('\n').map_or(0, |idx| idx + 1);
+    let end = source[span.start..]
+        .find('\n')
+        .map_or(source.len(), |idx| span.start + idx);
+
+    Some(source[start..end].trim_end_matches('\r'))
+}
+
+/* closest candidate within a couple of edits, as a "did you mean" help */
+pub fn suggest(word: &str, candidates: &[&str]) -> Option<String> {
+    let max_distance = (word.len() / 3).max(1);
+
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| format!("did you mean `{}`?", candidate))
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+
+    prev[b.len()]
+}
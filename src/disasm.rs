@@ -0,0 +1,71 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    assembler::{self, AsmError},
+    hack::{at, label, Address, HackInstr},
+    prelude::*,
+};
+
+/*
+ * Turns machine code back into assembly. Symbols are gone by then, so labels
+ * are guessed: an A-instruction right before a jump loads its target, which
+ * gets a label named after its ROM address
+ *
+ *   @4            @ROM4
+ *   0;JMP   ->    0;JMP
+ *   ...           (ROM4)
+ *
+ * Return addresses and anything else only loaded to be jumped to later stay
+ * plain numbers, as do all RAM addresses
+ */
+pub fn disassemble(words: &[u16]) -> Result<Vec<HackInstr>, AsmError> {
+    let mut instrs = Vec::with_capacity(words.len());
+    for (rom, word) in words.iter().enumerate() {
+        let instr = assembler::decode(*word).map_err(|message| AsmError {
+            line: 0,
+            message: format!("ROM {}: {}", rom, message),
+        })?;
+        instrs.push(instr);
+    }
+
+    // where each jump target is loaded, a constant elsewhere stays a number
+    let loads = jump_loads(&instrs);
+    let targets: BTreeSet<usize> = loads.values().copied().collect();
+
+    let mut asm = Vec::with_capacity(instrs.len() + targets.len());
+    for (rom, instr) in instrs.into_iter().enumerate() {
+        if targets.contains(&rom) {
+            asm.push(label(label_name(rom)));
+        }
+        match loads.get(&rom) {
+            Some(target) => asm.push(at(label_name(*target))),
+            None => asm.push(instr),
+        }
+    }
+    // a jump to just past the last instruction, e.g. to stop
+    if targets.contains(&words.len()) {
+        asm.push(label(label_name(words.len())));
+    }
+
+    Ok(asm)
+}
+
+// the ROM address of every A-instruction right before a jump and what it loads
+fn jump_loads(instrs: &[HackInstr]) -> BTreeMap<usize, usize> {
+    let mut loads = BTreeMap::new();
+    for (rom, pair) in instrs.windows(2).enumerate() {
+        if let [HackInstr::AInstr(Address::Value(target)), HackInstr::CInstr { jump: Some(_), .. }] =
+            pair
+        {
+            if usize::from(*target) <= instrs.len() {
+                loads.insert(rom, usize::from(*target));
+            }
+        }
+    }
+
+    loads
+}
+
+fn label_name(rom: usize) -> String {
+    format!("ROM{}", rom)
+}
@@ -0,0 +1,415 @@
+use core::{fmt, fmt::Write as _};
+
+use crate::{
+    assembler::{self, AsmError},
+    interpreter::State,
+    keyboard::Key,
+    memory::{self, RAM_SIZE},
+    prelude::*,
+    VmError,
+};
+
+/*
+ * Runs Hack machine code the way the CPU chip does: fetch the word at PC,
+ * decode it, then compute, store and jump in one cycle. C-instructions go
+ * through the ALU bit by bit, so any comp bits work, named in the assembly
+ * or not, and the two unused bits are ignored like the hardware does
+ *
+ *   let mut cpu = Emulator::from_asm(&asm)?;
+ *   cpu.run_steps(1_000_000)?;
+ *   cpu.assert_ram(&[(0, 257), (256, -1)])?;
+ *
+ * A program halts by running off the end of its ROM or by going into the
+ * usual (END) @END 0;JMP loop, which is noticed after its first round
+ */
+#[derive(Debug, Clone)]
+pub struct Emulator {
+    rom: Vec<u16>,
+    ram: Vec<u16>,
+    a: u16,
+    d: u16,
+    pc: usize,
+    steps: u64,
+    state: State,
+    /* how often each ROM address ran, empty unless count_hits was called */
+    hits: Vec<u64>,
+    /* RAM address the last instruction wrote */
+    last_write: Option<u16>,
+    screen_writes: u64,
+}
+
+/*
+ * Everything the emulator holds, to pick up from later or run several ways
+ * from one warmed-up state. encode writes it as text a file can keep
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    rom: Vec<u16>,
+    ram: Vec<u16>,
+    a: u16,
+    d: u16,
+    pc: usize,
+    steps: u64,
+    state: State,
+}
+
+/* What assert_ram found, every address not holding what was expected */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RamMismatch {
+    /* address, expected and actual value, signed as the program sees them */
+    pub differences: Vec<(u16, i16, i16)>,
+}
+
+impl Emulator {
+    pub fn new(rom: Vec<u16>) -> Self {
+        Emulator {
+            rom,
+            ram: vec![0; RAM_SIZE],
+            a: 0,
+            d: 0,
+            pc: 0,
+            steps: 0,
+            state: State::Running,
+            hits: Vec::new(),
+            last_write: None,
+            screen_writes: 0,
+        }
+    }
+
+    pub fn from_asm(asm: &str) -> Result<Self, AsmError> {
+        Ok(Emulator::new(assembler::assemble(asm)?))
+    }
+
+    /* runs the instruction at PC, leaving PC on it if it fails */
+    pub fn step(&mut self) -> Result<State, VmError> {
+        if self.state == State::Halted {
+            return Ok(State::Halted);
+        }
+        let Some(&word) = self.rom.get(self.pc) else {
+            self.state = State::Halted;
+            return Ok(State::Halted);
+        };
+
+        self.steps += 1;
+        self.last_write = None;
+        if let Some(hits) = self.hits.get_mut(self.pc) {
+            *hits += 1;
+        }
+        if word & 0x8000 == 0 {
+            self.a = word;
+            self.pc += 1;
+        } else {
+            self.execute(word)?;
+        }
+        if self.pc >= self.rom.len() {
+            self.state = State::Halted;
+        }
+
+        Ok(self.state)
+    }
+
+    /* runs until the program halts or max_steps instructions have run */
+    pub fn run_steps(&mut self, max_steps: u64) -> Result<State, VmError> {
+        for _ in 0..max_steps {
+            if self.step()? == State::Halted {
+                break;
+            }
+        }
+
+        Ok(self.state)
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /* instructions run so far */
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn a(&self) -> u16 {
+        self.a
+    }
+
+    pub fn d(&self) -> u16 {
+        self.d
+    }
+
+    pub fn rom(&self) -> &[u16] {
+        &self.rom
+    }
+
+    pub fn ram(&self, address: u16) -> u16 {
+        self.ram
+            .get(usize::from(address))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_ram(&mut self, address: u16, value: u16) {
+        if let Some(slot) = self.ram.get_mut(usize::from(address)) {
+            *slot = value;
+        }
+    }
+
+    /* checks RAM holds the values given, as signed as the program sees them */
+    pub fn assert_ram(&self, expected: &[(u16, i16)]) -> Result<(), RamMismatch> {
+        let differences: Vec<_> = expected
+            .iter()
+            .map(|(address, value)| (*address, *value, self.ram(*address) as i16))
+            .filter(|(_, expected, actual)| expected != actual)
+            .collect();
+
+        if differences.is_empty() {
+            return Ok(());
+        }
+        Err(RamMismatch { differences })
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.ram(memory::SP)
+    }
+
+    pub fn screen_writes(&self) -> u64 {
+        self.screen_writes
+    }
+
+    /* where the last instruction stored M, if it did */
+    pub fn last_write(&self) -> Option<u16> {
+        self.last_write
+    }
+
+    /* counts how often each instruction runs from here on, for profiling and coverage */
+    pub fn count_hits(&mut self) {
+        self.hits.resize(self.rom.len(), 0);
+    }
+
+    /* by ROM address, empty unless count_hits was called */
+    pub fn hits(&self) -> &[u64] {
+        &self.hits
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            rom: self.rom.clone(),
+            ram: self.ram.clone(),
+            a: self.a,
+            d: self.d,
+            pc: self.pc,
+            steps: self.steps,
+            state: self.state,
+        }
+    }
+
+    /* puts everything back as it was, the ROM included */
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.rom.clone_from(&snapshot.rom);
+        self.ram.clone_from(&snapshot.ram);
+        self.a = snapshot.a;
+        self.d = snapshot.d;
+        self.pc = snapshot.pc;
+        self.steps = snapshot.steps;
+        self.state = snapshot.state;
+        self.last_write = None;
+        if !self.hits.is_empty() {
+            self.hits.resize(self.rom.len(), 0);
+        }
+    }
+
+    pub fn press(&mut self, key: Key) {
+        self.set_ram(memory::KBD, key.code());
+    }
+
+    pub fn release(&mut self) {
+        self.set_ram(memory::KBD, 0);
+    }
+
+    pub fn screen(&self) -> &[u16] {
+        let start = usize::from(memory::SCREEN);
+        &self.ram[start..start + usize::from(memory::SCREEN_SIZE)]
+    }
+
+    fn execute(&mut self, word: u16) -> Result<(), VmError> {
+        // the a bit picks M over A as the ALU's second input
+        let y = match word & 0x1000 != 0 {
+            true => self.ram[self.m_address()?],
+            false => self.a,
+        };
+        let out = alu(self.d, y, word >> 6);
+
+        // everything is stored at the end of the cycle, M and the jump see the old A
+        let address = self.a;
+        if word & 0b001000 != 0 {
+            let slot = self.m_address()?;
+            self.ram[slot] = out;
+            self.last_write = Some(address);
+            if memory::is_screen(address) {
+                self.screen_writes += 1;
+            }
+        }
+        if word & 0b100000 != 0 {
+            self.a = out;
+        }
+        if word & 0b010000 != 0 {
+            self.d = out;
+        }
+
+        let out = out as i16;
+        let taken = (word & 0b100 != 0 && out < 0)
+            || (word & 0b010 != 0 && out == 0)
+            || (word & 0b001 != 0 && out > 0);
+        if !taken {
+            self.pc += 1;
+            return Ok(());
+        }
+
+        let target = usize::from(address);
+        // nothing changes from one round of the loop to the next, it's how programs stop
+        let spins = target == self.pc || (target + 1 == self.pc && self.rom[target] == address);
+        if spins && word & 0b111000 == 0 {
+            self.state = State::Halted;
+        }
+        self.pc = target;
+
+        Ok(())
+    }
+
+    fn m_address(&self) -> Result<usize, VmError> {
+        match usize::from(self.a) {
+            address if address < RAM_SIZE => Ok(address),
+            address => Err(VmError::BadAddress(address as u32)),
+        }
+    }
+}
+
+/* The Hack ALU, bits are c1 to c6 from the top: zx nx zy ny f no */
+fn alu(x: u16, y: u16, bits: u16) -> u16 {
+    let x = if bits & 0b100000 != 0 { 0 } else { x };
+    let x = if bits & 0b010000 != 0 { !x } else { x };
+    let y = if bits & 0b001000 != 0 { 0 } else { y };
+    let y = if bits & 0b000100 != 0 { !y } else { y };
+    let out = if bits & 0b000010 != 0 {
+        x.wrapping_add(y)
+    } else {
+        x & y
+    };
+
+    if bits & 0b000001 != 0 {
+        !out
+    } else {
+        out
+    }
+}
+
+impl Snapshot {
+    pub fn rom(&self) -> &[u16] {
+        &self.rom
+    }
+
+    /* instructions run when it was taken */
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /*
+     * The registers a line each, then the ROM 16 words a line and only the RAM
+     * words that aren't 0, all in hex:
+     *
+     *   hackvm-snapshot 1
+     *   a\t0100
+     *   ...
+     *   rom\t0100 EC10 0000 E308 ...
+     *   ram\t0000\t0106
+     */
+    pub fn encode(&self) -> String {
+        let mut out = String::from("hackvm-snapshot 1\n");
+        let _ = writeln!(out, "a\t{:04X}", self.a);
+        let _ = writeln!(out, "d\t{:04X}", self.d);
+        let _ = writeln!(out, "pc\t{}", self.pc);
+        let _ = writeln!(out, "steps\t{}", self.steps);
+        let _ = writeln!(out, "halted\t{}", self.state == State::Halted);
+        for words in self.rom.chunks(16) {
+            out.push_str("rom\t");
+            for (n, word) in words.iter().enumerate() {
+                if n > 0 {
+                    out.push(' ');
+                }
+                let _ = write!(out, "{:04X}", word);
+            }
+            out.push('\n');
+        }
+        for (address, value) in self.ram.iter().enumerate() {
+            if *value != 0 {
+                let _ = writeln!(out, "ram\t{:04X}\t{:04X}", address, value);
+            }
+        }
+
+        out
+    }
+
+    /* reads back what encode wrote, None if it is malformed */
+    pub fn decode(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        if lines.next()? != "hackvm-snapshot 1" {
+            return None;
+        }
+        let hex = |s: &str| u16::from_str_radix(s, 16).ok();
+
+        let mut snapshot = Snapshot {
+            rom: Vec::new(),
+            ram: vec![0; RAM_SIZE],
+            a: 0,
+            d: 0,
+            pc: 0,
+            steps: 0,
+            state: State::Running,
+        };
+        for line in lines {
+            let mut fields = line.split('\t');
+            let (key, value) = (fields.next()?, fields.next()?);
+            match key {
+                "a" => snapshot.a = hex(value)?,
+                "d" => snapshot.d = hex(value)?,
+                "pc" => snapshot.pc = value.parse().ok()?,
+                "steps" => snapshot.steps = value.parse().ok()?,
+                "halted" => {
+                    if value.parse().ok()? {
+                        snapshot.state = State::Halted;
+                    }
+                }
+                "rom" => {
+                    for word in value.split(' ') {
+                        snapshot.rom.push(hex(word)?);
+                    }
+                }
+                "ram" => {
+                    let slot = snapshot.ram.get_mut(usize::from(hex(value)?))?;
+                    *slot = hex(fields.next()?)?;
+                }
+                _ => return None,
+            }
+        }
+
+        Some(snapshot)
+    }
+}
+
+impl fmt::Display for RamMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (n, (address, expected, actual)) in self.differences.iter().enumerate() {
+            if n > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "RAM[{}] = {}, expected {}", address, actual, expected)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl core::error::Error for RamMismatch {}
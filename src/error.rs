@@ -0,0 +1,342 @@
+use core::{error::Error, fmt};
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::{memory, prelude::*, Command, MemorySegment, Span};
+
+#[derive(Debug)]
+pub enum VmError {
+    UnknownCommand(String),
+    /* unknown segment name or a segment the command can't operate on */
+    BadSegment {
+        segment: String,
+        reason: String,
+    },
+    OffsetOutOfRange {
+        segment: MemorySegment,
+        offset: u16,
+        capacity: u16,
+        /* the push / pop that triggered it */
+        command: Command,
+    },
+    MalformedLine {
+        line: String,
+        reason: String,
+    },
+    /* push constant value that doesn't fit in an A-instruction */
+    ConstantOutOfRange(String),
+    /* label or function name with characters the spec doesn't allow */
+    BadIdentifier {
+        name: String,
+        reason: String,
+    },
+    /* label declared twice in the same function */
+    DuplicateLabel {
+        label: String,
+        function: Option<String>,
+    },
+    /* goto / if-goto target never declared in the function */
+    UnresolvedLabel {
+        label: String,
+        function: Option<String>,
+    },
+    /* call target not declared anywhere in the program */
+    UndefinedFunction(String),
+    /* call passing a different number of arguments than another call to the same function */
+    InconsistentArity {
+        function: String,
+        args: u16,
+        other: u16,
+    },
+    /* argument access past what any caller passes */
+    ArgumentOverread {
+        function: String,
+        offset: u16,
+        max_args: u16,
+    },
+    /* function body that runs off its end */
+    MissingReturn(String),
+    /* dead commands following a return / goto */
+    UnreachableCode {
+        after: String,
+        first_line: usize,
+        last_line: usize,
+    },
+    /* static variable past the 240 slots shared by every file */
+    StaticOverflow(String),
+    /* bootstrap entry point that no input defines */
+    MissingEntry(String),
+    /* deepest the stack can get running into the region next to it */
+    StackOverflow {
+        /* None when a loop keeps pushing */
+        words: Option<u32>,
+        stack_base: u16,
+        region: &'static str,
+        calls: Vec<String>,
+    },
+    /* a running program reaching past the end of RAM */
+    BadAddress(u32),
+    /* more instructions than the 32K ROM holds, with the functions taking the most */
+    RomOverflow {
+        instructions: usize,
+        largest: Vec<(String, usize)>,
+    },
+    /* tokens left over once the command is complete */
+    TrailingTokens {
+        keyword: String,
+        extra: String,
+    },
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /* the fmt::Write the assembly goes to failed */
+    Fmt(fmt::Error),
+    /* error raised while handling the source text covered by span */
+    At {
+        span: Span,
+        error: Box<VmError>,
+    },
+}
+
+impl VmError {
+    /* attaches the source location to the error, io errors are left as is */
+    pub fn at(self, span: Span) -> Self {
+        match self {
+            #[cfg(feature = "std")]
+            VmError::Io(_) => self,
+            VmError::Fmt(_) | VmError::At { .. } => self,
+            error => VmError::At {
+                span,
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /* stable code used in diagnostics */
+    pub fn code(&self) -> &'static str {
+        match self {
+            VmError::UnknownCommand(_) => "E0001",
+            VmError::BadSegment { .. } => "E0002",
+            VmError::OffsetOutOfRange { .. } => "E0003",
+            VmError::MalformedLine { .. } => "E0004",
+            #[cfg(feature = "std")]
+            VmError::Io(_) => "E0005",
+            VmError::Fmt(_) => "E0005",
+            VmError::ConstantOutOfRange(_) => "E0006",
+            VmError::TrailingTokens { .. } => "E0007",
+            VmError::BadIdentifier { .. } => "E0008",
+            VmError::DuplicateLabel { .. } => "E0009",
+            VmError::UnresolvedLabel { .. } => "E0010",
+            VmError::UndefinedFunction(_) => "E0011",
+            VmError::InconsistentArity { .. } => "E0012",
+            VmError::ArgumentOverread { .. } => "E0013",
+            VmError::MissingReturn(_) => "E0014",
+            VmError::UnreachableCode { .. } => "E0015",
+            VmError::StaticOverflow(_) => "E0016",
+            VmError::MissingEntry(_) => "E0017",
+            VmError::RomOverflow { .. } => "E0018",
+            VmError::BadAddress(_) => "E0019",
+            VmError::StackOverflow { .. } => "E0020",
+            VmError::At { error, .. } => error.code(),
+        }
+    }
+
+    /* the source doesn't parse, as opposed to parsing into an invalid program */
+    pub fn is_syntax_error(&self) -> bool {
+        matches!(
+            self.kind(),
+            VmError::UnknownCommand(_)
+                | VmError::BadSegment { .. }
+                | VmError::MalformedLine { .. }
+                | VmError::ConstantOutOfRange(_)
+                | VmError::BadIdentifier { .. }
+                | VmError::TrailingTokens { .. }
+        )
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            VmError::At { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+
+    /* the error without its location */
+    pub fn kind(&self) -> &VmError {
+        match self {
+            VmError::At { error, .. } => error.kind(),
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmError::UnknownCommand(command) => write!(f, "Unknown command {}", command),
+            VmError::BadSegment { segment, reason } => {
+                write!(f, "Invalid memory segment {}: {}", segment, reason)
+            }
+            VmError::OffsetOutOfRange {
+                segment,
+                offset,
+                capacity,
+                command,
+            } => write!(
+                f,
+                "Offset {} is out of range for {} segment ({} reg) in `{}`",
+                offset, segment, capacity, command
+            ),
+            VmError::MalformedLine { line, reason } => write!(f, "{} in `{}`", reason, line),
+            VmError::ConstantOutOfRange(value) => write!(
+                f,
+                "Constant {} is out of range, the A-instruction holds at most {}",
+                value,
+                memory::MAX_CONSTANT
+            ),
+            VmError::BadIdentifier { name, reason } => {
+                write!(f, "Invalid identifier `{}`: {}", name, reason)
+            }
+            VmError::DuplicateLabel {
+                label,
+                function: Some(function),
+            } => write!(f, "Label {} is declared twice in {}", label, function),
+            VmError::DuplicateLabel {
+                label,
+                function: None,
+            } => write!(f, "Label {} is declared twice", label),
+            VmError::UnresolvedLabel {
+                label,
+                function: Some(function),
+            } => write!(f, "Label {} is never declared in {}", label, function),
+            VmError::UnresolvedLabel {
+                label,
+                function: None,
+            } => write!(f, "Label {} is never declared", label),
+            VmError::UndefinedFunction(name) => {
+                write!(f, "Function {} is called but never defined", name)
+            }
+            VmError::InconsistentArity {
+                function,
+                args,
+                other,
+            } => write!(
+                f,
+                "{} is called with {} argument(s) here but with {} elsewhere",
+                function, args, other
+            ),
+            VmError::ArgumentOverread {
+                function,
+                offset,
+                max_args,
+            } => write!(
+                f,
+                "{} reads argument {} but is called with at most {} argument(s)",
+                function, offset, max_args
+            ),
+            VmError::MissingReturn(function) => write!(
+                f,
+                "Function {} can reach its end without returning",
+                function
+            ),
+            VmError::UnreachableCode {
+                after,
+                first_line,
+                last_line,
+            } if first_line == last_line => {
+                write!(f, "Line {} is unreachable after `{}`", first_line, after)
+            }
+            VmError::UnreachableCode {
+                after,
+                first_line,
+                last_line,
+            } => write!(
+                f,
+                "Lines {}-{} are unreachable after `{}`",
+                first_line, last_line, after
+            ),
+            VmError::StaticOverflow(symbol) => write!(
+                f,
+                "Static variable {} doesn't fit, all {} static slots are taken",
+                symbol,
+                memory::STATIC_SIZE
+            ),
+            VmError::MissingEntry(entry) => write!(
+                f,
+                "The bootstrap calls {} but no input file defines it",
+                entry
+            ),
+            VmError::RomOverflow { instructions, .. } => write!(
+                f,
+                "Program needs {} instructions but the ROM only holds {}",
+                instructions,
+                memory::ROM_SIZE
+            ),
+            VmError::StackOverflow {
+                words: None,
+                stack_base,
+                region,
+                ..
+            } => write!(
+                f,
+                "Stack grows without bound from RAM[{}], into the {}",
+                stack_base, region
+            ),
+            VmError::StackOverflow {
+                words: Some(words),
+                stack_base,
+                region,
+                ..
+            } => write!(
+                f,
+                "Stack can grow to {} words from RAM[{}], into the {}",
+                words, stack_base, region
+            ),
+            VmError::BadAddress(address) => write!(
+                f,
+                "RAM[{}] is past the end of the {} words of RAM",
+                address,
+                memory::RAM_SIZE
+            ),
+            VmError::TrailingTokens { keyword, extra } => {
+                write!(
+                    f,
+                    "Unexpected `{}` after complete `{}` command",
+                    extra, keyword
+                )
+            }
+            #[cfg(feature = "std")]
+            VmError::Io(err) => write!(f, "{}", err),
+            VmError::Fmt(_) => write!(f, "Could not write the assembly"),
+            VmError::At { span, error } => write!(
+                f,
+                "Error at line {}, column {}: {}",
+                span.line, span.column, error
+            ),
+        }
+    }
+}
+
+impl Error for VmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            VmError::Io(err) => Some(err),
+            VmError::Fmt(err) => Some(err),
+            VmError::At { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for VmError {
+    fn from(err: io::Error) -> Self {
+        VmError::Io(err)
+    }
+}
+
+impl From<fmt::Error> for VmError {
+    fn from(err: fmt::Error) -> Self {
+        VmError::Fmt(err)
+    }
+}
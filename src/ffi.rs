@@ -0,0 +1,105 @@
+/*
+ * The translator for C and anything else that can call C, declared in
+ * include/hackvm.h. Build a library to link against with
+ *
+ *   cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * Every function returns HACKVM_OK or an error code. Codes above zero are the
+ * number of the diagnostic code, 4 for E0004, and stay the same across
+ * releases. Strings handed out are owned by the caller and go back through
+ * hackvm_free
+ */
+// the pointer requirements are spelled out above each function and in the header
+#![allow(clippy::missing_safety_doc)]
+
+use alloc::ffi::CString;
+use core::{
+    ffi::{c_char, c_int, CStr},
+    ptr,
+};
+
+use crate::{prelude::*, translate_str, Bootstrap, OptLevel, TranslationOptions, VmError};
+
+pub const HACKVM_OK: c_int = 0;
+/* a null pointer or a string that isn't UTF-8 */
+pub const HACKVM_INVALID_ARGUMENT: c_int = -1;
+
+/*
+ * Translates the NUL terminated VM source, filestem names its statics. A
+ * bootstrap of 0 leaves out the SP setup and call to Sys.init, opt_level is 0,
+ * 1 or 2 like -O. On success *asm points to the assembly, otherwise *error to a
+ * message. Either of asm and error may be null when the caller doesn't want it
+ */
+#[no_mangle]
+pub unsafe extern "C" fn hackvm_translate(
+    source: *const c_char,
+    filestem: *const c_char,
+    bootstrap: c_int,
+    opt_level: c_int,
+    asm: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> c_int {
+    let (Some(source), Some(filestem)) = (str_arg(source), str_arg(filestem)) else {
+        return HACKVM_INVALID_ARGUMENT;
+    };
+    let Some(opt_level) = u8::try_from(opt_level)
+        .ok()
+        .and_then(|level| OptLevel::try_from(level).ok())
+    else {
+        return HACKVM_INVALID_ARGUMENT;
+    };
+    let options = TranslationOptions {
+        bootstrap: if bootstrap == 0 {
+            Bootstrap::None
+        } else {
+            Bootstrap::Standard
+        },
+        opt_level,
+        ..TranslationOptions::default()
+    };
+
+    match translate_str(source, filestem, &options) {
+        Ok(output) => {
+            give(asm, output);
+            HACKVM_OK
+        }
+        Err(err) => {
+            give(error, err.to_string());
+            error_code(&err)
+        }
+    }
+}
+
+/* frees a string returned by any hackvm_ function, null is ignored */
+#[no_mangle]
+pub unsafe extern "C" fn hackvm_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/* E0004 -> 4 */
+pub fn error_code(err: &VmError) -> c_int {
+    err.code()
+        .trim_start_matches('E')
+        .parse()
+        .unwrap_or(HACKVM_INVALID_ARGUMENT)
+}
+
+// a NUL terminated UTF-8 string, None for null or invalid UTF-8
+unsafe fn str_arg<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+// hands s over to the caller through out, unless they passed null
+unsafe fn give(out: *mut *mut c_char, s: String) {
+    if out.is_null() {
+        return;
+    }
+    // the source could hold a NUL that ended up in a message
+    let s = CString::new(s.replace('\0', "")).unwrap_or_default();
+    ptr::write(out, s.into_raw());
+}
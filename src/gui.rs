@@ -0,0 +1,130 @@
+use minifb::{Key as HostKey, Window as MiniWindow, WindowOptions};
+
+use crate::{
+    keyboard::{Key, KeyMap},
+    memory::{SCREEN_HEIGHT, SCREEN_WIDTH},
+    screen,
+};
+
+/* Host keys typing a character, with what they type with shift held */
+const CHAR_KEYS: [(HostKey, char, char); 12] = [
+    (HostKey::Space, ' ', ' '),
+    (HostKey::Apostrophe, '\'', '"'),
+    (HostKey::Backquote, '`', '~'),
+    (HostKey::Backslash, '\\', '|'),
+    (HostKey::Comma, ',', '<'),
+    (HostKey::Equal, '=', '+'),
+    (HostKey::LeftBracket, '[', '{'),
+    (HostKey::Minus, '-', '_'),
+    (HostKey::Period, '.', '>'),
+    (HostKey::RightBracket, ']', '}'),
+    (HostKey::Semicolon, ';', ':'),
+    (HostKey::Slash, '/', '?'),
+];
+
+const SPECIAL_KEYS: [(HostKey, Key); 26] = [
+    (HostKey::Enter, Key::Newline),
+    (HostKey::NumPadEnter, Key::Newline),
+    (HostKey::Backspace, Key::Backspace),
+    (HostKey::Left, Key::Left),
+    (HostKey::Up, Key::Up),
+    (HostKey::Right, Key::Right),
+    (HostKey::Down, Key::Down),
+    (HostKey::Home, Key::Home),
+    (HostKey::End, Key::End),
+    (HostKey::PageUp, Key::PageUp),
+    (HostKey::PageDown, Key::PageDown),
+    (HostKey::Insert, Key::Insert),
+    (HostKey::Delete, Key::Delete),
+    (HostKey::Escape, Key::Escape),
+    (HostKey::F1, Key::F(1)),
+    (HostKey::F2, Key::F(2)),
+    (HostKey::F3, Key::F(3)),
+    (HostKey::F4, Key::F(4)),
+    (HostKey::F5, Key::F(5)),
+    (HostKey::F6, Key::F(6)),
+    (HostKey::F7, Key::F(7)),
+    (HostKey::F8, Key::F(8)),
+    (HostKey::F9, Key::F(9)),
+    (HostKey::F10, Key::F(10)),
+    (HostKey::F11, Key::F(11)),
+    (HostKey::F12, Key::F(12)),
+];
+
+// what the digits type with shift, on a US layout
+const SHIFTED_DIGITS: [char; 10] = [')', '!', '@', '#', '$', '%', '^', '&', '*', '('];
+
+/*
+ * A window showing the screen memory map of a running program, with the key
+ * held down in it reported through the keyboard memory map
+ */
+pub struct Window {
+    window: MiniWindow,
+    pixels: Vec<u32>,
+    keys: KeyMap,
+}
+
+impl Window {
+    pub fn open(title: &str, keys: KeyMap) -> Result<Self, minifb::Error> {
+        let options = WindowOptions {
+            scale: minifb::Scale::X2,
+            ..WindowOptions::default()
+        };
+        let mut window = MiniWindow::new(title, SCREEN_WIDTH, SCREEN_HEIGHT, options)?;
+        window.set_target_fps(60);
+
+        Ok(Window {
+            window,
+            pixels: vec![screen::WHITE; SCREEN_WIDTH * SCREEN_HEIGHT],
+            keys,
+        })
+    }
+
+    /* false once the user closed it */
+    pub fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    /* redraws it from the screen memory map, waiting for the next frame */
+    pub fn show(&mut self, screen: &[u16]) -> Result<(), minifb::Error> {
+        screen::render(screen, &mut self.pixels);
+        self.window
+            .update_with_buffer(&self.pixels, SCREEN_WIDTH, SCREEN_HEIGHT)
+    }
+
+    /* the key held down as of the last frame, the first one if there are several */
+    pub fn key(&self) -> Option<Key> {
+        let shift = self.window.is_key_down(HostKey::LeftShift)
+            || self.window.is_key_down(HostKey::RightShift);
+        self.window
+            .get_keys()
+            .into_iter()
+            .find_map(|key| self.translate(key, shift))
+    }
+
+    // mapped keys are reported as they are mapped whether shift is held or not
+    fn translate(&self, key: HostKey, shift: bool) -> Option<Key> {
+        if let Some((_, key)) = SPECIAL_KEYS.iter().find(|(host, _)| *host == key) {
+            return Some(self.keys.get(*key).unwrap_or(*key));
+        }
+
+        let (plain, shifted) = match key as u8 {
+            // Key0 to Key9 and A to Z come first, in order
+            n @ 0..=9 => (char::from(b'0' + n), SHIFTED_DIGITS[usize::from(n)]),
+            n @ 10..=35 => {
+                let letter = char::from(b'a' + n - 10);
+                (letter, letter.to_ascii_uppercase())
+            }
+            _ => CHAR_KEYS
+                .iter()
+                .find(|(host, ..)| *host == key)
+                .map(|(_, plain, shifted)| (*plain, *shifted))?,
+        };
+
+        match self.keys.get(Key::Char(plain)) {
+            Some(key) => Some(key),
+            None if shift => Some(Key::Char(shifted)),
+            None => Some(Key::Char(plain)),
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use alloc::borrow::Cow;
+use core::{fmt, str::FromStr};
+
+use crate::prelude::*;
+
+/*
+ * Hack assembly as the translator produces it. Codegen builds instructions,
+ * the optimizations rewrite them and to_asm prints them, nothing is pieced
+ * together from strings
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HackInstr {
+    /* @value or @symbol */
+    AInstr(Address),
+    /* dest=comp;jump, at least one of dest and jump is set */
+    CInstr {
+        dest: Dest,
+        comp: Comp,
+        jump: Option<Jump>,
+    },
+    /* (name), takes no ROM of its own */
+    Label(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Address {
+    Value(u16),
+    /* the built in symbols the translator uses all the time are borrowed */
+    Symbol(Cow<'static, str>),
+}
+
+/* Registers a C-instruction stores its result in */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Dest {
+    pub a: bool,
+    pub m: bool,
+    pub d: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Comp {
+    Zero,
+    One,
+    MinusOne,
+    D,
+    A,
+    M,
+    NotD,
+    NotA,
+    NotM,
+    NegD,
+    NegA,
+    NegM,
+    DPlusOne,
+    APlusOne,
+    MPlusOne,
+    DMinusOne,
+    AMinusOne,
+    MMinusOne,
+    DPlusA,
+    DPlusM,
+    DMinusA,
+    DMinusM,
+    AMinusD,
+    MMinusD,
+    DAndA,
+    DAndM,
+    DOrA,
+    DOrM,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Jump {
+    Jgt,
+    Jeq,
+    Jge,
+    Jlt,
+    Jne,
+    Jle,
+    Jmp,
+}
+
+/* Every computation with its mnemonic, the commutative ones are also read the other way round */
+const COMPS: [(Comp, &str); 28] = [
+    (Comp::Zero, "0"),
+    (Comp::One, "1"),
+    (Comp::MinusOne, "-1"),
+    (Comp::D, "D"),
+    (Comp::A, "A"),
+    (Comp::M, "M"),
+    (Comp::NotD, "!D"),
+    (Comp::NotA, "!A"),
+    (Comp::NotM, "!M"),
+    (Comp::NegD, "-D"),
+    (Comp::NegA, "-A"),
+    (Comp::NegM, "-M"),
+    (Comp::DPlusOne, "D+1"),
+    (Comp::APlusOne, "A+1"),
+    (Comp::MPlusOne, "M+1"),
+    (Comp::DMinusOne, "D-1"),
+    (Comp::AMinusOne, "A-1"),
+    (Comp::MMinusOne, "M-1"),
+    (Comp::DPlusA, "D+A"),
+    (Comp::DPlusM, "D+M"),
+    (Comp::DMinusA, "D-A"),
+    (Comp::DMinusM, "D-M"),
+    (Comp::AMinusD, "A-D"),
+    (Comp::MMinusD, "M-D"),
+    (Comp::DAndA, "D&A"),
+    (Comp::DAndM, "D&M"),
+    (Comp::DOrA, "D|A"),
+    (Comp::DOrM, "D|M"),
+];
+
+const JUMPS: [(Jump, &str); 7] = [
+    (Jump::Jgt, "JGT"),
+    (Jump::Jeq, "JEQ"),
+    (Jump::Jge, "JGE"),
+    (Jump::Jlt, "JLT"),
+    (Jump::Jne, "JNE"),
+    (Jump::Jle, "JLE"),
+    (Jump::Jmp, "JMP"),
+];
+
+impl Dest {
+    pub const NONE: Dest = Dest::new(false, false, false);
+    pub const A: Dest = Dest::new(true, false, false);
+    pub const M: Dest = Dest::new(false, true, false);
+    pub const D: Dest = Dest::new(false, false, true);
+    pub const AM: Dest = Dest::new(true, true, false);
+    pub const AD: Dest = Dest::new(true, false, true);
+    pub const MD: Dest = Dest::new(false, true, true);
+    pub const AMD: Dest = Dest::new(true, true, true);
+
+    pub const fn new(a: bool, m: bool, d: bool) -> Self {
+        Dest { a, m, d }
+    }
+
+    pub fn is_empty(self) -> bool {
+        self == Dest::NONE
+    }
+}
+
+impl Comp {
+    pub fn mnemonic(self) -> &'static str {
+        COMPS
+            .iter()
+            .find(|(comp, _)| *comp == self)
+            .map(|(_, mnemonic)| *mnemonic)
+            .unwrap_or_default()
+    }
+}
+
+impl Jump {
+    pub fn mnemonic(self) -> &'static str {
+        JUMPS
+            .iter()
+            .find(|(jump, _)| *jump == self)
+            .map(|(_, mnemonic)| *mnemonic)
+            .unwrap_or_default()
+    }
+}
+
+impl HackInstr {
+    /* labels only name the next instruction */
+    pub fn is_instruction(&self) -> bool {
+        !matches!(self, HackInstr::Label(_))
+    }
+}
+
+/* @address */
+pub fn at(address: impl Into<Address>) -> HackInstr {
+    HackInstr::AInstr(address.into())
+}
+
+/* dest=comp */
+pub fn set(dest: Dest, comp: Comp) -> HackInstr {
+    HackInstr::CInstr {
+        dest,
+        comp,
+        jump: None,
+    }
+}
+
+/* comp;jump */
+pub fn jump(comp: Comp, jump: Jump) -> HackInstr {
+    HackInstr::CInstr {
+        dest: Dest::NONE,
+        comp,
+        jump: Some(jump),
+    }
+}
+
+pub fn label(name: impl Into<String>) -> HackInstr {
+    HackInstr::Label(name.into())
+}
+
+impl From<u16> for Address {
+    fn from(value: u16) -> Self {
+        Address::Value(value)
+    }
+}
+
+impl From<&'static str> for Address {
+    fn from(symbol: &'static str) -> Self {
+        Address::Symbol(Cow::Borrowed(symbol))
+    }
+}
+
+impl From<String> for Address {
+    fn from(symbol: String) -> Self {
+        Address::Symbol(Cow::Owned(symbol))
+    }
+}
+
+impl From<&String> for Address {
+    fn from(symbol: &String) -> Self {
+        Address::Symbol(Cow::Owned(symbol.clone()))
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::Value(value) => write!(f, "{}", value),
+            Address::Symbol(symbol) => f.write_str(symbol),
+        }
+    }
+}
+
+// the order the book writes them in, AM, AD, MD and AMD
+impl fmt::Display for Dest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (set, reg) in [(self.a, "A"), (self.m, "M"), (self.d, "D")] {
+            if set {
+                f.write_str(reg)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Comp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+impl fmt::Display for Jump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+impl fmt::Display for HackInstr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HackInstr::AInstr(address) => write!(f, "@{}", address),
+            HackInstr::CInstr { dest, comp, jump } => {
+                if !dest.is_empty() {
+                    write!(f, "{}=", dest)?;
+                }
+                write!(f, "{}", comp)?;
+                if let Some(jump) = jump {
+                    write!(f, ";{}", jump)?;
+                }
+                Ok(())
+            }
+            HackInstr::Label(name) => write!(f, "({})", name),
+        }
+    }
+}
+
+impl FromStr for Dest {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut dest = Dest::NONE;
+        for reg in s.chars() {
+            let slot = match reg {
+                'A' => &mut dest.a,
+                'M' => &mut dest.m,
+                'D' => &mut dest.d,
+                _ => return Err(format!("Invalid destination {}", s)),
+            };
+            if *slot {
+                return Err(format!("Invalid destination {}", s));
+            }
+            *slot = true;
+        }
+
+        if dest.is_empty() {
+            return Err("Empty destination".to_owned());
+        }
+        Ok(dest)
+    }
+}
+
+impl FromStr for Comp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let swapped = match s.as_bytes() {
+            [x, op @ (b'+' | b'&' | b'|'), y] => {
+                String::from_utf8(vec![*y, *op, *x]).unwrap_or_default()
+            }
+            _ => String::new(),
+        };
+
+        COMPS
+            .iter()
+            .find(|(_, mnemonic)| *mnemonic == s || *mnemonic == swapped)
+            .map(|(comp, _)| *comp)
+            .ok_or_else(|| format!("Invalid computation {}", s))
+    }
+}
+
+impl FromStr for Jump {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        JUMPS
+            .iter()
+            .find(|(_, mnemonic)| *mnemonic == s)
+            .map(|(jump, _)| *jump)
+            .ok_or_else(|| format!("Invalid jump {}", s))
+    }
+}
+
+/* one instruction or label, without comments or surrounding whitespace */
+impl FromStr for HackInstr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(name) = s.strip_prefix('(') {
+            return match name.strip_suffix(')') {
+                Some(name) if !name.is_empty() => Ok(label(name)),
+                _ => Err(format!("Malformed label declaration {}", s)),
+            };
+        }
+
+        if let Some(address) = s.strip_prefix('@') {
+            if address.starts_with(|c: char| c.is_ascii_digit()) {
+                return match address.parse::<u16>() {
+                    Ok(value) if value < 0x8000 => Ok(at(value)),
+                    _ => Err(format!("Invalid A-instruction constant {}", address)),
+                };
+            }
+            if address.is_empty() {
+                return Err("Missing A-instruction address".to_owned());
+            }
+            return Ok(at(address.to_owned()));
+        }
+
+        let (dest, rest) = match s.split_once('=') {
+            Some((dest, rest)) => (dest.parse()?, rest),
+            None => (Dest::NONE, s),
+        };
+        let (comp, jump) = match rest.split_once(';') {
+            Some((comp, jump)) => (comp.parse()?, Some(jump.parse()?)),
+            None => (rest.parse()?, None),
+        };
+
+        if dest.is_empty() && jump.is_none() {
+            return Err(format!("Instruction {} has no effect", s));
+        }
+        Ok(HackInstr::CInstr { dest, comp, jump })
+    }
+}
+
+/* Prints instructions one per line, the way they end up in the .asm file */
+pub fn to_asm(instrs: &[HackInstr]) -> String {
+    let mut asm = String::new();
+    for instr in instrs {
+        asm.push_str(&instr.to_string());
+        asm.push('\n');
+    }
+
+    asm
+}
+
+/* Reads assembly back, skipping comments and blank lines. Errors carry the 1-based line */
+pub fn parse(asm: &str) -> Result<Vec<HackInstr>, (usize, String)> {
+    let mut instrs = Vec::new();
+
+    for (n, line) in asm.lines().enumerate() {
+        let code = match line.find("//") {
+            Some(idx) => &line[..idx],
+            None => line,
+        }
+        .trim();
+        if code.is_empty() {
+            continue;
+        }
+
+        instrs.push(code.parse().map_err(|err| (n + 1, err))?);
+    }
+
+    Ok(instrs)
+}
@@ -0,0 +1,168 @@
+use alloc::collections::BTreeMap;
+use core::fmt::Write as _;
+
+use crate::{interpreter::Interpreter, prelude::*};
+
+pub const ALLOC: &str = "Memory.alloc";
+pub const DEALLOC: &str = "Memory.deAlloc";
+
+/*
+ * The blocks the OS's Memory.alloc hands out and what Memory.deAlloc is given,
+ * followed through the calls and returns the interpreter runs, see
+ * Interpreter::track_heap. Sites are the command indices of the calls, as in
+ * Interpreter::command_at
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeapTracker {
+    /* blocks not freed yet by address */
+    live: BTreeMap<u16, Block>,
+    /* where each freed address was freed, until alloc hands it out again */
+    freed: BTreeMap<u16, usize>,
+    problems: Vec<HeapProblem>,
+    /* size and site of the Memory.alloc calls that haven't returned yet */
+    pending: Vec<(u16, usize)>,
+    pub allocations: u64,
+    pub frees: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    pub address: u16,
+    /* words asked for */
+    pub size: u16,
+    pub site: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeapProblem {
+    DoubleFree {
+        address: u16,
+        site: usize,
+        freed_at: usize,
+    },
+    /* an address alloc never handed out */
+    BadFree {
+        address: u16,
+        site: usize,
+    },
+}
+
+impl HeapTracker {
+    pub fn new() -> Self {
+        HeapTracker::default()
+    }
+
+    /* function entered from site with its first argument */
+    pub(crate) fn called(&mut self, function: &str, argument: u16, site: usize) {
+        match function {
+            ALLOC => self.pending.push((argument, site)),
+            DEALLOC => {
+                if self.live.remove(&argument).is_some() {
+                    self.frees += 1;
+                    self.freed.insert(argument, site);
+                } else if let Some(freed_at) = self.freed.get(&argument) {
+                    self.problems.push(HeapProblem::DoubleFree {
+                        address: argument,
+                        site,
+                        freed_at: *freed_at,
+                    });
+                } else {
+                    self.problems.push(HeapProblem::BadFree {
+                        address: argument,
+                        site,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /* function returned value */
+    pub(crate) fn returned(&mut self, function: &str, value: u16) {
+        if function != ALLOC {
+            return;
+        }
+        let Some((size, site)) = self.pending.pop() else {
+            return;
+        };
+        self.allocations += 1;
+        self.freed.remove(&value);
+        self.live.insert(
+            value,
+            Block {
+                address: value,
+                size,
+                site,
+            },
+        );
+    }
+
+    /* blocks still allocated, by address */
+    pub fn leaks(&self) -> impl Iterator<Item = &Block> {
+        self.live.values()
+    }
+
+    /* double and bad frees in the order they happened */
+    pub fn problems(&self) -> &[HeapProblem] {
+        &self.problems
+    }
+
+    /*
+     * What was allocated and freed, then every block left over and every free
+     * that went wrong with where in the source it happened:
+     *
+     *   Heap: 3 allocation(s), 1 freed, 2 block(s) of 7 words never freed
+     *     RAM[2050]  5 words  allocated at Main.vm:14
+     *   double free of RAM[2050] at Main.vm:30, freed before at Main.vm:22
+     */
+    pub fn report(&self, vm: &Interpreter) -> String {
+        let site = |pc: usize| match vm.command_at(pc) {
+            Some((filestem, command)) => format!("{}.vm:{}", filestem, command.span.line),
+            None => format!("command {}", pc),
+        };
+        let leaked: u32 = self.leaks().map(|block| u32::from(block.size)).sum();
+
+        let mut out = format!(
+            "Heap: {} allocation(s), {} freed",
+            self.allocations, self.frees
+        );
+        match self.live.len() {
+            0 => out.push_str(", nothing left allocated\n"),
+            blocks => {
+                let _ = writeln!(out, ", {} block(s) of {} words never freed", blocks, leaked);
+            }
+        }
+        for block in self.leaks() {
+            let _ = writeln!(
+                out,
+                "  RAM[{}]  {} words  allocated at {}",
+                block.address,
+                block.size,
+                site(block.site)
+            );
+        }
+        for problem in &self.problems {
+            let _ = match problem {
+                HeapProblem::DoubleFree {
+                    address,
+                    site: at,
+                    freed_at,
+                } => writeln!(
+                    out,
+                    "double free of RAM[{}] at {}, freed before at {}",
+                    address,
+                    site(*at),
+                    site(*freed_at)
+                ),
+                HeapProblem::BadFree { address, site: at } => writeln!(
+                    out,
+                    "free of RAM[{}] at {}, which was never allocated",
+                    address,
+                    site(*at)
+                ),
+            };
+        }
+
+        out
+    }
+}
@@ -0,0 +1,706 @@
+use alloc::collections::{BTreeMap, VecDeque};
+
+use crate::{
+    heap::HeapTracker,
+    keyboard::Key,
+    memory::{self, Frame, StaticAllocator, FRAME_SIZE, RAM_SIZE},
+    prelude::*,
+    Bootstrap, Command, MemorySegment, Span, Spanned, VmError,
+};
+
+/*
+ * Runs VM commands directly, over the same 32K RAM and with the same stack and
+ * call frames the translated code would have. Statics get their addresses
+ * the way the assembler hands them out, so RAM ends up as it would on the CPU:
+ *
+ *   let mut vm = Interpreter::new();
+ *   vm.add_file("Main", commands)?;
+ *   vm.start(&Bootstrap::Standard)?;
+ *   vm.run_steps(1_000_000)?;
+ *   let result = vm.ram(256);
+ *
+ * lt and gt test the sign of x - y like the translated code and the constant
+ * folding do, so operands far apart compare the wrong way on every backend
+ */
+#[derive(Debug, Clone)]
+pub struct Interpreter {
+    ops: Vec<Op>,
+    /* stems of the files loaded so far, in order */
+    files: Vec<String>,
+    /* where every function starts */
+    functions: BTreeMap<String, usize>,
+    statics: StaticAllocator,
+    ram: Vec<u16>,
+    /* the command to run next */
+    pc: usize,
+    calls: Vec<Call>,
+    steps: u64,
+    state: State,
+    /* how to undo the last commands run, the latest last, see record */
+    journal: VecDeque<Delta>,
+    journal_limit: usize,
+    /* what the command being run has changed so far, while recording */
+    delta: Option<Delta>,
+    /* how often each command ran, empty unless count_hits was called */
+    hits: Vec<u64>,
+    heap: Option<HeapTracker>,
+    /* addresses the last command wrote, while tracking writes */
+    writes: Option<Vec<u16>>,
+    screen_writes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Running,
+    /* ran off the end, returned from the entry point or went into a goto to itself */
+    Halted,
+}
+
+/* A call being run, innermost last in Interpreter::calls */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    pub function: String,
+    /* the command after the call, None for the bootstrap's call to the entry point */
+    pub return_to: Option<usize>,
+    pub n_args: u16,
+    /* the callee's ARG and LCL, which stay put while it runs */
+    pub arg: u16,
+    pub lcl: u16,
+}
+
+/* What running a command changed, enough to put it back */
+#[derive(Debug, Clone)]
+struct Delta {
+    pc: usize,
+    state: State,
+    /* addresses written and what they held before, in the order they were written */
+    writes: Vec<(usize, u16)>,
+    call: Option<CallChange>,
+}
+
+#[derive(Debug, Clone)]
+enum CallChange {
+    Entered,
+    Returned(Call),
+}
+
+/* A command with everything running it needs worked out up front */
+#[derive(Debug, Clone)]
+struct Op {
+    command: Command,
+    span: Span,
+    file: usize,
+    /* where a goto or if-goto jumps or a call enters, calls are resolved by start */
+    target: Option<usize>,
+    /* RAM address of a static */
+    address: u16,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter {
+            ops: Vec::new(),
+            files: Vec::new(),
+            functions: BTreeMap::new(),
+            statics: StaticAllocator::new(),
+            ram: vec![0; RAM_SIZE],
+            pc: 0,
+            calls: Vec::new(),
+            steps: 0,
+            state: State::Running,
+            journal: VecDeque::new(),
+            journal_limit: 0,
+            delta: None,
+            hits: Vec::new(),
+            heap: None,
+            writes: None,
+            screen_writes: 0,
+        }
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter::default()
+    }
+
+    /*
+     * Loads the commands of a file, filestem names its statics. Labels are
+     * resolved within their function, errors carry the span of the command
+     */
+    pub fn add_file(
+        &mut self,
+        filestem: &str,
+        commands: Vec<Spanned<Command>>,
+    ) -> Result<(), VmError> {
+        let file = self.files.len();
+        self.files.push(filestem.to_owned());
+        let first = self.ops.len();
+
+        // labels of the scope, the commands before the first function or a function's body
+        let mut scope_start = first;
+        let mut labels: BTreeMap<String, usize> = BTreeMap::new();
+        let mut function: Option<String> = None;
+        for command in commands {
+            let idx = self.ops.len();
+            if let Command::Label(label) = &command.node {
+                if labels.insert(label.clone(), idx).is_some() {
+                    let err = VmError::DuplicateLabel {
+                        label: label.clone(),
+                        function,
+                    };
+                    return Err(err.at(command.span));
+                }
+            }
+
+            let mut address = 0;
+            match &command.node {
+                Command::Function(name, _) => {
+                    self.resolve_gotos(scope_start, &labels, &function)?;
+                    (scope_start, labels) = (idx, BTreeMap::new());
+                    function = Some(name.clone());
+                    self.functions.insert(name.clone(), idx);
+                }
+                Command::Push(MemorySegment::Static, index)
+                | Command::Pop(MemorySegment::Static, index) => {
+                    address = self
+                        .statics
+                        .allocate(filestem, *index)
+                        .map_err(|err| err.at(command.span))?;
+                }
+                _ => {}
+            }
+            self.ops.push(Op {
+                command: command.node,
+                span: command.span,
+                file,
+                target: None,
+                address,
+            });
+        }
+
+        self.resolve_gotos(scope_start, &labels, &function)
+    }
+
+    /*
+     * Resets RAM and sets up the stack once every file is loaded. With a
+     * bootstrap whose entry point is defined this calls it like the
+     * translated bootstrap, otherwise the first command runs first with SP at
+     * the stack base, like a project 7 test expects
+     */
+    pub fn start(&mut self, bootstrap: &Bootstrap) -> Result<(), VmError> {
+        for idx in 0..self.ops.len() {
+            if let Command::Call(function, _) = &self.ops[idx].command {
+                self.ops[idx].target = self.functions.get(function).copied();
+            }
+        }
+
+        self.ram.fill(0);
+        self.calls.clear();
+        self.journal.clear();
+        self.screen_writes = 0;
+        if let Some(heap) = &mut self.heap {
+            *heap = HeapTracker::new();
+        }
+        self.steps = 0;
+        self.state = State::Running;
+        self.pc = 0;
+
+        let (stack_base, entry) = match bootstrap {
+            Bootstrap::Standard => (memory::STACK_BASE, Some("Sys.init")),
+            Bootstrap::None => (memory::STACK_BASE, None),
+            Bootstrap::Custom { stack_base, entry } => (*stack_base, Some(entry.as_str())),
+        };
+        self.ram[usize::from(memory::SP)] = stack_base;
+
+        match entry.and_then(|entry| self.functions.get(entry).map(|pc| (entry, *pc))) {
+            Some((entry, pc)) => {
+                // the frame the bootstrap's call leaves, with nothing to return to
+                for _ in 0..FRAME_SIZE {
+                    self.push(0)?;
+                }
+                let sp = self.ram[usize::from(memory::SP)];
+                let arg = Frame::callee_arg(sp, 0);
+                self.ram[usize::from(memory::ARG)] = arg;
+                self.ram[usize::from(memory::LCL)] = sp;
+                self.calls.push(Call {
+                    function: entry.to_owned(),
+                    return_to: None,
+                    n_args: 0,
+                    arg,
+                    lcl: sp,
+                });
+                self.pc = pc;
+            }
+            None if self.ops.is_empty() => self.state = State::Halted,
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /* runs one command, one that fails stays the current command */
+    pub fn step(&mut self) -> Result<State, VmError> {
+        if self.state == State::Halted {
+            return Ok(State::Halted);
+        }
+        let Some(op) = self.ops.get(self.pc) else {
+            self.state = State::Halted;
+            return Ok(State::Halted);
+        };
+
+        let (span, pc) = (op.span, self.pc);
+        if self.journal_limit > 0 {
+            self.delta = Some(Delta {
+                pc,
+                state: self.state,
+                writes: Vec::new(),
+                call: None,
+            });
+        }
+        if let Some(writes) = &mut self.writes {
+            writes.clear();
+        }
+        self.steps += 1;
+        if let Some(hits) = self.hits.get_mut(pc) {
+            *hits += 1;
+        }
+        self.pc += 1;
+        let result = self.execute(pc);
+        if result.is_err() {
+            self.pc = pc;
+        } else if self.pc >= self.ops.len() {
+            self.state = State::Halted;
+        }
+
+        // a failed command is journaled too, stepping back undoes what it got to write
+        if let Some(delta) = self.delta.take() {
+            if self.journal.len() == self.journal_limit {
+                self.journal.pop_front();
+            }
+            self.journal.push_back(delta);
+        }
+        result.map_err(|err| err.at(span))?;
+
+        Ok(self.state)
+    }
+
+    /*
+     * Keeps what it takes to undo the last limit commands run from here on,
+     * 0 stops and forgets them. Each costs the RAM words it wrote
+     */
+    pub fn record(&mut self, limit: usize) {
+        self.journal_limit = limit;
+        while self.journal.len() > limit {
+            self.journal.pop_front();
+        }
+    }
+
+    /* undoes the last command run, false when there's none left in the journal */
+    pub fn step_back(&mut self) -> bool {
+        let Some(delta) = self.journal.pop_back() else {
+            return false;
+        };
+
+        for (address, value) in delta.writes.into_iter().rev() {
+            self.ram[address] = value;
+        }
+        match delta.call {
+            Some(CallChange::Entered) => {
+                self.calls.pop();
+            }
+            Some(CallChange::Returned(call)) => self.calls.push(call),
+            None => {}
+        }
+        if let Some(hits) = self.hits.get_mut(delta.pc) {
+            *hits -= 1;
+        }
+        self.pc = delta.pc;
+        self.state = delta.state;
+        self.steps -= 1;
+
+        true
+    }
+
+    /* counts how often each command runs from here on, for profiling and coverage */
+    pub fn count_hits(&mut self) {
+        self.hits.resize(self.ops.len(), 0);
+    }
+
+    /* by command index as in command_at, empty unless count_hits was called */
+    pub fn hits(&self) -> &[u64] {
+        &self.hits
+    }
+
+    /*
+     * Follows the calls to the OS's Memory.alloc and Memory.deAlloc from here
+     * on, for finding leaks and double frees. step_back doesn't undo it
+     */
+    pub fn track_heap(&mut self) {
+        self.heap.get_or_insert_with(HeapTracker::new);
+    }
+
+    /* None unless track_heap was called */
+    pub fn heap(&self) -> Option<&HeapTracker> {
+        self.heap.as_ref()
+    }
+
+    /* notes the addresses each command writes from here on, for tracing */
+    pub fn track_writes(&mut self) {
+        self.writes.get_or_insert_with(Vec::new);
+    }
+
+    /* in the order first written, empty unless track_writes was called */
+    pub fn last_writes(&self) -> &[u16] {
+        self.writes.as_deref().unwrap_or_default()
+    }
+
+    /* commands step_back can undo */
+    pub fn journal_len(&self) -> usize {
+        self.journal.len()
+    }
+
+    /* runs until the program halts or max_steps commands have run */
+    pub fn run_steps(&mut self, max_steps: u64) -> Result<State, VmError> {
+        for _ in 0..max_steps {
+            if self.step()? == State::Halted {
+                break;
+            }
+        }
+
+        Ok(self.state)
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /* commands run since start */
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn ram(&self, address: u16) -> u16 {
+        self.ram
+            .get(usize::from(address))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_ram(&mut self, address: u16, value: u16) {
+        if let Some(slot) = self.ram.get_mut(usize::from(address)) {
+            *slot = value;
+        }
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.ram(memory::SP)
+    }
+
+    pub fn press(&mut self, key: Key) {
+        self.set_ram(memory::KBD, key.code());
+    }
+
+    pub fn release(&mut self) {
+        self.set_ram(memory::KBD, 0);
+    }
+
+    pub fn screen(&self) -> &[u16] {
+        let start = usize::from(memory::SCREEN);
+        &self.ram[start..start + usize::from(memory::SCREEN_SIZE)]
+    }
+
+    /* start clears the count along with the RAM */
+    pub fn screen_writes(&self) -> u64 {
+        self.screen_writes
+    }
+
+    /* the value on top of the stack, None while it is empty */
+    pub fn top(&self) -> Option<u16> {
+        let sp = self.sp();
+        (sp > 0).then(|| self.ram(sp - 1))
+    }
+
+    /* the command run next with the file it's in, None once halted */
+    pub fn current(&self) -> Option<(&str, Spanned<&Command>)> {
+        if self.state == State::Halted {
+            return None;
+        }
+        self.command_at(self.pc)
+    }
+
+    /* index of the command run next, counting every loaded file's commands in order */
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn command_at(&self, pc: usize) -> Option<(&str, Spanned<&Command>)> {
+        let op = self.ops.get(pc)?;
+        Some((
+            &self.files[op.file],
+            Spanned {
+                node: &op.command,
+                span: op.span,
+            },
+        ))
+    }
+
+    /* where the function command of name is */
+    pub fn function_start(&self, name: &str) -> Option<usize> {
+        self.functions.get(name).copied()
+    }
+
+    /* the first command of filestem on line or after it, what a breakpoint on a comment stops at */
+    pub fn find_line(&self, filestem: &str, line: usize) -> Option<usize> {
+        let file = self.files.iter().position(|file| file == filestem)?;
+        self.ops
+            .iter()
+            .position(|op| op.file == file && op.span.line >= line)
+    }
+
+    /* the calls being run, the entry point first */
+    pub fn calls(&self) -> &[Call] {
+        &self.calls
+    }
+
+    /* the pointers of the running function, with its file's static base */
+    pub fn frame(&self) -> Frame {
+        Frame {
+            lcl: self.ram(memory::LCL),
+            arg: self.ram(memory::ARG),
+            this: self.ram(memory::THIS),
+            that: self.ram(memory::THAT),
+            static_base: memory::STATIC_BASE,
+        }
+    }
+
+    /* static addresses handed out, in address order */
+    pub fn statics(&self) -> &StaticAllocator {
+        &self.statics
+    }
+
+    fn execute(&mut self, pc: usize) -> Result<(), VmError> {
+        let op = &self.ops[pc];
+        match &op.command {
+            Command::Push(segment, index) => {
+                let value = match segment {
+                    MemorySegment::Constant => *index,
+                    MemorySegment::Static => self.ram[usize::from(op.address)],
+                    segment => self.ram[self.address(*segment, *index)?],
+                };
+                self.push(value)?;
+            }
+            Command::Pop(segment, index) => {
+                let address = match segment {
+                    MemorySegment::Static => usize::from(op.address),
+                    segment => self.address(*segment, *index)?,
+                };
+                let value = self.pop()?;
+                self.write(address, value);
+            }
+
+            Command::Add => self.binary(u16::wrapping_add)?,
+            Command::Sub => self.binary(u16::wrapping_sub)?,
+            Command::And => self.binary(|x, y| x & y)?,
+            Command::Or => self.binary(|x, y| x | y)?,
+            Command::Neg => self.unary(|y| 0u16.wrapping_sub(y))?,
+            Command::Not => self.unary(|y| !y)?,
+            Command::Eq => self.binary(|x, y| truth(x == y))?,
+            Command::Lt => self.binary(|x, y| truth((x.wrapping_sub(y) as i16) < 0))?,
+            Command::Gt => self.binary(|x, y| truth((x.wrapping_sub(y) as i16) > 0))?,
+
+            Command::Label(_) => {}
+            Command::Goto(_) => {
+                let target = op.target.unwrap_or(pc);
+                // `label END, goto END` is how programs stop
+                if target == pc || (target + 1 == pc && self.is_label(target)) {
+                    self.state = State::Halted;
+                }
+                self.pc = target;
+            }
+            Command::IfGoto(_) => {
+                let target = op.target.unwrap_or(pc);
+                if self.pop()? != 0 {
+                    self.pc = target;
+                }
+            }
+
+            Command::Function(_, n_locals) => {
+                for _ in 0..*n_locals {
+                    self.push(0)?;
+                }
+            }
+            Command::Call(function, n_args) => {
+                let (function, n_args) = (function.clone(), *n_args);
+                let Some(target) = op.target else {
+                    return Err(VmError::UndefinedFunction(function));
+                };
+
+                // the same frame the translated call saves, the return address is the command's index
+                self.push(self.pc as u16)?;
+                for pointer in [memory::LCL, memory::ARG, memory::THIS, memory::THAT] {
+                    self.push(self.ram(pointer))?;
+                }
+                let sp = self.sp();
+                let arg = Frame::callee_arg(sp, n_args);
+                self.write(usize::from(memory::ARG), arg);
+                self.write(usize::from(memory::LCL), sp);
+                if let Some(delta) = &mut self.delta {
+                    delta.call = Some(CallChange::Entered);
+                }
+                if let Some(heap) = &mut self.heap {
+                    let argument = if n_args > 0 {
+                        self.ram[checked(arg)?]
+                    } else {
+                        0
+                    };
+                    heap.called(&function, argument, pc);
+                }
+                self.calls.push(Call {
+                    function,
+                    return_to: Some(self.pc),
+                    n_args,
+                    arg,
+                    lcl: sp,
+                });
+                self.pc = target;
+            }
+            Command::Return => {
+                let frame = self.frame();
+                let value = self.pop()?;
+                let arg = checked(frame.arg)?;
+                self.write(arg, value);
+                self.write(usize::from(memory::SP), frame.arg.wrapping_add(1));
+                for (pointer, distance) in [
+                    (memory::THAT, 1),
+                    (memory::THIS, 2),
+                    (memory::ARG, 3),
+                    (memory::LCL, 4),
+                ] {
+                    let saved = checked(frame.lcl.wrapping_sub(distance))?;
+                    self.write(usize::from(pointer), self.ram[saved]);
+                }
+
+                let call = self.calls.pop();
+                if let (Some(heap), Some(call)) = (&mut self.heap, &call) {
+                    heap.returned(&call.function, value);
+                }
+                let return_to = call.as_ref().and_then(|call| call.return_to);
+                if let (Some(delta), Some(call)) = (&mut self.delta, call) {
+                    delta.call = Some(CallChange::Returned(call));
+                }
+                match return_to {
+                    Some(return_to) => self.pc = return_to,
+                    // returning from the entry point, or from code nothing called
+                    None => self.state = State::Halted,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push(&mut self, value: u16) -> Result<(), VmError> {
+        let sp = self.sp();
+        let slot = checked(sp)?;
+        self.write(slot, value);
+        self.write(usize::from(memory::SP), sp.wrapping_add(1));
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<u16, VmError> {
+        let sp = self.sp().wrapping_sub(1);
+        let slot = checked(sp)?;
+        self.write(usize::from(memory::SP), sp);
+        Ok(self.ram[slot])
+    }
+
+    // every write a command makes goes through here to be journaled
+    fn write(&mut self, address: usize, value: u16) {
+        if let Some(delta) = &mut self.delta {
+            delta.writes.push((address, self.ram[address]));
+        }
+        if let Some(writes) = &mut self.writes {
+            if !writes.contains(&(address as u16)) {
+                writes.push(address as u16);
+            }
+        }
+        if memory::is_screen(address as u16) {
+            self.screen_writes += 1;
+        }
+        self.ram[address] = value;
+    }
+
+    fn binary(&mut self, op: impl Fn(u16, u16) -> u16) -> Result<(), VmError> {
+        let y = self.pop()?;
+        let x = self.pop()?;
+        self.push(op(x, y))
+    }
+
+    fn unary(&mut self, op: impl Fn(u16) -> u16) -> Result<(), VmError> {
+        let y = self.pop()?;
+        self.push(op(y))
+    }
+
+    // RAM index of segment[index] under the running function's pointers
+    fn address(&self, segment: MemorySegment, index: u16) -> Result<usize, VmError> {
+        let frame = self.frame();
+        if let Some(address) = segment.address_of(index, &frame) {
+            return Ok(usize::from(address));
+        }
+
+        let base = match segment {
+            MemorySegment::Local => frame.lcl,
+            MemorySegment::Argument => frame.arg,
+            MemorySegment::This => frame.this,
+            MemorySegment::That => frame.that,
+            _ => 0,
+        };
+        Err(VmError::BadAddress(u32::from(base) + u32::from(index)))
+    }
+
+    fn is_label(&self, idx: usize) -> bool {
+        matches!(self.ops[idx].command, Command::Label(_))
+    }
+
+    // points the gotos of the scope starting at first at its labels
+    fn resolve_gotos(
+        &mut self,
+        first: usize,
+        labels: &BTreeMap<String, usize>,
+        function: &Option<String>,
+    ) -> Result<(), VmError> {
+        for idx in first..self.ops.len() {
+            if let Command::Goto(label) | Command::IfGoto(label) = &self.ops[idx].command {
+                match labels.get(label) {
+                    Some(target) => self.ops[idx].target = Some(*target),
+                    None => {
+                        let err = VmError::UnresolvedLabel {
+                            label: label.clone(),
+                            function: function.clone(),
+                        };
+                        return Err(err.at(self.ops[idx].span));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn checked(address: u16) -> Result<usize, VmError> {
+    match usize::from(address) {
+        address if address < RAM_SIZE => Ok(address),
+        address => Err(VmError::BadAddress(address as u32)),
+    }
+}
+
+// true is all ones, -1
+fn truth(holds: bool) -> u16 {
+    if holds {
+        0xffff
+    } else {
+        0
+    }
+}
@@ -0,0 +1,113 @@
+use alloc::collections::BTreeMap;
+use core::str::FromStr;
+
+use crate::prelude::*;
+
+/*
+ * A key the Hack keyboard reports. While one is held RAM[24576] holds its
+ * code: printable ASCII as itself, the others from 128 on
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Key {
+    Char(char),
+    Newline,
+    Backspace,
+    Left,
+    Up,
+    Right,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    Escape,
+    /* F1 to F12 */
+    F(u8),
+}
+
+/* The keys past printable ASCII by name, in code order from 128 */
+const NAMED_KEYS: [(&str, Key); 13] = [
+    ("newline", Key::Newline),
+    ("backspace", Key::Backspace),
+    ("left", Key::Left),
+    ("up", Key::Up),
+    ("right", Key::Right),
+    ("down", Key::Down),
+    ("home", Key::Home),
+    ("end", Key::End),
+    ("page-up", Key::PageUp),
+    ("page-down", Key::PageDown),
+    ("insert", Key::Insert),
+    ("delete", Key::Delete),
+    ("escape", Key::Escape),
+];
+
+impl Key {
+    pub fn code(self) -> u16 {
+        match self {
+            Key::Char(c) => c as u16,
+            Key::F(n) => 140 + u16::from(n),
+            key => {
+                let idx = NAMED_KEYS.iter().position(|(_, named)| *named == key);
+                128 + idx.unwrap_or_default() as u16
+            }
+        }
+    }
+}
+
+// a printable character as itself, space and the others by name: newline, left, f1
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if c.is_ascii_graphic() {
+                return Ok(Key::Char(c));
+            }
+        }
+
+        let name = s.to_ascii_lowercase();
+        if name == "space" {
+            return Ok(Key::Char(' '));
+        }
+        if let Some((_, key)) = NAMED_KEYS.iter().find(|(named, _)| *named == name) {
+            return Ok(*key);
+        }
+        match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+            Some(n @ 1..=12) => Ok(Key::F(n)),
+            _ => Err(format!(
+                "Unknown key {} (expected a printable character, space, newline, \
+                 backspace, left, up, right, down, home, end, page-up, page-down, \
+                 insert, delete, escape or f1 to f12)",
+                s
+            )),
+        }
+    }
+}
+
+/*
+ * Keys reported as other keys, say w as up for a game played with wasd.
+ * Host keys are named by what they type without shift
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyMap {
+    keys: BTreeMap<Key, Key>,
+}
+
+impl KeyMap {
+    pub fn new() -> Self {
+        KeyMap::default()
+    }
+
+    pub fn set(&mut self, host: Key, key: Key) {
+        self.keys.insert(host, key);
+    }
+
+    /* None for a key reported as what it is */
+    pub fn get(&self, host: Key) -> Option<Key> {
+        self.keys.get(&host).copied()
+    }
+}
@@ -0,0 +1,109 @@
+use alloc::collections::BTreeMap;
+use core::{fmt, str::FromStr};
+
+use crate::prelude::*;
+
+/*
+ * Names the labels the translator makes up itself, the ones eq / lt / gt jump
+ * between. Every comparison asks for a fresh id and builds its labels from it
+ * and a kind, JUMP_START, JUMP_END or CMP_RET. Ids must never repeat within a
+ * program
+ */
+pub trait LabelStrategy: fmt::Debug {
+    /* file is the stem of the file being translated, function the function if inside one */
+    fn next_id(&mut self, file: &str, function: Option<&str>) -> String;
+    fn label(&self, kind: &str, id: &str) -> String;
+
+    /*
+     * Hands the next n ids to code numbered elsewhere and returns the first of
+     * them, see parts. Only numbering across the whole program has to skip them
+     */
+    fn reserve(&mut self, _n: u16) -> u16 {
+        0
+    }
+}
+
+/* What generated labels are numbered across */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LabelScope {
+    /* one counter for the whole program, JUMP_START_17 */
+    #[default]
+    Program,
+    /* a counter per file, JUMP_START_Main.3 */
+    File,
+    /* a counter per function, JUMP_START_Main.run.0 */
+    Function,
+}
+
+impl FromStr for LabelScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "program" => Ok(LabelScope::Program),
+            "file" => Ok(LabelScope::File),
+            "function" => Ok(LabelScope::Function),
+            _ => Err(format!(
+                "Unknown label scope {} (expected program, file or function)",
+                s
+            )),
+        }
+    }
+}
+
+/*
+ * Numbers labels across the chosen scope and puts prefix in front of them. The
+ * default, a single counter and no prefix, gives the labels older versions did.
+ * Numbering per file or function keeps an edit from renaming every label after
+ * it, and a prefix keeps them apart from labels written in the VM code
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Labels {
+    prefix: String,
+    scope: LabelScope,
+    counters: BTreeMap<String, u16>,
+}
+
+impl Labels {
+    pub fn new(prefix: &str, scope: LabelScope) -> Self {
+        Labels {
+            prefix: prefix.to_owned(),
+            scope,
+            counters: BTreeMap::new(),
+        }
+    }
+}
+
+impl LabelStrategy for Labels {
+    fn next_id(&mut self, file: &str, function: Option<&str>) -> String {
+        let scope = match self.scope {
+            LabelScope::Program => "",
+            LabelScope::File => file,
+            LabelScope::Function => function.unwrap_or(file),
+        };
+        let counter = self.counters.entry(scope.to_owned()).or_default();
+        let n = *counter;
+        *counter += 1;
+
+        if scope.is_empty() {
+            n.to_string()
+        } else {
+            format!("{}.{}", scope, n)
+        }
+    }
+
+    fn label(&self, kind: &str, id: &str) -> String {
+        format!("{}{}_{}", self.prefix, kind, id)
+    }
+
+    fn reserve(&mut self, n: u16) -> u16 {
+        if self.scope != LabelScope::Program {
+            return 0;
+        }
+        let counter = self.counters.entry(String::new()).or_default();
+        let first = *counter;
+        *counter += n;
+
+        first
+    }
+}
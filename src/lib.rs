@@ -1,19 +1,270 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::{
+    fmt::{self, Write as _},
+    mem,
+    str::FromStr,
+};
+#[cfg(feature = "std")]
 use std::{
     fs::File,
-    io::{self, BufWriter, Write},
+    io,
     path::{Path, PathBuf},
-    str::FromStr,
 };
 
+#[cfg(feature = "std")]
+pub mod analysis;
+pub mod assembler;
+#[cfg(feature = "std")]
+pub mod cache;
+#[cfg(feature = "std")]
+pub mod callgraph;
+#[cfg(feature = "std")]
+pub mod config;
+pub mod coverage;
+pub mod debugger;
+pub mod diagnostics;
+pub mod disasm;
+pub mod emulator;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "gui")]
+pub mod gui;
+pub mod hack;
+pub mod heap;
+pub mod interpreter;
+pub mod keyboard;
+pub mod labels;
+pub mod lift;
+#[cfg(feature = "std")]
+pub mod listing;
+pub mod machine;
+pub mod memory;
+pub mod optimize;
+#[cfg(feature = "std")]
+pub mod os;
+pub mod output;
+mod parser;
+pub mod parts;
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod provenance;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "std")]
+pub mod scaffold;
+pub mod screen;
+pub mod sourcemap;
+mod span;
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod testgen;
+pub mod testscript;
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod verify;
+#[cfg(feature = "std")]
+pub mod vfs;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use error::VmError;
+pub use parser::{parse, Parser};
+#[cfg(feature = "std")]
+pub use parser::{parse_lines, ParseLines};
+pub use span::{Span, Spanned};
+pub use stats::TranslationStats;
+
+use hack::{at, jump, set, Comp, Dest, HackInstr, Jump};
+use labels::{LabelScope, LabelStrategy, Labels};
+use memory::StaticAllocator;
+use optimize::InlineFunction;
+use output::{AsmWrite, Discard, FmtWriter};
+use prelude::*;
+use sourcemap::{Mapping, SourceMap};
+
+// what the std prelude would bring in, for building without it
+mod prelude {
+    pub use alloc::{
+        borrow::ToOwned,
+        boxed::Box,
+        format,
+        string::{String, ToString},
+        vec,
+        vec::Vec,
+    };
+}
+
+/* Assembly collected before it is handed to the writer */
+const BUFFER_SIZE: usize = 8 * 1024;
+
 #[derive(Debug)]
-pub struct VMTranslator<W: Write> {
-    writer: BufWriter<W>,
-    next_jump: u16,
+pub struct VMTranslator<W: AsmWrite> {
+    /* None only once into_inner took it */
+    writer: Option<W>,
+    buffer: String,
+    /* names the labels of comparisons */
+    label_strategy: Box<dyn LabelStrategy>,
     ret_idx: u16,
+    /* names the statics, another file's while inlining its function */
     filestem: String,
+    /* stem of the file being translated, generated labels are numbered in it */
+    curr_file: String,
+    /* file being translated, for annotations and the source map */
+    source: String,
+    /* function currently being translated, labels are scoped to it */
+    curr_function: Option<String>,
+    /* scoped labels emitted so far */
+    labels: BTreeSet<String>,
+    options: TranslationOptions,
+    stats: TranslationStats,
+    /* static variables used so far */
+    statics: BTreeSet<String>,
+    /* the RAM address each static is emitted as */
+    static_addresses: StaticAllocator,
+    /* lines written so far, and the last of them holding code */
+    lines: usize,
+    last_code_line: usize,
+    source_map: SourceMap,
+    /* functions whose calls get replaced by their body */
+    inlines: BTreeMap<String, InlineFunction>,
+    /* end of the last command's code, held back by emit_code */
+    deferred: Option<(&'static str, Vec<HackInstr>)>,
+    /* what the last @ instruction emit_code let through loaded, if A still holds it */
+    a_register: Option<hack::Address>,
 }
 
-#[derive(Debug)]
+/* Code emitted by write_prelude before any translated command */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bootstrap {
+    /* SP = 256, call Sys.init */
+    Standard,
+    /* nothing, for the project 7 tests which set up the stack themselves */
+    None,
+    Custom { stack_base: u16, entry: String },
+}
+
+impl Bootstrap {
+    /* function the bootstrap jumps to */
+    pub fn entry(&self) -> Option<&str> {
+        match self {
+            Bootstrap::Standard => Some("Sys.init"),
+            Bootstrap::None => None,
+            Bootstrap::Custom { entry, .. } => Some(entry),
+        }
+    }
+
+    /* where SP starts */
+    pub fn stack_base(&self) -> Option<u16> {
+        match self {
+            Bootstrap::Standard => Some(memory::STACK_BASE),
+            Bootstrap::None => None,
+            Bootstrap::Custom { stack_base, .. } => Some(*stack_base),
+        }
+    }
+}
+
+/* Which passes of optimize run, each level includes the ones below */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    /* every command translated on its own, the code the book describes */
+    #[default]
+    None,
+    /* peepholes that only look at neighbouring commands */
+    Safe,
+    /* passes over the whole program: dropping dead functions, inlining, tail calls */
+    Aggressive,
+}
+
+impl TryFrom<u8> for OptLevel {
+    type Error = String;
+
+    fn try_from(level: u8) -> Result<Self, Self::Error> {
+        match level {
+            0 => Ok(OptLevel::None),
+            1 => Ok(OptLevel::Safe),
+            2 => Ok(OptLevel::Aggressive),
+            _ => Err(format!(
+                "Unknown optimization level {} (expected 0, 1 or 2)",
+                level
+            )),
+        }
+    }
+}
+
+impl FromStr for OptLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<u8>()
+            .map_err(|_| format!("Unknown optimization level {} (expected 0, 1 or 2)", s))?
+            .try_into()
+    }
+}
+
+impl fmt::Display for OptLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", *self as u8)
+    }
+}
+
+/*
+ * Everything that changes how a program gets translated, in one place. The
+ * command line fills it from its flags and hackvm.toml, library users start
+ * from Default, which gives the code the book describes: the standard
+ * bootstrap, every local zeroed and no optimizations. The constructors,
+ * the builder and translate_str all take one
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranslationOptions {
+    pub bootstrap: Bootstrap,
+    /* trailing tokens after a command are errors instead of warnings */
+    pub strict: bool,
+    /* precede the code of every command with a `// Foo.vm:17: push local 2` comment */
+    pub annotate: bool,
+    /* how hard to work on rewriting the code into a shorter equivalent, see optimize */
+    pub opt_level: OptLevel,
+    /* eq / lt / gt jump to one copy of their code in the prelude */
+    pub shared_comparisons: bool,
+    /* call and return jump to one copy of the frame handling in the prelude */
+    pub compact_calls: bool,
+    /* record where the code of every command ends up, see source_map */
+    pub source_map: bool,
+    /* set the locals of a function to 0 on entry, as the VM spec requires */
+    pub zero_locals: bool,
+    /* put in front of the labels generated for comparisons */
+    pub label_prefix: String,
+    /* what generated labels are numbered across */
+    pub label_scope: LabelScope,
+}
+
+impl Default for TranslationOptions {
+    fn default() -> Self {
+        TranslationOptions {
+            bootstrap: Bootstrap::Standard,
+            strict: false,
+            annotate: false,
+            opt_level: OptLevel::None,
+            shared_comparisons: false,
+            compact_calls: false,
+            source_map: false,
+            zero_locals: true,
+            label_prefix: String::new(),
+            label_scope: LabelScope::Program,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum MemorySegment {
     Local,
     Argument,
@@ -25,7 +276,13 @@ pub enum MemorySegment {
     Pointer,
 }
 
-#[derive(Debug)]
+/* with serde, the keywords name the variants: {"push": ["local", 2]} */
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum Command {
     /* Syntax: push / pop <segment> <offset> */
     Push(MemorySegment, u16),
@@ -55,306 +312,1161 @@ pub enum Command {
     Return,
 }
 
+#[cfg(feature = "std")]
 impl VMTranslator<File> {
     pub fn new(inpath: &Path) -> io::Result<Self> {
-        let outpath = inpath.with_extension("asm");
+        VMTranslator::with_options(inpath, TranslationOptions::default())
+    }
+
+    pub fn with_options(inpath: &Path, options: TranslationOptions) -> io::Result<Self> {
+        VMTranslator::with_output(inpath, &asm_path(inpath), options)
+    }
+
+    /* writes to outpath instead of next to the input */
+    pub fn with_output(
+        inpath: &Path,
+        outpath: &Path,
+        options: TranslationOptions,
+    ) -> io::Result<Self> {
         let outfile = File::create(outpath)?;
-        let writer = BufWriter::new(outfile);
+        // directories like `.` have no stem, update_filestem names the statics per file anyway
         let filestem = inpath
             .file_stem()
             .and_then(|stem| stem.to_str())
-            .unwrap()
-            .to_owned();
+            .unwrap_or_default();
 
-        Ok(VMTranslator {
+        Ok(VMTranslator::with_writer(outfile, filestem, options))
+    }
+}
+
+impl VMTranslator<Discard> {
+    /* output goes nowhere until the builder is given a writer */
+    pub fn builder() -> VMTranslatorBuilder<Discard> {
+        VMTranslatorBuilder {
+            writer: Discard,
+            filestem: String::new(),
+            options: TranslationOptions::default(),
+            label_strategy: None,
+            static_addresses: None,
+        }
+    }
+}
+
+/*
+ * Sets a translator up one knob at a time instead of through a constructor
+ * taking all of them:
+ *
+ *   let translator = VMTranslator::builder()
+ *       .writer(Vec::new())
+ *       .filestem("Main")
+ *       .opt_level(OptLevel::Safe)
+ *       .build();
+ *
+ * Whatever is left out is what with_writer would have used
+ */
+#[derive(Debug)]
+pub struct VMTranslatorBuilder<W: AsmWrite> {
+    writer: W,
+    filestem: String,
+    options: TranslationOptions,
+    /* labels built from the options unless replaced */
+    label_strategy: Option<Box<dyn LabelStrategy>>,
+    static_addresses: Option<StaticAllocator>,
+}
+
+impl<W: AsmWrite> VMTranslatorBuilder<W> {
+    pub fn writer<V: AsmWrite>(self, writer: V) -> VMTranslatorBuilder<V> {
+        VMTranslatorBuilder {
             writer,
-            next_jump: 0,
-            ret_idx: 0,
-            filestem,
-        })
+            filestem: self.filestem,
+            options: self.options,
+            label_strategy: self.label_strategy,
+            static_addresses: self.static_addresses,
+        }
+    }
+
+    /* names the statics until update_filestem is called */
+    pub fn filestem(mut self, filestem: &str) -> Self {
+        self.filestem = filestem.to_owned();
+        self
+    }
+
+    /* replaces every option set so far */
+    pub fn options(mut self, options: TranslationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn bootstrap(mut self, bootstrap: Bootstrap) -> Self {
+        self.options.bootstrap = bootstrap;
+        self
+    }
+
+    pub fn annotate(mut self, annotate: bool) -> Self {
+        self.options.annotate = annotate;
+        self
+    }
+
+    pub fn opt_level(mut self, opt_level: OptLevel) -> Self {
+        self.options.opt_level = opt_level;
+        self
+    }
+
+    pub fn label_strategy(mut self, strategy: impl LabelStrategy + 'static) -> Self {
+        self.label_strategy = Some(Box::new(strategy));
+        self
+    }
+
+    /* addresses allocated up front, e.g. by analysis::allocate_statics for the whole program */
+    pub fn static_addresses(mut self, statics: StaticAllocator) -> Self {
+        self.static_addresses = Some(statics);
+        self
+    }
+
+    pub fn build(self) -> VMTranslator<W> {
+        let mut translator = VMTranslator::with_writer(self.writer, &self.filestem, self.options);
+        if let Some(strategy) = self.label_strategy {
+            translator.label_strategy = strategy;
+        }
+        if let Some(statics) = self.static_addresses {
+            translator.static_addresses = statics;
+        }
+        translator
     }
 }
 
-impl<W: Write> VMTranslator<W> {
-    pub fn write_asm(&mut self, command: Command) -> io::Result<()> {
-        command.verify_offset();
+// like BufWriter, what is still buffered gets written when the translator goes away
+impl<W: AsmWrite> Drop for VMTranslator<W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
 
-        let asm = match command {
-            Command::Push(segment, offset) => match segment {
-                MemorySegment::Constant => format!("@{}\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1\n", offset),
-                MemorySegment::Static => {
-                    let static_label = format!("{}.{}", self.filestem, offset);
-                    format!("@{}\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n", static_label)
+/* Functions with more locals than this zero them in a loop instead of one by one */
+const UNROLLED_LOCALS: u16 = 8;
+
+fn return_asm() -> Vec<HackInstr> {
+    /*
+     * Copy LCL to R13
+     * Store return addr in R14
+     * Move return val to arg 0
+     * Move SP to *ARG + 1
+     * Restore THIS, THAT, ARG, LCL pointers
+     * Uncoditional jump to return addr
+     */
+    let mut return_asm = vec![
+        at("LCL"),
+        set(Dest::D, Comp::M),
+        at("R13"),
+        set(Dest::M, Comp::D),
+        at(memory::RETURN_ADDRESS_OFFSET),
+        set(Dest::D, Comp::DMinusA),
+        set(Dest::A, Comp::D),
+        set(Dest::D, Comp::M),
+        at("R14"),
+        set(Dest::M, Comp::D),
+    ];
+    return_asm.extend(pop_d());
+    return_asm.extend([
+        at("ARG"),
+        set(Dest::A, Comp::M),
+        set(Dest::M, Comp::D),
+        at("ARG"),
+        set(Dest::D, Comp::MPlusOne),
+        at("SP"),
+        set(Dest::M, Comp::D),
+    ]);
+    for (pointer, distance) in memory::SAVED_POINTERS {
+        return_asm.extend([
+            at("R13"),
+            set(Dest::D, Comp::M),
+            at(distance),
+            set(Dest::D, Comp::DMinusA),
+            set(Dest::A, Comp::D),
+            set(Dest::D, Comp::M),
+            at(pointer),
+            set(Dest::M, Comp::D),
+        ]);
+    }
+    return_asm.extend([
+        at("R14"),
+        set(Dest::A, Comp::M),
+        jump(Comp::Zero, Jump::Jmp),
+    ]);
+
+    return_asm
+}
+
+// *SP = D, SP++
+fn push_d() -> [HackInstr; 5] {
+    [
+        at("SP"),
+        set(Dest::A, Comp::M),
+        set(Dest::M, Comp::D),
+        at("SP"),
+        set(Dest::M, Comp::MPlusOne),
+    ]
+}
+
+// SP--, D = *SP
+fn pop_d() -> Vec<HackInstr> {
+    vec![
+        at("SP"),
+        set(Dest::M, Comp::MMinusOne),
+        set(Dest::A, Comp::M),
+        set(Dest::D, Comp::M),
+    ]
+}
+
+// y = pop, x = top, top = comp with D = y and M = x
+fn binary(comp: Comp) -> Vec<HackInstr> {
+    let mut asm = pop_d();
+    asm.extend([
+        at("SP"),
+        set(Dest::M, Comp::MMinusOne),
+        set(Dest::A, Comp::M),
+        set(Dest::M, comp),
+        at("SP"),
+        set(Dest::M, Comp::MPlusOne),
+    ]);
+
+    asm
+}
+
+fn unary(comp: Comp) -> Vec<HackInstr> {
+    vec![
+        at("SP"),
+        set(Dest::M, Comp::MMinusOne),
+        set(Dest::A, Comp::M),
+        set(Dest::M, comp),
+        at("SP"),
+        set(Dest::M, Comp::MPlusOne),
+    ]
+}
+
+fn pointer(offset: u16) -> &'static str {
+    if offset == 0 {
+        "THIS"
+    } else {
+        "THAT"
+    }
+}
+
+// what x - y is tested against for eq / lt / gt
+fn comparison_jump(comparison: &Command) -> Jump {
+    match comparison {
+        Command::Eq => Jump::Jeq,
+        Command::Lt => Jump::Jlt,
+        _ => Jump::Jgt,
+    }
+}
+
+fn count_instructions(asm: &[HackInstr]) -> usize {
+    asm.iter().filter(|instr| instr.is_instruction()).count()
+}
+
+// only allocates the key the first time it is counted
+fn add_count(counts: &mut BTreeMap<String, usize>, key: &str, n: usize) {
+    match counts.get_mut(key) {
+        Some(count) => *count += n,
+        None => {
+            counts.insert(key.to_owned(), n);
+        }
+    }
+}
+
+// dir/Foo.vm -> Foo.vm, without needing Path
+fn file_name(source: &str) -> &str {
+    source.rsplit(['/', '\\']).next().unwrap_or(source)
+}
+
+/*
+ * Translates the source of a single file in memory, filestem names its statics.
+ * Runs the same passes the command line would for opts
+ */
+pub fn translate_str(
+    source: &str,
+    filestem: &str,
+    opts: &TranslationOptions,
+) -> Result<String, VmError> {
+    let commands = Parser::new(source).strict(opts.strict).parse_all()?;
+    let mut translator =
+        VMTranslator::with_writer(FmtWriter(String::new()), filestem, opts.clone());
+
+    // the whole program passes work on analysis units, which need std
+    #[cfg(feature = "std")]
+    let commands = {
+        let mut units = vec![analysis::Unit::new(format!("{}.vm", filestem), commands)];
+        if opts.opt_level >= OptLevel::Aggressive {
+            if let Some(entry) = opts.bootstrap.entry() {
+                optimize::remove_dead_functions(&mut units, entry);
+            }
+            translator.inline_functions(optimize::inlinable_functions(&units));
+        }
+        units.remove(0).commands
+    };
+
+    translator.write_prelude()?;
+    translator.write_commands(commands)?;
+    translator.check_rom()?;
+
+    let FmtWriter(asm) = translator.into_inner()?;
+    Ok(asm)
+}
+
+/* Foo.vm -> Foo.asm, a directory Dir -> Dir/Dir.asm */
+#[cfg(feature = "std")]
+pub fn asm_path(inpath: &Path) -> PathBuf {
+    if inpath.is_dir() {
+        inpath.join(format!("{}.asm", dir_name(inpath)))
+    } else {
+        inpath.with_extension("asm")
+    }
+}
+
+/* Dir -> Dir.asm next to the directory, the layout older versions used */
+#[cfg(feature = "std")]
+pub fn sibling_asm_path(inpath: &Path) -> PathBuf {
+    if !inpath.is_dir() {
+        return asm_path(inpath);
+    }
+
+    let dir = inpath
+        .canonicalize()
+        .unwrap_or_else(|_| inpath.to_path_buf());
+    dir.with_file_name(format!("{}.asm", dir_name(&dir)))
+}
+
+// `.` and friends have no file name of their own
+#[cfg(feature = "std")]
+fn dir_name(dir: &Path) -> String {
+    match dir.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => dir
+            .canonicalize()
+            .ok()
+            .and_then(|dir| {
+                dir.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| "out".to_owned()),
+    }
+}
+
+impl<W: AsmWrite> VMTranslator<W> {
+    /* translates into a Vec<u8> or any other writer, with the default options */
+    pub fn from_writer(writer: W, filestem: &str) -> Self {
+        VMTranslator::with_writer(writer, filestem, TranslationOptions::default())
+    }
+
+    /* translates into any writer, filestem names the statics until update_filestem is called */
+    pub fn with_writer(writer: W, filestem: &str, options: TranslationOptions) -> Self {
+        VMTranslator {
+            writer: Some(writer),
+            buffer: String::new(),
+            label_strategy: Box::new(Labels::new(&options.label_prefix, options.label_scope)),
+            ret_idx: 0,
+            filestem: filestem.to_owned(),
+            curr_file: filestem.to_owned(),
+            source: format!("{}.vm", filestem),
+            curr_function: None,
+            labels: BTreeSet::new(),
+            options,
+            stats: TranslationStats::default(),
+            statics: BTreeSet::new(),
+            static_addresses: StaticAllocator::new(),
+            lines: 0,
+            last_code_line: 0,
+            source_map: SourceMap::new(),
+            inlines: BTreeMap::new(),
+            deferred: None,
+            a_register: None,
+        }
+    }
+
+    /* like write_asm, annotating the code with where the command came from if asked to */
+    pub fn write_command(&mut self, command: Spanned<Command>) -> Result<(), VmError> {
+        self.write_group(&[command])?;
+        self.emit_deferred();
+
+        self.write_out().map_err(Into::into)
+    }
+
+    /*
+     * Translates a run of commands, usually a whole file. Unlike write_command
+     * the optimizations see past a single command here and can fuse neighbours
+     */
+    pub fn write_commands(&mut self, mut commands: Vec<Spanned<Command>>) -> Result<(), VmError> {
+        if self.options.opt_level >= OptLevel::Safe {
+            commands = optimize::cancel_unary_pairs(commands);
+            commands = optimize::fold_constants(commands);
+        }
+        let mut commands = commands.into_iter().peekable();
+
+        while let Some(command) = commands.next() {
+            let level = self.options.opt_level;
+            let fuse = match (&command.node, commands.peek().map(|next| &next.node)) {
+                (Command::Push(..), Some(Command::Pop(..)))
+                | (Command::Eq | Command::Lt | Command::Gt, Some(Command::IfGoto(_))) => {
+                    level >= OptLevel::Safe
                 }
-                MemorySegment::Temp => format!("@{}\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n", 5 + offset),
-                MemorySegment::Pointer => {
-                    if offset == 0 {
-                        format!("@THIS\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n")
-                    } else {
-                        format!("@THAT\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n")
-                    }
+                // inlining beats reusing the frame, and there is no frame outside of a function
+                (Command::Call(function, _), Some(Command::Return)) => {
+                    level >= OptLevel::Aggressive
+                        && self.curr_function.is_some()
+                        && !self.inlines.contains_key(function)
                 }
+                _ => false,
+            };
 
-                _ => format!(
-                    "@{}\nD=A\n@{}\nA=D+M\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n",
-                    offset,
-                    segment.to_label(),
-                ),
-            },
+            match commands.next_if(|_| fuse) {
+                Some(next) => self.write_group(&[command, next])?,
+                None => self.write_group(&[command])?,
+            }
+        }
+        self.emit_deferred();
+
+        Ok(())
+    }
+
+    // one command, or neighbours fused into a single piece of code
+    fn write_group(&mut self, group: &[Spanned<Command>]) -> Result<(), VmError> {
+        if self.options.annotate {
+            let filename = file_name(&self.source).to_owned();
+            for command in group {
+                let annotation = format!("// {}:{}: {}", filename, command.span.line, command.node);
+                self.write_lines(&annotation);
+            }
+        }
+
+        let first_line = self.lines + 1;
+        let first_instruction = self.stats.instructions;
+
+        match group {
+            [command] => self
+                .write_asm(command.node.clone())
+                .map_err(|err| err.at(command.span))?,
+            [call, ret] if ret.node == Command::Return => {
+                let Command::Call(function, n_args) = &call.node else {
+                    unreachable!("only calls are fused with a return");
+                };
+                let label = format!("{}$tail.{}", function, self.ret_idx);
+                self.ret_idx += 1;
+                self.stats.calls += 1;
+
+                let asm = optimize::tail_call(function, *n_args, &label);
+                self.emit_code("call+return", asm);
+            }
+            [comparison, jump] if matches!(jump.node, Command::IfGoto(_)) => {
+                let Command::IfGoto(label) = &jump.node else {
+                    unreachable!("matched above");
+                };
+                self.stats.comparisons += 1;
+
+                let asm = optimize::compare_and_jump(&comparison.node, &self.scoped_label(label));
+                let keyword = match comparison.node {
+                    Command::Eq => "eq+if-goto",
+                    Command::Lt => "lt+if-goto",
+                    _ => "gt+if-goto",
+                };
+                self.emit_code(keyword, asm);
+            }
+            [push, pop] => {
+                let (Command::Push(from, src), Command::Pop(to, dst)) = (&push.node, &pop.node)
+                else {
+                    unreachable!(
+                        "only push / pop, comparison / if-goto and call / return pairs are fused"
+                    );
+                };
+                push.node.verify_offset().map_err(|err| err.at(push.span))?;
+                pop.node.verify_offset().map_err(|err| err.at(pop.span))?;
+
+                self.count_statics(&push.node);
+                self.count_statics(&pop.node);
+                let statics = (&mut self.static_addresses, self.filestem.as_str());
+                let asm = optimize::move_asm((*from, *src), (*to, *dst), statics)
+                    .map_err(|err| err.at(pop.span))?;
+                self.emit_code("push+pop", asm);
+            }
+            _ => unreachable!("at most two commands are fused"),
+        }
+
+        if self.options.source_map {
+            let vm: Vec<String> = group
+                .iter()
+                .map(|command| command.node.to_string())
+                .collect();
+            self.source_map.push(Mapping {
+                source: self.source.clone(),
+                line: group[0].span.line,
+                command: vm.join("; "),
+                asm_lines: (first_line, self.last_code_line.max(first_line)),
+                rom: (self.stats.instructions > first_instruction)
+                    .then(|| (first_instruction, self.stats.instructions - 1)),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn count_statics(&mut self, command: &Command) {
+        if let Command::Push(MemorySegment::Static, offset)
+        | Command::Pop(MemorySegment::Static, offset) = command
+        {
+            self.statics.insert(format!("{}.{}", self.filestem, offset));
+            self.stats.static_slots = self.statics.len();
+        }
+    }
+
+    fn static_address(&mut self, offset: u16) -> Result<u16, VmError> {
+        self.static_addresses.allocate(&self.filestem, offset)
+    }
+
+    pub fn write_asm(&mut self, command: Command) -> Result<(), VmError> {
+        command.verify_offset()?;
+
+        let keyword = command.keyword();
+        self.count_statics(&command);
+        match &command {
+            Command::Call(..) => self.stats.calls += 1,
+            Command::Eq | Command::Lt | Command::Gt => self.stats.comparisons += 1,
+            _ => (),
+        }
+
+        let asm = self.command_asm(command)?;
+        self.emit_code(keyword, asm);
+
+        self.write_out().map_err(Into::into)
+    }
+
+    // the code of a single command, without writing it anywhere
+    fn command_asm(&mut self, command: Command) -> Result<Vec<HackInstr>, VmError> {
+        let asm = match command {
+            Command::Push(segment, offset) => {
+                let mut asm = match segment {
+                    // the ALU makes 0 and 1 without loading them into A first
+                    MemorySegment::Constant
+                        if offset <= 1 && self.options.opt_level >= OptLevel::Safe =>
+                    {
+                        let value = if offset == 0 { Comp::Zero } else { Comp::One };
+                        vec![set(Dest::D, value)]
+                    }
+                    MemorySegment::Constant => vec![at(offset), set(Dest::D, Comp::A)],
+                    MemorySegment::Static => {
+                        vec![at(self.static_address(offset)?), set(Dest::D, Comp::M)]
+                    }
+                    MemorySegment::Temp => {
+                        vec![at(memory::TEMP_BASE + offset), set(Dest::D, Comp::M)]
+                    }
+                    MemorySegment::Pointer => vec![at(pointer(offset)), set(Dest::D, Comp::M)],
+
+                    _ => vec![
+                        at(offset),
+                        set(Dest::D, Comp::A),
+                        at(segment.to_label()?),
+                        set(Dest::A, Comp::DPlusM),
+                        set(Dest::D, Comp::M),
+                    ],
+                };
+                asm.extend(push_d());
+
+                asm
+            }
 
             Command::Pop(segment, offset) => match segment {
                 MemorySegment::Static => {
-                    let static_label = format!("{}.{}", self.filestem, offset);
-                    format!("@SP\nM=M-1\nA=M\nD=M\n@{}\nM=D\n", static_label)
+                    let mut asm = pop_d();
+                    asm.extend([at(self.static_address(offset)?), set(Dest::M, Comp::D)]);
+                    asm
+                }
+                MemorySegment::Temp => {
+                    let mut asm = pop_d();
+                    asm.extend([at(memory::TEMP_BASE + offset), set(Dest::M, Comp::D)]);
+                    asm
                 }
-                MemorySegment::Temp => format!("@SP\nM=M-1\nA=M\nD=M\n@{}\nM=D\n", 5 + offset),
                 MemorySegment::Pointer => {
-                    if offset == 0 {
-                        format!("@SP\nM=M-1\nA=M\nD=M\n@THIS\nM=D\n")
-                    } else {
-                        format!("@SP\nM=M-1\nA=M\nD=M\n@THAT\nM=D\n")
-                    }
+                    let mut asm = pop_d();
+                    asm.extend([at(pointer(offset)), set(Dest::M, Comp::D)]);
+                    asm
                 }
                 MemorySegment::Constant => {
-                    panic!("Pop operation cannot be performed for a constant")
+                    return Err(VmError::BadSegment {
+                        segment: "constant".to_owned(),
+                        reason: "pop operation cannot be performed for a constant".to_owned(),
+                    })
                 }
 
-                _ => format!(
-                    "@{}\nD=M\n@R13\nM=D\n@{}\nD=A\n@R13\nM=D+M\n\
-                    @SP\nM=M-1\nA=M\nD=M\n@R13\nA=M\nM=D\n",
-                    segment.to_label(),
-                    offset,
-                ),
+                _ => {
+                    let mut asm = vec![
+                        at(segment.to_label()?),
+                        set(Dest::D, Comp::M),
+                        at("R13"),
+                        set(Dest::M, Comp::D),
+                        at(offset),
+                        set(Dest::D, Comp::A),
+                        at("R13"),
+                        set(Dest::M, Comp::DPlusM),
+                    ];
+                    asm.extend(pop_d());
+                    asm.extend([at("R13"), set(Dest::A, Comp::M), set(Dest::M, Comp::D)]);
+                    asm
+                }
             },
 
-            Command::Add => format!("@SP\nM=M-1\nA=M\nD=M\n@SP\nM=M-1\nA=M\nM=D+M\n@SP\nM=M+1\n"),
-            Command::Sub => format!("@SP\nM=M-1\nA=M\nD=M\n@SP\nM=M-1\nA=M\nM=M-D\n@SP\nM=M+1\n"),
-            Command::Neg => format!("@SP\nM=M-1\nA=M\nM=-M\n@SP\nM=M+1\n"),
+            Command::Add => binary(Comp::DPlusM),
+            Command::Sub => binary(Comp::MMinusD),
+            Command::Neg => unary(Comp::NegM),
 
-            Command::Not => format!("@SP\nM=M-1\nA=M\nM=!M\n@SP\nM=M+1\n"),
-            Command::Or => format!("@SP\nM=M-1\nA=M\nD=M\n@SP\nM=M-1\nA=M\nM=D|M\n@SP\nM=M+1\n"),
-            Command::And => format!("@SP\nM=M-1\nA=M\nD=M\n@SP\nM=M-1\nA=M\nM=D&M\n@SP\nM=M+1\n"),
+            Command::Not => unary(Comp::NotM),
+            Command::Or => binary(Comp::DOrM),
+            Command::And => binary(Comp::DAndM),
 
-            Command::Eq => {
-                let (jump_start, jump_end) = self.jump_labels();
-                self.next_jump += 1;
-
-                format!(
-                    "@SP\nM=M-1\nA=M\nD=M\n@SP\nM=M-1\nA=M\nD=M-D\n\
-                    @{}\nD;JEQ\n@SP\nA=M\nM=0\n\
-                    @{}\n0;JMP\n\
-                    ({})\n@SP\nA=M\nM=-1\n\
-                    ({})\n@SP\nM=M+1",
-                    jump_start, jump_end, jump_start, jump_end
-                )
-            }
-            Command::Lt => {
-                let (jump_start, jump_end) = self.jump_labels();
-                self.next_jump += 1;
-
-                format!(
-                    "@SP\nM=M-1\nA=M\nD=M\n@SP\nM=M-1\nA=M\nD=M-D\n\
-                    @{}\nD;JLT\n@SP\nA=M\nM=0\n\
-                    @{}\n0;JMP\n\
-                    ({})\n@SP\nA=M\nM=-1\n\
-                    ({})\n@SP\nM=M+1",
-                    jump_start, jump_end, jump_start, jump_end
-                )
+            Command::Eq | Command::Lt | Command::Gt if self.options.shared_comparisons => {
+                let id = self.next_label_id();
+                let return_label = self.label_strategy.label("CMP_RET", &id);
+
+                optimize::comparison_call(&command, &return_label)
             }
-            Command::Gt => {
+            Command::Eq | Command::Lt | Command::Gt => {
                 let (jump_start, jump_end) = self.jump_labels();
-                self.next_jump += 1;
-
-                format!(
-                    "@SP\nM=M-1\nA=M\nD=M\n@SP\nM=M-1\nA=M\nD=M-D\n\
-                    @{}\nD;JGT\n@SP\nA=M\nM=0\n\
-                    @{}\n0;JMP\n\
-                    ({})\n@SP\nA=M\nM=-1\n\
-                    ({})\n@SP\nM=M+1",
-                    jump_start, jump_end, jump_start, jump_end
-                )
+
+                let mut asm = pop_d();
+                asm.extend([
+                    at("SP"),
+                    set(Dest::M, Comp::MMinusOne),
+                    set(Dest::A, Comp::M),
+                    set(Dest::D, Comp::MMinusD),
+                    at(&jump_start),
+                    jump(Comp::D, comparison_jump(&command)),
+                    at("SP"),
+                    set(Dest::A, Comp::M),
+                    set(Dest::M, Comp::Zero),
+                    at(&jump_end),
+                    jump(Comp::Zero, Jump::Jmp),
+                    HackInstr::Label(jump_start),
+                    at("SP"),
+                    set(Dest::A, Comp::M),
+                    set(Dest::M, Comp::MinusOne),
+                    HackInstr::Label(jump_end),
+                    at("SP"),
+                    set(Dest::M, Comp::MPlusOne),
+                ]);
+                asm
             }
 
             Command::Function(name, n_local_vars) => {
-                let mut func_asm = format!("({})\n", name);
-                for _ in 0..n_local_vars {
-                    func_asm.push_str(&format!("@SP\nA=M\nM=0\n@SP\nM=M+1\n"));
+                let mut func_asm = vec![HackInstr::Label(name.clone())];
+                if !self.options.zero_locals {
+                    if n_local_vars > 0 {
+                        func_asm.extend([
+                            at(n_local_vars),
+                            set(Dest::D, Comp::A),
+                            at("SP"),
+                            set(Dest::M, Comp::DPlusM),
+                        ]);
+                    }
+                } else if n_local_vars > UNROLLED_LOCALS {
+                    // user labels can't hold a $ of their own, so this can't clash with one
+                    let label = format!("{}$$locals", name);
+                    func_asm.extend([
+                        at(n_local_vars),
+                        set(Dest::D, Comp::A),
+                        HackInstr::Label(label.clone()),
+                        at("SP"),
+                        set(Dest::AM, Comp::MPlusOne),
+                        set(Dest::A, Comp::AMinusOne),
+                        set(Dest::M, Comp::Zero),
+                        set(Dest::D, Comp::DMinusOne),
+                        at(label),
+                        jump(Comp::D, Jump::Jgt),
+                    ]);
+                } else {
+                    for _ in 0..n_local_vars {
+                        func_asm.extend([
+                            at("SP"),
+                            set(Dest::A, Comp::M),
+                            set(Dest::M, Comp::Zero),
+                            at("SP"),
+                            set(Dest::M, Comp::MPlusOne),
+                        ]);
+                    }
                 }
+                self.curr_function = Some(name);
 
                 func_asm
             }
 
-            Command::Call(func_name, n_args) => self.translate_func_call(func_name, n_args),
-
-            Command::Return => {
-                /*
-                 * Copy LCL to R13
-                 * Store return addr in R14
-                 * Move return val to arg 0
-                 * Move SP to *ARG + 1
-                 * Restore THIS, THAT, ARG, LCL pointers
-                 * Uncoditional jump to return addr
-                 */
-                format!(
-                    "@LCL\nD=M\n@R13\nM=D\n\
-                    @5\nD=D-A\nA=D\nD=M\n@R14\nM=D\n\
-                    @SP\nM=M-1\nA=M\nD=M\n@ARG\nA=M\nM=D\n\
-                    @ARG\nD=M+1\n@SP\nM=D\n\
-                    @R13\nD=M\n@1\nD=D-A\nA=D\nD=M\n@THAT\nM=D\n\
-                    @R13\nD=M\n@2\nD=D-A\nA=D\nD=M\n@THIS\nM=D\n\
-                    @R13\nD=M\n@3\nD=D-A\nA=D\nD=M\n@ARG\nM=D\n\
-                    @R13\nD=M\n@4\nD=D-A\nA=D\nD=M\n@LCL\nM=D\n\
-                    @R14\nA=M\n0;JMP\n"
-                )
+            Command::Call(func_name, n_args) => match self.inline_call(&func_name, n_args) {
+                Some(asm) => asm,
+                None if self.options.compact_calls => {
+                    let return_label = format!("{}$ret.{}", func_name, self.ret_idx);
+                    self.ret_idx += 1;
+
+                    optimize::compact_call(&func_name, n_args, &return_label)
+                }
+                None => self.translate_func_call(func_name, n_args),
+            },
+            Command::Return if self.options.compact_calls => {
+                vec![at("$RETURN$"), jump(Comp::Zero, Jump::Jmp)]
             }
+            Command::Return => return_asm(),
 
-            Command::Label(label) => format!("({})\n", label),
-            Command::Goto(label) => format!("@{}\n0;JMP\n", label),
-            Command::IfGoto(label) => format!("@SP\nM=M-1\nA=M\nD=M\n@{}\nD;JNE\n", label),
+            Command::Label(label) => {
+                let scoped = self.scoped_label(&label);
+                if !self.labels.insert(scoped.clone()) {
+                    return Err(VmError::DuplicateLabel {
+                        label,
+                        function: self.curr_function.clone(),
+                    });
+                }
+                vec![HackInstr::Label(scoped)]
+            }
+            Command::Goto(label) => {
+                vec![at(self.scoped_label(&label)), jump(Comp::Zero, Jump::Jmp)]
+            }
+            Command::IfGoto(label) => {
+                let mut asm = pop_d();
+                asm.extend([at(self.scoped_label(&label)), jump(Comp::D, Jump::Jne)]);
+                asm
+            }
         };
 
-        writeln!(self.writer, "{}", asm)?;
+        Ok(asm)
+    }
+
+    /*
+     * The body of a function registered with inline_functions in place of a call
+     * to it, None when the call has to stay a call. The arguments stay on the stack
+     * where the call left them and the body reaches them and its locals relative to
+     * SP, whose distance to the frame is known at every command as the body can't jump
+     */
+    fn inline_call(&mut self, function: &str, n_args: u16) -> Option<Vec<HackInstr>> {
+        let inline = self.inlines.get(function)?.clone();
+        if inline.arguments > n_args {
+            return None;
+        }
+
+        let filestem = mem::replace(&mut self.filestem, inline.filestem.clone());
+        let asm = self.inline_asm(&inline, n_args);
+        self.filestem = filestem;
+
+        asm.ok()
+            .filter(|asm| count_instructions(asm) <= optimize::INLINE_THRESHOLD)
+    }
+
+    fn inline_asm(
+        &mut self,
+        inline: &InlineFunction,
+        n_args: u16,
+    ) -> Result<Vec<HackInstr>, VmError> {
+        // slots between the first argument and SP
+        let mut depth = n_args + inline.frame_size();
+        let locals = depth - inline.n_locals;
+        let mut asm = inline.prologue();
+
+        for command in &inline.body {
+            command.verify_offset()?;
+            let code = match command {
+                Command::Push(MemorySegment::Argument, offset) => {
+                    optimize::frame_push(depth - offset)
+                }
+                Command::Push(MemorySegment::Local, offset) => {
+                    optimize::frame_push(depth - locals - offset)
+                }
+                Command::Pop(MemorySegment::Argument, offset) => {
+                    optimize::frame_pop(depth - offset)
+                }
+                Command::Pop(MemorySegment::Local, offset) => {
+                    optimize::frame_pop(depth - locals - offset)
+                }
+                _ => {
+                    self.count_statics(command);
+                    self.command_asm(command.clone())?
+                }
+            };
+            asm.extend(code);
+            let (popped, pushed) = optimize::stack_effect(command);
+            depth = depth + pushed - popped;
+        }
+        asm.extend(inline.epilogue(n_args, depth));
+
+        Ok(asm)
+    }
+
+    /* replaces the default JUMP_START_0, JUMP_START_1, ... naming of generated labels */
+    pub fn label_strategy(&mut self, strategy: impl LabelStrategy + 'static) {
+        self.label_strategy = Box::new(strategy);
+    }
+
+    /* functions whose calls write_commands replaces with their body, see optimize::inlinable_functions */
+    pub fn inline_functions(&mut self, inlines: BTreeMap<String, InlineFunction>) {
+        self.inlines = inlines;
+    }
+
+    // emits the code of a command, adding its instructions to the stats
+    fn emit_code(&mut self, keyword: &'static str, mut asm: Vec<HackInstr>) {
+        if self.options.opt_level == OptLevel::None {
+            return self.account(keyword, &asm);
+        }
+
+        /*
+         * The SP increment a command ends with is held back until the next
+         * command is known, if that one starts by decrementing SP again both go
+         */
+        if let Some((_, mut deferred)) = self.deferred.take() {
+            deferred.append(&mut asm);
+            asm = deferred;
+        }
+        let mut asm = optimize::cancel_stack_adjustments(asm);
+        for tail in optimize::deferrable() {
+            if asm.ends_with(&tail) {
+                asm.truncate(asm.len() - tail.len());
+                self.deferred = Some((keyword, tail));
+                break;
+            }
+        }
+        let asm = optimize::skip_reloads(asm, &mut self.a_register);
+
+        if !asm.is_empty() {
+            self.account(keyword, &asm);
+        }
+    }
+
+    // whatever emit_code held back at the end of a run of commands
+    fn emit_deferred(&mut self) {
+        if let Some((keyword, asm)) = self.deferred.take() {
+            let asm = optimize::skip_reloads(asm, &mut self.a_register);
+            self.account(keyword, &asm);
+        }
+    }
+
+    fn account(&mut self, keyword: &'static str, asm: &[HackInstr]) {
+        let before = self.stats.instructions;
+        self.emit(asm);
+        let emitted = self.stats.instructions - before;
+
+        let owner = match &self.curr_function {
+            Some(function) => function,
+            None => &self.filestem,
+        };
+        *self.stats.by_command.entry(keyword).or_default() += emitted;
+        add_count(&mut self.stats.by_function, owner, emitted);
+        add_count(&mut self.stats.by_file, &self.source, emitted);
+    }
+
+    /* writes a chunk of assembly followed by a blank line, keeping count of its instructions */
+    fn emit(&mut self, asm: &[HackInstr]) {
+        self.stats.instructions += count_instructions(asm);
+
+        // formatted straight into the buffer, one line per instruction
+        for instr in asm {
+            let _ = writeln!(self.buffer, "{}", instr);
+        }
+        self.buffer.push('\n');
+        if !asm.is_empty() {
+            self.last_code_line = self.lines + asm.len();
+        }
+        self.lines += asm.len() + 1;
+    }
+
+    // only buffers, write_out hands the text to the writer
+    fn write_lines(&mut self, text: &str) {
+        let code = text.trim_end_matches('\n');
+        if !code.is_empty() {
+            self.last_code_line = self.lines + code.lines().count();
+        }
+        self.lines += text.matches('\n').count() + 1;
+
+        self.buffer.push_str(text);
+        self.buffer.push('\n');
+    }
+
+    // passes the buffer on once it is large enough to be worth a write
+    fn write_out(&mut self) -> Result<(), W::Error> {
+        if self.buffer.len() < BUFFER_SIZE {
+            return Ok(());
+        }
+        self.write_buffer()
+    }
+
+    fn write_buffer(&mut self) -> Result<(), W::Error> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.write_asm(&self.buffer)?;
+        }
+        self.buffer.clear();
 
         Ok(())
     }
 
-    pub fn translate_func_call(&mut self, func_name: String, n_args: u16) -> String {
+    /* every command written with write_command so far, if options.source_map is set */
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    /* instructions written so far, labels and blank lines excluded */
+    pub fn instructions(&self) -> usize {
+        self.stats.instructions
+    }
+
+    pub fn stats(&self) -> &TranslationStats {
+        &self.stats
+    }
+
+    /*
+     * Where the statics are in RAM. Statics missing from the allocator the
+     * translator started with get the next free address when first used
+     */
+    pub fn static_addresses(&self) -> &StaticAllocator {
+        &self.static_addresses
+    }
+
+    /* Err once the program written so far no longer fits in the ROM */
+    pub fn check_rom(&self) -> Result<(), VmError> {
+        if self.stats.instructions <= memory::ROM_SIZE {
+            return Ok(());
+        }
+
+        Err(VmError::RomOverflow {
+            instructions: self.stats.instructions,
+            largest: self.stats.largest_functions(5),
+        })
+    }
+
+    pub fn translate_func_call(&mut self, func_name: String, n_args: u16) -> Vec<HackInstr> {
         let ret_addr = format!("{}$ret.{}", func_name, self.ret_idx);
         self.ret_idx += 1;
 
         /* save current function frame */
         // return address in the ROM
-        let mut call_asm = format!("@{}\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1\n", ret_addr);
+        let mut call_asm = vec![at(&ret_addr), set(Dest::D, Comp::A)];
+        call_asm.extend(push_d());
 
         // recording segment pointers
         ["LCL", "ARG", "THIS", "THAT"].iter().for_each(|segment| {
-            call_asm.push_str(&format!("@{}\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n", segment));
+            call_asm.extend([at(*segment), set(Dest::D, Comp::M)]);
+            call_asm.extend(push_d());
         });
 
         // setting LCL to SP
-        call_asm.push_str(&format!("@SP\nD=M\n@LCL\nM=D\n"));
+        call_asm.extend([
+            at("SP"),
+            set(Dest::D, Comp::M),
+            at("LCL"),
+            set(Dest::M, Comp::D),
+        ]);
         // setting arg 0 to first arg pushed onto stack
-        call_asm.push_str(&format!(
-            "@SP\nD=M\n@{}\nD=D-A\n@5\nD=D-A\n@ARG\nM=D\n",
-            n_args
-        ));
+        call_asm.extend([
+            at("SP"),
+            set(Dest::D, Comp::M),
+            at(n_args),
+            set(Dest::D, Comp::DMinusA),
+            at(memory::FRAME_SIZE),
+            set(Dest::D, Comp::DMinusA),
+            at("ARG"),
+            set(Dest::M, Comp::D),
+        ]);
 
-        call_asm.push_str(&format!("@{}\n0;JMP\n", func_name));
-        call_asm.push_str(&format!("({})\n", ret_addr));
+        call_asm.extend([at(func_name), jump(Comp::Zero, Jump::Jmp)]);
+        call_asm.push(HackInstr::Label(ret_addr));
 
         call_asm
     }
 
-    pub fn write_prelude(&mut self) -> io::Result<()> {
-        writeln!(self.writer, "@256\nD=A\n@SP\nM=D\n\n")?;
-        let sys_init = self.translate_func_call("Sys.init".into(), 0);
-        writeln!(self.writer, "{}", sys_init)?;
-        Ok(())
+    pub fn flush(&mut self) -> Result<(), W::Error> {
+        self.write_buffer()?;
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush_asm(),
+            None => Ok(()),
+        }
+    }
+
+    /* flushes and hands back the writer, e.g. the Vec<u8> given to from_writer */
+    pub fn into_inner(mut self) -> Result<W, W::Error> {
+        self.flush()?;
+        Ok(self
+            .writer
+            .take()
+            .expect("only into_inner takes the writer"))
+    }
+
+    pub fn write_header(&mut self, header: &str) -> Result<(), W::Error> {
+        self.write_lines(header);
+        self.write_out()
+    }
+
+    /* the bootstrap, then the shared routines some options jump to */
+    pub fn write_prelude(&mut self) -> Result<(), W::Error> {
+        let before = self.stats.instructions;
+
+        let bootstrap = &self.options.bootstrap;
+        if let (Some(stack_base), Some(entry)) = (bootstrap.stack_base(), bootstrap.entry()) {
+            let entry = entry.to_owned();
+            self.emit(&[
+                at(stack_base),
+                set(Dest::D, Comp::A),
+                at("SP"),
+                set(Dest::M, Comp::D),
+            ]);
+            let call_entry = self.translate_func_call(entry, 0);
+            self.emit(&call_entry);
+        }
+
+        // jumped over, so it's fine without the bootstrap too
+        if let Some(runtime) = optimize::runtime(&self.options) {
+            self.emit(&runtime);
+        }
+        self.stats.bootstrap = self.stats.instructions - before;
+
+        self.write_out()
+    }
+
+    pub fn options(&self) -> &TranslationOptions {
+        &self.options
+    }
+
+    /* moves on to the next file, filestem names its statics and source labels its code */
+    pub fn start_file(&mut self, filestem: &str, source: &str) {
+        self.curr_function = None;
+        self.filestem = filestem.to_owned();
+        self.curr_file = filestem.to_owned();
+        self.source = source.to_owned();
     }
 
-    pub fn update_filestem(&mut self, curr_file: &PathBuf) {
-        self.filestem = curr_file
+    #[cfg(feature = "std")]
+    pub fn update_filestem(&mut self, curr_file: &Path) {
+        let filestem = curr_file
             .file_stem()
             .and_then(|stem| stem.to_str())
-            .unwrap()
-            .to_owned();
+            .unwrap();
+        self.start_file(filestem, &curr_file.display().to_string());
     }
 
-    fn jump_labels(&self) -> (String, String) {
+    // labels are emitted as FunctionName$label, or verbatim outside of any function
+    fn scoped_label(&self, label: &str) -> String {
+        match &self.curr_function {
+            Some(function) => format!("{}${}", function, label),
+            None => label.to_owned(),
+        }
+    }
+
+    fn next_label_id(&mut self) -> String {
+        self.label_strategy
+            .next_id(&self.curr_file, self.curr_function.as_deref())
+    }
+
+    fn jump_labels(&mut self) -> (String, String) {
+        let id = self.next_label_id();
         (
-            format!("JUMP_START_{}", self.next_jump),
-            format!("JUMP_END_{}", self.next_jump),
+            self.label_strategy.label("JUMP_START", &id),
+            self.label_strategy.label("JUMP_END", &id),
         )
     }
 }
 
-pub fn parse(line: &str) -> Result<Command, String> {
-    let parts: Vec<_> = line.split_whitespace().collect();
-    let command = match parts[0] {
-        "push" => Command::Push(
-            MemorySegment::from_str(parts[1])?,
-            parts[2].parse::<u16>().map_err(|e| e.to_string())?,
-        ),
-
-        "pop" => Command::Pop(
-            MemorySegment::from_str(parts[1])?,
-            parts[2].parse::<u16>().map_err(|e| e.to_string())?,
-        ),
-
-        "add" => Command::Add,
-        "sub" => Command::Sub,
-        "neg" => Command::Neg,
-        "not" => Command::Not,
-        "or" => Command::Or,
-        "and" => Command::And,
-        "eq" => Command::Eq,
-        "lt" => Command::Lt,
-        "gt" => Command::Gt,
-
-        "label" => Command::Label(parts[1].to_owned()),
-        "goto" => Command::Goto(parts[1].to_owned()),
-        "if-goto" => Command::IfGoto(parts[1].to_owned()),
-
-        "function" => Command::Function(
-            parts[1].to_owned(),
-            parts[2].parse::<u16>().map_err(|e| e.to_string())?,
-        ),
-        "call" => Command::Call(
-            parts[1].to_owned(),
-            parts[2].parse::<u16>().map_err(|e| e.to_string())?,
-        ),
-        "return" => Command::Return,
-
-        _ => return Err(format!("Unknown command {}", parts[0])),
-    };
+impl Command {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Command::Push(..) => "push",
+            Command::Pop(..) => "pop",
+            Command::Add => "add",
+            Command::Sub => "sub",
+            Command::Neg => "neg",
+            Command::Not => "not",
+            Command::Or => "or",
+            Command::And => "and",
+            Command::Eq => "eq",
+            Command::Lt => "lt",
+            Command::Gt => "gt",
+            Command::Label(_) => "label",
+            Command::Goto(_) => "goto",
+            Command::IfGoto(_) => "if-goto",
+            Command::Function(..) => "function",
+            Command::Call(..) => "call",
+            Command::Return => "return",
+        }
+    }
 
-    Ok(command)
-}
+    /* the command as it would be written in a .vm file, same as to_string */
+    pub fn to_vm_string(&self) -> String {
+        self.to_string()
+    }
 
-impl Command {
-    fn verify_offset(&self) {
+    fn verify_offset(&self) -> Result<(), VmError> {
         match self {
             Command::Push(segment, offset) | Command::Pop(segment, offset) => {
-                match segment {
-                    MemorySegment::Static => {
-                        if *offset > 238 {
-                            // RAM[16-255]
-                            panic!("Received offset out of STATIC range (238 reg)  {}", offset);
-                        }
-                    }
-                    MemorySegment::Temp => {
-                        if *offset > 7 {
-                            // RAM[5-12]
-                            panic!("Received offset out of TEMP range (8 reg)  {}", offset);
-                        }
+                match segment.capacity() {
+                    // STATIC: RAM[16-255], TEMP: RAM[5-12], POINTER: THIS / THAT
+                    Some(capacity) if *offset >= capacity => Err(VmError::OffsetOutOfRange {
+                        segment: *segment,
+                        offset: *offset,
+                        capacity,
+                        command: self.clone(),
+                    }),
+                    _ if *segment == MemorySegment::Constant && *offset > memory::MAX_CONSTANT => {
+                        Err(VmError::ConstantOutOfRange(offset.to_string()))
                     }
-                    MemorySegment::Pointer => {
-                        if !(0..=1).contains(offset) {
-                            panic!("POINTER offset can be either 0 or 1 | Received {}", offset);
-                        }
-                    }
-                    _ => (),
+                    _ => Ok(()),
                 }
             }
-            _ => (),
+            _ => Ok(()),
         }
     }
 }
 
+/* VM syntax, parse reads it back into the same command */
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Push(segment, offset) | Command::Pop(segment, offset) => {
+                write!(f, "{} {} {}", self.keyword(), segment, offset)
+            }
+            Command::Label(label) | Command::Goto(label) | Command::IfGoto(label) => {
+                write!(f, "{} {}", self.keyword(), label)
+            }
+            Command::Function(name, n) | Command::Call(name, n) => {
+                write!(f, "{} {} {}", self.keyword(), name, n)
+            }
+            _ => f.write_str(self.keyword()),
+        }
+    }
+}
+
+impl fmt::Display for MemorySegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 impl MemorySegment {
-    fn to_label(&self) -> &str {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MemorySegment::Local => "local",
+            MemorySegment::Argument => "argument",
+            MemorySegment::This => "this",
+            MemorySegment::That => "that",
+            MemorySegment::Constant => "constant",
+            MemorySegment::Static => "static",
+            MemorySegment::Temp => "temp",
+            MemorySegment::Pointer => "pointer",
+        }
+    }
+
+    fn to_label(self) -> Result<&'static str, VmError> {
         match self {
-            MemorySegment::Local => "LCL",
-            MemorySegment::Argument => "ARG",
-            MemorySegment::This => "THIS",
-            MemorySegment::That => "THAT",
-            _ => panic!("Shoudln't have come here"),
+            MemorySegment::Local => Ok("LCL"),
+            MemorySegment::Argument => Ok("ARG"),
+            MemorySegment::This => Ok("THIS"),
+            MemorySegment::That => Ok("THAT"),
+            _ => Err(VmError::BadSegment {
+                segment: format!("{:?}", self),
+                reason: "segment has no base pointer".to_owned(),
+            }),
         }
     }
 }
 
 impl FromStr for MemorySegment {
-    type Err = String;
+    type Err = VmError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let segment = match s {
@@ -366,17 +1478,14 @@ impl FromStr for MemorySegment {
             "static" => MemorySegment::Static,
             "temp" => MemorySegment::Temp,
             "pointer" => MemorySegment::Pointer,
-            _ => return Err(format!("Received unknown memory segment {}", s)),
+            _ => {
+                return Err(VmError::BadSegment {
+                    segment: s.to_owned(),
+                    reason: "unknown memory segment".to_owned(),
+                })
+            }
         };
 
         Ok(segment)
     }
 }
-
-impl<W: Write> Drop for VMTranslator<W> {
-    fn drop(&mut self) {
-        if let Err(err) = self.writer.flush() {
-            panic!("Couldn't flush writer: {}", err)
-        }
-    }
-}
@@ -0,0 +1,476 @@
+use alloc::collections::BTreeSet;
+
+use crate::{
+    binary,
+    hack::{self, at, jump, set, Address, Comp, Dest, HackInstr, Jump},
+    memory, pop_d,
+    prelude::*,
+    push_d, return_asm, unary, Command, MemorySegment,
+};
+
+/*
+ * Best effort: reads the VM commands back out of assembly by recognizing the
+ * code translate gives every command. That is the code of -O0 without
+ * --compact-calls or --shared-comparisons, anything else is only partly
+ * recognized. Instructions matching no command are skipped and reported:
+ *
+ *   let lifted = lift::lift(&asm)?;
+ *   assert!(lifted.unrecognized.is_empty());
+ *
+ * Code doesn't say which file it came from, statics come back numbered by
+ * their slot from RAM[16] on and labels outside of a function as they were
+ * written
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lifted {
+    pub commands: Vec<Command>,
+    /* ROM addresses of the instructions no command's code matched */
+    pub unrecognized: Vec<usize>,
+}
+
+/* One step of a command's code: an instruction as is, or a part read off it */
+#[derive(Debug, Clone)]
+enum Pat {
+    Is(HackInstr),
+    /* @<number> */
+    Value,
+    /* @<symbol> */
+    Symbol,
+    /* (<name>) */
+    Label,
+}
+
+/* Assembly with errors carrying the 1-based line, like hack::parse */
+pub fn lift(asm: &str) -> Result<Lifted, (usize, String)> {
+    Ok(lift_instrs(&hack::parse(asm)?))
+}
+
+pub fn lift_instrs(instrs: &[HackInstr]) -> Lifted {
+    let functions = called_functions(instrs);
+    let mut lifted = Lifted::default();
+    let mut function: Option<String> = None;
+    let mut pos = 0;
+
+    // the bootstrap sets SP and calls the entry point, neither is a command of the program
+    let setup = bootstrap();
+    if matches(&setup, instrs).is_some() {
+        if let Some((Command::Call(..), len)) = lift_call(&instrs[setup.len()..]) {
+            pos = setup.len() + len;
+        }
+    }
+    let mut rom = count_rom(&instrs[..pos]);
+
+    while pos < instrs.len() {
+        let rest = &instrs[pos..];
+        let found = lift_function(rest, &functions)
+            .or_else(|| lift_call(rest))
+            .or_else(|| lift_return(rest))
+            .or_else(|| lift_push(rest))
+            .or_else(|| lift_pop(rest))
+            .or_else(|| lift_arithmetic(rest))
+            .or_else(|| lift_branch(rest));
+
+        let Some((command, len)) = found else {
+            if rest[0].is_instruction() {
+                lifted.unrecognized.push(rom);
+                rom += 1;
+            }
+            pos += 1;
+            continue;
+        };
+        let command = match command {
+            Command::Function(name, locals) => {
+                function = Some(name.clone());
+                Command::Function(name, locals)
+            }
+            Command::Label(label) => Command::Label(unscoped(label, &function)),
+            Command::Goto(label) => Command::Goto(unscoped(label, &function)),
+            Command::IfGoto(label) => Command::IfGoto(unscoped(label, &function)),
+            command => command,
+        };
+        lifted.commands.push(command);
+        rom += count_rom(&rest[..len]);
+        pos += len;
+    }
+
+    lifted
+}
+
+fn lift_function(instrs: &[HackInstr], functions: &BTreeSet<String>) -> Option<(Command, usize)> {
+    let HackInstr::Label(name) = instrs.first()? else {
+        return None;
+    };
+    let called = functions.contains(name);
+    // a function nothing calls is told from a label outside of any by its Class.name
+    if !called && (name.contains('$') || !name.contains('.')) {
+        return None;
+    }
+
+    // more locals than are zeroed one by one
+    let looped = code([
+        Pat::Label,
+        Pat::Value,
+        Pat::Is(set(Dest::D, Comp::A)),
+        Pat::Label,
+        Pat::Is(at("SP")),
+        Pat::Is(set(Dest::AM, Comp::MPlusOne)),
+        Pat::Is(set(Dest::A, Comp::AMinusOne)),
+        Pat::Is(set(Dest::M, Comp::Zero)),
+        Pat::Is(set(Dest::D, Comp::DMinusOne)),
+        Pat::Symbol,
+        Pat::Is(jump(Comp::D, Jump::Jgt)),
+    ]);
+    if let Some(parts) = matches(&looped, instrs) {
+        let label = format!("{}$$locals", name);
+        if text(parts[2]) == label && text(parts[3]) == label {
+            return Some((
+                Command::Function(name.clone(), value(parts[1])),
+                looped.len(),
+            ));
+        }
+    }
+    // --no-zero-locals only moves SP past them
+    let skipped = code([
+        Pat::Label,
+        Pat::Value,
+        Pat::Is(set(Dest::D, Comp::A)),
+        Pat::Is(at("SP")),
+        Pat::Is(set(Dest::M, Comp::DPlusM)),
+    ]);
+    if let Some(parts) = matches(&skipped, instrs) {
+        return Some((
+            Command::Function(name.clone(), value(parts[1])),
+            skipped.len(),
+        ));
+    }
+
+    let zero = exactly([
+        at("SP"),
+        set(Dest::A, Comp::M),
+        set(Dest::M, Comp::Zero),
+        at("SP"),
+        set(Dest::M, Comp::MPlusOne),
+    ]);
+    let mut locals = 0;
+    while matches(&zero, &instrs[1 + zero.len() * locals..]).is_some() {
+        locals += 1;
+    }
+    Some((
+        Command::Function(name.clone(), locals as u16),
+        1 + zero.len() * locals,
+    ))
+}
+
+fn lift_call(instrs: &[HackInstr]) -> Option<(Command, usize)> {
+    let mut call = with_push([Pat::Symbol, Pat::Is(set(Dest::D, Comp::A))]);
+    for pointer in ["LCL", "ARG", "THIS", "THAT"] {
+        call.extend(with_push([
+            Pat::Is(at(pointer)),
+            Pat::Is(set(Dest::D, Comp::M)),
+        ]));
+    }
+    call.extend(code([
+        Pat::Is(at("SP")),
+        Pat::Is(set(Dest::D, Comp::M)),
+        Pat::Is(at("LCL")),
+        Pat::Is(set(Dest::M, Comp::D)),
+        Pat::Is(at("SP")),
+        Pat::Is(set(Dest::D, Comp::M)),
+        Pat::Value,
+        Pat::Is(set(Dest::D, Comp::DMinusA)),
+        Pat::Is(at(memory::FRAME_SIZE)),
+        Pat::Is(set(Dest::D, Comp::DMinusA)),
+        Pat::Is(at("ARG")),
+        Pat::Is(set(Dest::M, Comp::D)),
+        Pat::Symbol,
+        Pat::Is(jump(Comp::Zero, Jump::Jmp)),
+        Pat::Label,
+    ]));
+
+    let parts = matches(&call, instrs)?;
+    let (ret, n_args, function, ret_label) = (parts[0], parts[1], parts[2], parts[3]);
+    if text(ret) != text(ret_label) {
+        return None;
+    }
+    Some((
+        Command::Call(text(function).to_owned(), value(n_args)),
+        call.len(),
+    ))
+}
+
+fn lift_return(instrs: &[HackInstr]) -> Option<(Command, usize)> {
+    let code = exactly(return_asm());
+    matches(&code, instrs)?;
+    Some((Command::Return, code.len()))
+}
+
+fn lift_push(instrs: &[HackInstr]) -> Option<(Command, usize)> {
+    // through a pointer: push local 2
+    let pointed = with_push([
+        Pat::Value,
+        Pat::Is(set(Dest::D, Comp::A)),
+        Pat::Symbol,
+        Pat::Is(set(Dest::A, Comp::DPlusM)),
+        Pat::Is(set(Dest::D, Comp::M)),
+    ]);
+    if let Some(parts) = matches(&pointed, instrs) {
+        if let Some(segment) = pointed_segment(parts[1]) {
+            return Some((Command::Push(segment, value(parts[0])), pointed.len()));
+        }
+    }
+
+    let constant = with_push([Pat::Value, Pat::Is(set(Dest::D, Comp::A))]);
+    if let Some(parts) = matches(&constant, instrs) {
+        let command = Command::Push(MemorySegment::Constant, value(parts[0]));
+        return Some((command, constant.len()));
+    }
+
+    // at a fixed address: static, temp and pointer
+    for address in [Pat::Value, Pat::Symbol] {
+        let fixed = with_push([address, Pat::Is(set(Dest::D, Comp::M))]);
+        if let Some((segment, index)) =
+            matches(&fixed, instrs).and_then(|parts| fixed_segment(parts[0]))
+        {
+            return Some((Command::Push(segment, index), fixed.len()));
+        }
+    }
+
+    None
+}
+
+fn lift_pop(instrs: &[HackInstr]) -> Option<(Command, usize)> {
+    // the address goes to R13 before the value is popped
+    let mut pointed = code([
+        Pat::Symbol,
+        Pat::Is(set(Dest::D, Comp::M)),
+        Pat::Is(at("R13")),
+        Pat::Is(set(Dest::M, Comp::D)),
+        Pat::Value,
+        Pat::Is(set(Dest::D, Comp::A)),
+        Pat::Is(at("R13")),
+        Pat::Is(set(Dest::M, Comp::DPlusM)),
+    ]);
+    pointed.extend(exactly(pop_d()));
+    pointed.extend(exactly([
+        at("R13"),
+        set(Dest::A, Comp::M),
+        set(Dest::M, Comp::D),
+    ]));
+    if let Some(parts) = matches(&pointed, instrs) {
+        if let Some(segment) = pointed_segment(parts[0]) {
+            return Some((Command::Pop(segment, value(parts[1])), pointed.len()));
+        }
+    }
+
+    for address in [Pat::Value, Pat::Symbol] {
+        let mut fixed = exactly(pop_d());
+        fixed.extend(code([address, Pat::Is(set(Dest::M, Comp::D))]));
+        if let Some((segment, index)) =
+            matches(&fixed, instrs).and_then(|parts| fixed_segment(parts[0]))
+        {
+            return Some((Command::Pop(segment, index), fixed.len()));
+        }
+    }
+
+    None
+}
+
+fn lift_arithmetic(instrs: &[HackInstr]) -> Option<(Command, usize)> {
+    let computed = [
+        (Command::Add, binary(Comp::DPlusM)),
+        (Command::Sub, binary(Comp::MMinusD)),
+        (Command::And, binary(Comp::DAndM)),
+        (Command::Or, binary(Comp::DOrM)),
+        (Command::Neg, unary(Comp::NegM)),
+        (Command::Not, unary(Comp::NotM)),
+    ];
+    for (command, asm) in computed {
+        let code = exactly(asm);
+        if matches(&code, instrs).is_some() {
+            return Some((command, code.len()));
+        }
+    }
+
+    for (command, test) in [
+        (Command::Eq, Jump::Jeq),
+        (Command::Lt, Jump::Jlt),
+        (Command::Gt, Jump::Jgt),
+    ] {
+        let mut comparison = exactly(pop_d());
+        comparison.extend(code([
+            Pat::Is(at("SP")),
+            Pat::Is(set(Dest::M, Comp::MMinusOne)),
+            Pat::Is(set(Dest::A, Comp::M)),
+            Pat::Is(set(Dest::D, Comp::MMinusD)),
+            Pat::Symbol,
+            Pat::Is(jump(Comp::D, test)),
+            Pat::Is(at("SP")),
+            Pat::Is(set(Dest::A, Comp::M)),
+            Pat::Is(set(Dest::M, Comp::Zero)),
+            Pat::Symbol,
+            Pat::Is(jump(Comp::Zero, Jump::Jmp)),
+            Pat::Label,
+            Pat::Is(at("SP")),
+            Pat::Is(set(Dest::A, Comp::M)),
+            Pat::Is(set(Dest::M, Comp::MinusOne)),
+            Pat::Label,
+            Pat::Is(at("SP")),
+            Pat::Is(set(Dest::M, Comp::MPlusOne)),
+        ]));
+        if let Some(parts) = matches(&comparison, instrs) {
+            let (start, end) = (text(parts[0]), text(parts[1]));
+            if text(parts[2]) == start && text(parts[3]) == end {
+                return Some((command, comparison.len()));
+            }
+        }
+    }
+
+    None
+}
+
+fn lift_branch(instrs: &[HackInstr]) -> Option<(Command, usize)> {
+    let mut if_goto = exactly(pop_d());
+    if_goto.extend(code([Pat::Symbol, Pat::Is(jump(Comp::D, Jump::Jne))]));
+    if let Some(parts) = matches(&if_goto, instrs) {
+        return Some((Command::IfGoto(text(parts[0]).to_owned()), if_goto.len()));
+    }
+
+    let goto = code([Pat::Symbol, Pat::Is(jump(Comp::Zero, Jump::Jmp))]);
+    if let Some(parts) = matches(&goto, instrs) {
+        return Some((Command::Goto(text(parts[0]).to_owned()), goto.len()));
+    }
+
+    match instrs.first()? {
+        HackInstr::Label(label) => Some((Command::Label(label.clone()), 1)),
+        _ => None,
+    }
+}
+
+// SP = <stack base>, followed by the call to the entry point
+fn bootstrap() -> Vec<Pat> {
+    code([
+        Pat::Value,
+        Pat::Is(set(Dest::D, Comp::A)),
+        Pat::Is(at("SP")),
+        Pat::Is(set(Dest::M, Comp::D)),
+    ])
+}
+
+// the functions some call jumps to, right before its return address
+fn called_functions(instrs: &[HackInstr]) -> BTreeSet<String> {
+    let mut functions = BTreeSet::new();
+    for window in instrs.windows(3) {
+        if let [HackInstr::AInstr(Address::Symbol(function)), HackInstr::CInstr {
+            jump: Some(Jump::Jmp),
+            ..
+        }, HackInstr::Label(ret)] = window
+        {
+            if ret.starts_with(&format!("{}$ret.", function)) {
+                functions.insert(function.to_string());
+            }
+        }
+    }
+
+    functions
+}
+
+// the parts of instrs the pattern reads, in order, if instrs start with it
+fn matches<'a>(pattern: &[Pat], instrs: &'a [HackInstr]) -> Option<Vec<&'a HackInstr>> {
+    if instrs.len() < pattern.len() {
+        return None;
+    }
+
+    let mut parts = Vec::new();
+    for (pat, instr) in pattern.iter().zip(instrs) {
+        match (pat, instr) {
+            (Pat::Is(expected), instr) if expected == instr => {}
+            (Pat::Value, HackInstr::AInstr(Address::Value(_)))
+            | (Pat::Symbol, HackInstr::AInstr(Address::Symbol(_)))
+            | (Pat::Label, HackInstr::Label(_)) => parts.push(instr),
+            _ => return None,
+        }
+    }
+
+    Some(parts)
+}
+
+fn code(pats: impl IntoIterator<Item = Pat>) -> Vec<Pat> {
+    pats.into_iter().collect()
+}
+
+fn exactly(asm: impl IntoIterator<Item = HackInstr>) -> Vec<Pat> {
+    asm.into_iter().map(Pat::Is).collect()
+}
+
+// the code followed by pushing D
+fn with_push(pats: impl IntoIterator<Item = Pat>) -> Vec<Pat> {
+    let mut pats = code(pats);
+    pats.extend(exactly(push_d()));
+    pats
+}
+
+// local, argument, this and that are reached through the pointer named by instr
+fn pointed_segment(instr: &HackInstr) -> Option<MemorySegment> {
+    match text(instr) {
+        "LCL" => Some(MemorySegment::Local),
+        "ARG" => Some(MemorySegment::Argument),
+        "THIS" => Some(MemorySegment::This),
+        "THAT" => Some(MemorySegment::That),
+        _ => None,
+    }
+}
+
+// temp, pointer and static are at the address instr loads
+fn fixed_segment(instr: &HackInstr) -> Option<(MemorySegment, u16)> {
+    let temps = memory::TEMP_BASE..memory::TEMP_BASE + memory::TEMP_SIZE;
+    let statics = memory::STATIC_BASE..memory::STATIC_BASE + memory::STATIC_SIZE;
+    match instr {
+        HackInstr::AInstr(Address::Value(address)) if temps.contains(address) => {
+            Some((MemorySegment::Temp, address - memory::TEMP_BASE))
+        }
+        HackInstr::AInstr(Address::Value(address)) if statics.contains(address) => {
+            Some((MemorySegment::Static, address - memory::STATIC_BASE))
+        }
+        HackInstr::AInstr(Address::Value(_)) => None,
+        _ => match text(instr) {
+            "THIS" => Some((MemorySegment::Pointer, 0)),
+            "THAT" => Some((MemorySegment::Pointer, 1)),
+            // <file>.<index>, as the assembler allocates them
+            symbol => {
+                let (_, index) = symbol.rsplit_once('.')?;
+                Some((MemorySegment::Static, index.parse().ok()?))
+            }
+        },
+    }
+}
+
+fn value(instr: &HackInstr) -> u16 {
+    match instr {
+        HackInstr::AInstr(Address::Value(value)) => *value,
+        _ => 0,
+    }
+}
+
+// the symbol of an A-instruction or the name of a label
+fn text(instr: &HackInstr) -> &str {
+    match instr {
+        HackInstr::AInstr(Address::Symbol(symbol)) => symbol,
+        HackInstr::Label(name) => name,
+        _ => "",
+    }
+}
+
+// labels in a function are emitted as <function>$<label>
+fn unscoped(label: String, function: &Option<String>) -> String {
+    match function {
+        Some(function) => match label.strip_prefix(&format!("{}$", function)) {
+            Some(label) => label.to_owned(),
+            None => label,
+        },
+        None => label,
+    }
+}
+
+fn count_rom(instrs: &[HackInstr]) -> usize {
+    instrs.iter().filter(|instr| instr.is_instruction()).count()
+}
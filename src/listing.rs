@@ -0,0 +1,100 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{assembler::PREDEFINED_SYMBOLS, memory::STATIC_BASE};
+
+/*
+ * Lays the assembly out the way the assembler will, every instruction next to
+ * its ROM address and every symbol next to what it resolves to:
+ *
+ *   ROM  instruction
+ *        // Main.vm:3: goto LOOP
+ *    42  @Main.main$LOOP          // ROM 17
+ *    43  0;JMP
+ *
+ * Comments and blank lines are kept so annotations stay next to their code
+ */
+pub fn listing(asm: &str) -> String {
+    let labels = resolve_labels(asm);
+    let mut variables: HashMap<&str, u16> = HashMap::new();
+    let mut next_variable = STATIC_BASE;
+
+    let mut out = String::from("  ROM  instruction\n");
+    let mut pc = 0;
+    for line in asm.lines() {
+        let instr = strip_comment(line);
+
+        if line.trim().is_empty() {
+            out.push('\n');
+        } else if instr.is_empty() {
+            let _ = writeln!(out, "       {}", line.trim());
+        } else if let Some(label) = label_of(instr) {
+            let _ = writeln!(out, "       {:<24} // ROM {}", instr, labels[label]);
+        } else {
+            let resolved = match instr.strip_prefix('@') {
+                Some(symbol) if symbol.starts_with(|c: char| c.is_ascii_digit()) => None,
+                Some(symbol) => Some(match labels.get(symbol) {
+                    Some(address) => format!("ROM {}", address),
+                    None => {
+                        let address = PREDEFINED_SYMBOLS
+                            .iter()
+                            .find(|(name, _)| *name == symbol)
+                            .map(|(_, address)| *address)
+                            .unwrap_or_else(|| {
+                                *variables.entry(symbol).or_insert_with(|| {
+                                    next_variable += 1;
+                                    next_variable - 1
+                                })
+                            });
+                        format!("RAM {}", address)
+                    }
+                }),
+                None => None,
+            };
+
+            match resolved {
+                Some(resolved) => {
+                    let _ = writeln!(out, "{:>5}  {:<24} // {}", pc, instr, resolved);
+                }
+                None => {
+                    let _ = writeln!(out, "{:>5}  {}", pc, instr);
+                }
+            }
+            pc += 1;
+        }
+    }
+
+    out
+}
+
+/* ROM address of every (label), the assembler's first pass */
+pub fn resolve_labels(asm: &str) -> HashMap<&str, usize> {
+    let mut labels = HashMap::new();
+    let mut pc = 0;
+
+    for line in asm.lines() {
+        let instr = strip_comment(line);
+        if instr.is_empty() {
+            continue;
+        }
+        match label_of(instr) {
+            Some(label) => {
+                labels.insert(label, pc);
+            }
+            None => pc += 1,
+        }
+    }
+
+    labels
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find("//") {
+        Some(idx) => &line[..idx],
+        None => line,
+    }
+    .trim()
+}
+
+fn label_of(instr: &str) -> Option<&str> {
+    instr.strip_prefix('(')?.strip_suffix(')')
+}
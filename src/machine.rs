@@ -0,0 +1,142 @@
+use crate::{
+    emulator::Emulator,
+    interpreter::{Interpreter, State},
+    keyboard::Key,
+    memory, VmError,
+};
+
+/*
+ * The interpreter or the CPU emulator, driven by the host. A cycle is an
+ * instruction on the CPU emulator and a command on the interpreter
+ */
+pub trait Machine {
+    fn step(&mut self) -> Result<State, VmError>;
+    fn state(&self) -> State;
+    /* cycles run since the start */
+    fn steps(&self) -> u64;
+    fn pc(&self) -> usize;
+    fn ram(&self, address: u16) -> u16;
+    fn set_ram(&mut self, address: u16, value: u16);
+    /* the screen memory map, 32 words a row */
+    fn screen(&self) -> &[u16];
+    /* words written to the screen memory map since the start */
+    fn screen_writes(&self) -> u64;
+
+    /* holds key down until release */
+    fn press(&mut self, key: Key) {
+        self.set_ram(memory::KBD, key.code());
+    }
+
+    fn release(&mut self) {
+        self.set_ram(memory::KBD, 0);
+    }
+
+    /*
+     * Runs cycles cycles, fewer only when the program halts or fails first.
+     * Nothing runs between calls, there are no threads and nothing reads the
+     * time, so the same ticks with the same keys pressed in between always
+     * end in the same state. That is what property tests and lockstep runs of
+     * two machines need:
+     *
+     *   let mut cpu = Emulator::from_asm(&asm)?;
+     *   cpu.press(Key::Char('q'));
+     *   let tick = cpu.tick(10_000)?;
+     *   if tick.screen_changed {
+     *       redraw(cpu.screen());
+     *   }
+     */
+    fn tick(&mut self, cycles: u64) -> Result<Tick, VmError> {
+        let (steps, screen_writes) = (self.steps(), self.screen_writes());
+        let mut state = self.state();
+        for _ in 0..cycles {
+            state = self.step()?;
+            if state == State::Halted {
+                break;
+            }
+        }
+
+        Ok(Tick {
+            cycles: self.steps() - steps,
+            state,
+            screen_changed: self.screen_writes() > screen_writes,
+        })
+    }
+}
+
+/* What a tick did */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tick {
+    /* cycles that ran */
+    pub cycles: u64,
+    pub state: State,
+    /* the program wrote to the screen, even if the pixels ended up the same */
+    pub screen_changed: bool,
+}
+
+impl Machine for Emulator {
+    fn step(&mut self) -> Result<State, VmError> {
+        Emulator::step(self)
+    }
+
+    fn state(&self) -> State {
+        Emulator::state(self)
+    }
+
+    fn steps(&self) -> u64 {
+        Emulator::steps(self)
+    }
+
+    fn pc(&self) -> usize {
+        Emulator::pc(self)
+    }
+
+    fn ram(&self, address: u16) -> u16 {
+        Emulator::ram(self, address)
+    }
+
+    fn set_ram(&mut self, address: u16, value: u16) {
+        Emulator::set_ram(self, address, value)
+    }
+
+    fn screen(&self) -> &[u16] {
+        Emulator::screen(self)
+    }
+
+    fn screen_writes(&self) -> u64 {
+        Emulator::screen_writes(self)
+    }
+}
+
+impl Machine for Interpreter {
+    fn step(&mut self) -> Result<State, VmError> {
+        Interpreter::step(self)
+    }
+
+    fn state(&self) -> State {
+        Interpreter::state(self)
+    }
+
+    fn steps(&self) -> u64 {
+        Interpreter::steps(self)
+    }
+
+    fn pc(&self) -> usize {
+        Interpreter::pc(self)
+    }
+
+    fn ram(&self, address: u16) -> u16 {
+        Interpreter::ram(self, address)
+    }
+
+    fn set_ram(&mut self, address: u16, value: u16) {
+        Interpreter::set_ram(self, address, value)
+    }
+
+    fn screen(&self) -> &[u16] {
+        Interpreter::screen(self)
+    }
+
+    fn screen_writes(&self) -> u64 {
+        Interpreter::screen_writes(self)
+    }
+}
@@ -1,83 +1,2352 @@
 use std::{
-    env, fs,
-    io::{self, Write},
-    path::Path,
+    collections::BTreeMap,
+    fmt, fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+    thread,
+    time::{Duration, Instant, SystemTime},
 };
 
-use hackvm::{parse, VMTranslator};
+use clap::{Args, Parser, Subcommand};
+use hackvm::{
+    analysis::{self, Finding, Unit},
+    asm_path, assembler,
+    cache::{Cache, CACHE_DIR},
+    callgraph::CallGraph,
+    config::{Config, LintLevel, Os, CONFIG_FILE},
+    coverage::Coverage,
+    debugger::{DebugCommand, Debugger},
+    diagnostics::{Diagnostic, DiagnosticFormat, Severity},
+    disasm,
+    emulator::{Emulator, Snapshot},
+    hack,
+    interpreter::{Interpreter, State},
+    labels::LabelScope,
+    lift, listing,
+    machine::Machine,
+    memory::{self, StaticAllocator},
+    optimize, os, parse_lines,
+    parts::TranslatedFile,
+    profile::Profile,
+    provenance::Provenance,
+    scaffold::{self, Template},
+    sibling_asm_path,
+    sourcemap::{self, SourceMap},
+    testgen::Expectation,
+    testscript::{Program, Script, ScriptFiles},
+    trace, verify,
+    vfs::{self, FileProvider, MemoryFs, Overlay, RealFs},
+    Bootstrap, Command, OptLevel, Span, TranslationOptions, VMTranslator, VmError,
+};
+#[cfg(feature = "gui")]
+use hackvm::{
+    gui,
+    keyboard::{Key, KeyMap},
+};
+use rayon::prelude::*;
 
-fn main() -> io::Result<()> {
-    let args: Vec<_> = env::args().collect();
-    assert!(args.len() >= 2, "Usage: hackvm <filename>.vm");
-    let inpath = Path::new(&args[1]);
+#[derive(Debug, Parser)]
+#[command(
+    name = "hackvm",
+    version,
+    about = "Translate nand2tetris VM code into Hack assembly",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    arg_required_else_help = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+    /* `hackvm <path>` is short for `hackvm translate <path>` */
+    #[command(flatten)]
+    translate: TranslateArgs,
+}
 
-    if inpath.is_file() {
-        assert!(
-            inpath.extension().and_then(|ext| ext.to_str()) == Some("vm"),
-            "Expected .vm file"
-        );
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// Translate .vm files or directories into a single .asm file
+    Translate(TranslateArgs),
+    /// Parse and analyze the program without writing any output
+    Check(CheckArgs),
+    /// Retranslate whenever an input .vm file changes
+    Watch(WatchArgs),
+    /// Create a new project from a template
+    New(NewArgs),
+    /// Translate the project described by hackvm.toml
+    Build(BuildArgs),
+    /// Turn a .hack file back into assembly, with labels for the jump targets
+    Disasm(DisasmArgs),
+    /// Recover the VM commands of assembly this translator wrote at -O0
+    Lift(LiftArgs),
+    /// Run a program on the VM interpreter, or translated on the Hack CPU emulator
+    Run(RunArgs),
+    /// Step through a program on the VM interpreter, with breakpoints
+    Debug(DebugArgs),
+    /// Run nand2tetris .tst scripts, checking their output against the .cmp files
+    Test(TestArgs),
+    /// Write a .tst script and .cmp file for a program from what it should leave in memory
+    GenTest(GenTestArgs),
+    /// Print which functions call which, as Graphviz DOT or JSON
+    Graph(GraphArgs),
+}
 
-        let content = fs::read_to_string(inpath)?;
-        let mut translator = VMTranslator::new(inpath)?;
-        translator.write_prelude()?;
-
-        write_file_asm(&mut translator, content)?;
-    } else if inpath.is_dir() {
-        let infiles: Vec<_> = fs::read_dir(inpath)?
-            .filter_map(|entry| {
-                let path = entry.ok()?.path();
-                if path.extension().and_then(|ext| ext.to_str()) == Some("vm") {
-                    Some(path)
-                } else {
-                    None
+/* what to read and how to read it, shared by every subcommand working on sources */
+#[derive(Debug, Args)]
+struct SourceArgs {
+    /// .vm files, directories or glob patterns, `-` reads from stdin
+    #[arg(value_name = "PATH")]
+    inputs: Vec<String>,
+    /// Read VM source from stdin
+    #[arg(long)]
+    stdin: bool,
+    /// File stem of the source read from stdin, used to name its statics
+    #[arg(long, value_name = "STEM", default_value = "Stdin")]
+    name: String,
+    /// Treat trailing tokens after a command as errors
+    #[arg(long)]
+    strict: bool,
+    /// Skip the SP setup and the call to Sys.init
+    #[arg(long, overrides_with = "bootstrap")]
+    no_bootstrap: bool,
+    /// Emit the SP setup and the call to Sys.init even if hackvm.toml turns it off
+    #[arg(long, overrides_with = "no_bootstrap")]
+    bootstrap: bool,
+    /// How diagnostics are printed: human or json
+    #[arg(long, value_name = "FORMAT", default_value = "human")]
+    diagnostics: DiagnosticFormat,
+    /// Only print errors
+    #[arg(short, long)]
+    quiet: bool,
+    /* settings from hackvm.toml, the flags above take precedence */
+    #[arg(skip)]
+    project: Config,
+}
+
+#[derive(Debug, Args)]
+struct TranslateArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+    /// Where to write the output, `-` for stdout. With several --emit kinds the
+    /// directory to write them to
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+    /// What to write, comma separated: asm for Hack assembly, hack for the
+    /// machine code the built in assembler makes of it
+    #[arg(
+        long,
+        value_name = "KIND",
+        value_delimiter = ',',
+        default_value = "asm"
+    )]
+    emit: Vec<Emit>,
+    /// Overwrite an existing output file
+    #[arg(short, long)]
+    force: bool,
+    /// Write Dir.asm next to a directory instead of Dir/Dir.asm
+    #[arg(long)]
+    sibling_output: bool,
+    /// Check the generated assembly for malformed instructions and undefined symbols
+    #[arg(long)]
+    verify: bool,
+    /// Record the inputs and options in a header of the output
+    #[arg(long)]
+    provenance: bool,
+    /// Print the address given to every static variable
+    #[arg(long)]
+    static_map: bool,
+    /// Print per file command and instruction counts and timings
+    #[arg(short, long)]
+    verbose: bool,
+    /// Precede the code of every command with the file, line and command it came from
+    #[arg(long)]
+    annotate: bool,
+    /// Write a JSON map from the assembly back to the VM commands to <OUTPUT>.map
+    #[arg(long)]
+    source_map: bool,
+    /// Write every instruction with its ROM address and resolved symbols to a .lst file
+    #[arg(long)]
+    emit_listing: bool,
+    /// Print instruction counts per command, function and file once done
+    #[arg(long)]
+    stats: bool,
+    /// Keep the code of every file in .hackvm-cache next to the output and only
+    /// retranslate the files that changed. Not used with --stats or --source-map
+    #[arg(long)]
+    cache: bool,
+    /// Shrink the generated code: 0 not at all, 1 with peepholes over neighbouring
+    /// commands, 2 also with whole program passes like inlining. -O alone means -O2
+    #[arg(
+        short = 'O',
+        long,
+        value_name = "LEVEL",
+        num_args = 0..=1,
+        default_missing_value = "2"
+    )]
+    opt_level: Option<OptLevel>,
+    /// Emit eq, lt and gt once in the prelude and jump there for every comparison
+    #[arg(long)]
+    shared_comparisons: bool,
+    /// Emit the call and return sequences once in the prelude and jump there instead
+    #[arg(long)]
+    compact_calls: bool,
+    /// Leave the locals of a function with whatever the stack held instead of zeroing
+    /// them, for code that always assigns its locals before reading them
+    #[arg(long)]
+    no_zero_locals: bool,
+    /// Put PREFIX in front of the labels generated for comparisons
+    #[arg(long, value_name = "PREFIX", default_value = "")]
+    label_prefix: String,
+    /// Number generated labels across the program, or per file or function so
+    /// that an edit doesn't rename every label after it
+    #[arg(long, value_name = "SCOPE", default_value = "program")]
+    label_scope: LabelScope,
+}
+
+#[derive(Debug, Args)]
+struct WatchArgs {
+    #[command(flatten)]
+    translate: TranslateArgs,
+    /// How often to look for changes, in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    interval: u64,
+}
+
+#[derive(Debug, Args)]
+struct CheckArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Debug, Args)]
+struct BuildArgs {
+    /// Project configuration to build
+    #[arg(long, value_name = "PATH", default_value = CONFIG_FILE)]
+    config: PathBuf,
+    #[command(flatten)]
+    translate: TranslateArgs,
+}
+
+#[derive(Debug, Args)]
+struct DisasmArgs {
+    /// .hack file to disassemble
+    input: PathBuf,
+    /// Where to write the assembly, stdout if not given
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct LiftArgs {
+    /// .asm file to lift
+    input: PathBuf,
+    /// Where to write the VM commands, stdout if not given
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct RunArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+    /// What runs the program: vm interprets the commands, asm translates them
+    /// and runs the machine code on the CPU emulator
+    #[arg(long, value_name = "BACKEND", default_value = "vm")]
+    backend: Backend,
+    /// Optimization level of the translation run with --backend=asm, see translate
+    #[arg(
+        short = 'O',
+        long,
+        value_name = "LEVEL",
+        num_args = 0..=1,
+        default_missing_value = "2"
+    )]
+    opt_level: Option<OptLevel>,
+    /// Give up on a program that hasn't halted after this many commands, or
+    /// instructions with --backend=asm
+    #[arg(long, value_name = "N")]
+    max_steps: Option<u64>,
+    /// Set RAM[ADDRESS] before the program starts. The vm backend runs its
+    /// bootstrap first, the asm one has it in the code run after
+    #[arg(long, value_name = "ADDRESS=VALUE", value_parser = parse_assignment)]
+    set: Vec<(u16, i16)>,
+    /// Print RAM[ADDRESS] or RAM[START..END] once the program halts
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_range)]
+    print_ram: Vec<(u16, u16)>,
+    /// Print how many commands, or instructions with --backend=asm, each
+    /// function ran and how often it was called once the program stops
+    #[arg(long)]
+    profile: bool,
+    /// Follow the calls to the OS's Memory.alloc and Memory.deAlloc and report
+    /// the blocks never freed and any double frees once the program stops.
+    /// Only with --backend=vm
+    #[arg(long)]
+    heap: bool,
+    /// Write every command run, or instruction with --backend=asm, to FILE as
+    /// a line of JSON with its PC, the SP it left and the addresses it wrote
+    #[arg(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+    /// Only trace every Nth step
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "trace")]
+    trace_every: u64,
+    /// Write which VM lines ran to FILE once the program stops, and print how
+    /// many of each file's did. Only with --backend=vm
+    #[arg(long, value_name = "FILE")]
+    coverage: Option<PathBuf>,
+    /// How --coverage is written: lcov or annotate, the sources with how often
+    /// each line ran
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        default_value = "lcov",
+        requires = "coverage"
+    )]
+    coverage_format: CoverageFormat,
+    /// Save the CPU emulator's state to FILE once the program stops, halted or
+    /// not, for --resume to pick up from. Only with --backend=asm
+    #[arg(long, value_name = "FILE")]
+    snapshot: Option<PathBuf>,
+    /// Start from a state --snapshot saved instead of from the beginning. The
+    /// inputs have to translate to the program it was saved from
+    #[arg(long, value_name = "FILE")]
+    resume: Option<PathBuf>,
+    /// Show the screen in a window while the program runs. The window stays open
+    /// once it halts, closing it stops the program
+    #[cfg(feature = "gui")]
+    #[arg(long)]
+    screen: bool,
+    /// Report a host key as another Hack key in the window, like w=up. Keys are
+    /// printable characters or names: space, newline, backspace, left, f1 and so on
+    #[cfg(feature = "gui")]
+    #[arg(long, value_name = "HOST=KEY", value_parser = parse_key_mapping)]
+    key_map: Vec<(Key, Key)>,
+}
+
+#[derive(Debug, Args)]
+struct DebugArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+    /// How many commands continue runs before stopping on its own
+    #[arg(long, value_name = "N", default_value_t = 10_000_000)]
+    max_steps: u64,
+    /// How many of the last commands run step-back can undo, 0 turns it off
+    #[arg(long, value_name = "N", default_value_t = 100_000)]
+    journal: usize,
+    /// Set RAM[ADDRESS] before the first command, after the bootstrap's call
+    #[arg(long, value_name = "ADDRESS=VALUE", value_parser = parse_assignment)]
+    set: Vec<(u16, i16)>,
+}
+
+#[derive(Debug, Args)]
+struct TestArgs {
+    /// .tst scripts to run. An .asm or .hack file they load is translated afresh
+    /// from the .vm files next to the script when there are any
+    #[arg(value_name = "SCRIPT", required = true)]
+    scripts: Vec<PathBuf>,
+    /// Optimization level of the programs translated for the CPU emulator, see translate
+    #[arg(
+        short = 'O',
+        long,
+        value_name = "LEVEL",
+        num_args = 0..=1,
+        default_missing_value = "2"
+    )]
+    opt_level: Option<OptLevel>,
+    /// Treat trailing tokens after a command as errors
+    #[arg(long)]
+    strict: bool,
+    /// How diagnostics are printed: human or json
+    #[arg(long, value_name = "FORMAT", default_value = "human")]
+    diagnostics: DiagnosticFormat,
+    /// Only print failures
+    #[arg(short, long)]
+    quiet: bool,
+    /// Write which lines of the VM programs the scripts ran to FILE, added up
+    /// over every script, and print how many of each file's did
+    #[arg(long, value_name = "FILE")]
+    coverage: Option<PathBuf>,
+    /// How --coverage is written: lcov or annotate, the sources with how often
+    /// each line ran
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        default_value = "lcov",
+        requires = "coverage"
+    )]
+    coverage_format: CoverageFormat,
+}
+
+#[derive(Debug, Args)]
+struct GenTestArgs {
+    /// TOML file naming the program, the values to set and the values it should
+    /// leave. The script and compare file are written next to it
+    #[arg(value_name = "EXPECTATION")]
+    expectation: PathBuf,
+    /// Steps to run the program for finding how many it takes, when the
+    /// expectation doesn't say
+    #[arg(long, value_name = "N", default_value_t = 10_000_000)]
+    max_steps: u64,
+    /// Optimization level of the program translated for the CPU emulator, see translate
+    #[arg(
+        short = 'O',
+        long,
+        value_name = "LEVEL",
+        num_args = 0..=1,
+        default_missing_value = "2"
+    )]
+    opt_level: Option<OptLevel>,
+    /// Treat trailing tokens after a command as errors
+    #[arg(long)]
+    strict: bool,
+    /// How diagnostics are printed: human or json
+    #[arg(long, value_name = "FORMAT", default_value = "human")]
+    diagnostics: DiagnosticFormat,
+    /// Overwrite an existing script or compare file
+    #[arg(short, long)]
+    force: bool,
+    /// Don't print what was written
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+#[derive(Debug, Args)]
+struct GraphArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+    /// What to print: dot or json
+    #[arg(long, value_name = "FORMAT", default_value = "dot")]
+    format: GraphFormat,
+    /// Where to write the graph, stdout if not given
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct NewArgs {
+    /// Directory to create the project in
+    name: PathBuf,
+    /// Project layout
+    #[arg(short, long, default_value = "bare", value_parser = parse_template)]
+    template: Template,
+}
+
+/* What translate writes */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Emit {
+    Asm,
+    Hack,
+}
+
+/* What run runs the program on */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    Vm,
+    Asm,
+}
+
+/* How --coverage is written */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CoverageFormat {
+    Lcov,
+    Annotate,
+}
+
+/* What graph prints */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GraphFormat {
+    Dot,
+    Json,
+}
+
+/*
+ * What went wrong, mapped to stable exit codes scripts can branch on:
+ * 0 success, 1 usage, 2 parse, 3 semantic, 4 io
+ */
+#[derive(Debug)]
+enum CliError {
+    Usage(String),
+    /* the sources don't parse */
+    Parse(String),
+    /* the sources parse but don't make a valid program */
+    Semantic(String),
+    Io(io::Error),
+}
+
+type CliResult<T = ()> = Result<T, CliError>;
+
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Usage(_) => 1,
+            CliError::Parse(_) => 2,
+            CliError::Semantic(_) => 3,
+            CliError::Io(_) => 4,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(msg) | CliError::Parse(msg) | CliError::Semantic(msg) => {
+                write!(f, "{}", msg)
+            }
+            CliError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+// bad patterns and paths surface as InvalidInput
+impl From<io::Error> for CliError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::InvalidInput => CliError::Usage(err.to_string()),
+            _ => CliError::Io(err),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(err) => {
+            let _ = err.print();
+            // --help and --version come through here too
+            return match err.exit_code() {
+                0 => ExitCode::SUCCESS,
+                _ => ExitCode::from(1),
+            };
+        }
+    };
+
+    let result = match cli.command {
+        Some(Commands::Translate(args)) => run_translate(&args),
+        Some(Commands::Check(args)) => run_check(&args),
+        Some(Commands::Watch(args)) => watch(args),
+        Some(Commands::New(args)) => new_project(&args).map_err(CliError::from),
+        Some(Commands::Build(args)) => run_build(args),
+        Some(Commands::Disasm(args)) => run_disasm(&args),
+        Some(Commands::Lift(args)) => run_lift(&args),
+        Some(Commands::Run(args)) => run_program(&args),
+        Some(Commands::Debug(args)) => debug(&args),
+        Some(Commands::Test(args)) => run_tests(&args),
+        Some(Commands::GenTest(args)) => gen_test(&args),
+        Some(Commands::Graph(args)) => run_graph(&args),
+        None => run_translate(&cli.translate),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+fn parse_template(s: &str) -> Result<Template, String> {
+    s.parse()
+}
+
+// 0=256, values as signed as the program sees them
+fn parse_assignment(s: &str) -> Result<(u16, i16), String> {
+    let invalid = || format!("Expected ADDRESS=VALUE, found {}", s);
+    let (address, value) = s.split_once('=').ok_or_else(invalid)?;
+    let address = parse_address(address)?;
+    let value = value.trim().parse().map_err(|_| invalid())?;
+
+    Ok((address, value))
+}
+
+// 256 or 256..260, the end left out
+fn parse_range(s: &str) -> Result<(u16, u16), String> {
+    match s.split_once("..") {
+        Some((start, end)) => {
+            let (start, end) = (parse_address(start)?, parse_address(end)?);
+            if start >= end {
+                return Err(format!("Empty range {}", s));
+            }
+            Ok((start, end))
+        }
+        None => {
+            let address = parse_address(s)?;
+            Ok((address, address + 1))
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+fn parse_key_mapping(s: &str) -> Result<(Key, Key), String> {
+    let (host, key) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Expected HOST=KEY, found {}", s))?;
+
+    Ok((host.parse()?, key.parse()?))
+}
+
+fn parse_address(s: &str) -> Result<u16, String> {
+    match s.trim().parse::<u16>() {
+        Ok(address) if usize::from(address) < memory::RAM_SIZE => Ok(address),
+        _ => Err(format!(
+            "Invalid RAM address {} (expected 0 to {})",
+            s,
+            memory::RAM_SIZE - 1
+        )),
+    }
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asm" => Ok(Emit::Asm),
+            "hack" => Ok(Emit::Hack),
+            _ => Err(format!("Unknown output kind {} (expected asm or hack)", s)),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vm" => Ok(Backend::Vm),
+            "asm" => Ok(Backend::Asm),
+            _ => Err(format!("Unknown backend {} (expected vm or asm)", s)),
+        }
+    }
+}
+
+impl FromStr for CoverageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lcov" => Ok(CoverageFormat::Lcov),
+            "annotate" => Ok(CoverageFormat::Annotate),
+            _ => Err(format!(
+                "Unknown coverage format {} (expected lcov or annotate)",
+                s
+            )),
+        }
+    }
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "json" => Ok(GraphFormat::Json),
+            _ => Err(format!("Unknown graph format {} (expected dot or json)", s)),
+        }
+    }
+}
+
+fn run_translate(args: &TranslateArgs) -> CliResult {
+    let (provider, inpaths) = open_inputs(&args.source)?;
+
+    // several inputs are written next to the first one unless -o says otherwise,
+    // source from stdin goes back out to stdout
+    // several kinds of output are all written, each under the name of the input
+    let several = args.emits(Emit::Asm) && args.emits(Emit::Hack);
+    let outpath = match &args.output {
+        Some(dir) if several && !is_stdout(dir) => {
+            dir.join(asm_path(&inpaths[0]).file_name().unwrap_or_default())
+        }
+        Some(output) => output.clone(),
+        None if args.source.reads_stdin() => PathBuf::from("-"),
+        None if args.sibling_output => sibling_asm_path(&inpaths[0]),
+        None => asm_path(&inpaths[0]),
+    };
+    if several && is_stdout(&outpath) {
+        return Err(CliError::Usage(
+            "--emit with several kinds writes files, pass -o with a directory".to_owned(),
+        ));
+    }
+    // where only machine code is emitted it takes the place of the assembly
+    let outpath = match args.emits(Emit::Asm) {
+        false if args.output.is_none() && !is_stdout(&outpath) => outpath.with_extension("hack"),
+        _ => outpath,
+    };
+    let hackpath = several.then(|| outpath.with_extension("hack"));
+    let asm_file = !is_stdout(&outpath) && args.emits(Emit::Asm);
+    // an explicit -o means the caller knows where the output goes
+    for path in [Some(&outpath), hackpath.as_ref()].into_iter().flatten() {
+        if args.output.is_none() && !args.force && !is_stdout(path) && path.exists() {
+            return Err(CliError::Usage(format!(
+                "{} already exists, pass --force to overwrite it or -o to write elsewhere",
+                path.display()
+            )));
+        }
+    }
+    if args.verify && !asm_file {
+        return Err(CliError::Usage(
+            "--verify needs the assembly written to a file".to_owned(),
+        ));
+    }
+    if args.source_map && !asm_file {
+        return Err(CliError::Usage(
+            "--source-map needs the assembly written to a file".to_owned(),
+        ));
+    }
+    if args.emit_listing && !asm_file {
+        return Err(CliError::Usage(
+            "--emit-listing needs the assembly written to a file".to_owned(),
+        ));
+    }
+
+    translate(provider.as_ref(), &inpaths, &outpath, args)?;
+    // assembled from the file just written rather than translating again
+    if let Some(hackpath) = &hackpath {
+        write_hack(&fs::read_to_string(&outpath)?, hackpath)?;
+    }
+
+    if args.verify {
+        verify_output(&outpath, &args.source)?;
+    }
+    if args.emit_listing {
+        let asm = fs::read_to_string(&outpath)?;
+        fs::write(outpath.with_extension("lst"), listing::listing(&asm))?;
+    }
+
+    Ok(())
+}
+
+/*
+ * Translates what hackvm.toml lists, written to [build] output or <name>.asm
+ * next to the file. Inputs and flags given on the command line win
+ */
+fn run_build(mut args: BuildArgs) -> CliResult {
+    let config = Config::load(&args.config)?;
+    let root = args.config.parent().unwrap_or(Path::new(""));
+    let translate = &mut args.translate;
+
+    if translate.source.inputs.is_empty() && !translate.source.stdin {
+        translate.source.inputs = config.inputs.clone();
+    }
+    if translate.output.is_none() {
+        translate.output = match (&config.output, &config.name) {
+            (Some(output), _) => Some(PathBuf::from(output)),
+            (None, Some(name)) => Some(root.join(format!("{}.asm", name))),
+            (None, None) => None,
+        };
+    }
+    // building again replaces the previous build
+    translate.force = true;
+    translate.source.project = config;
+
+    run_translate(translate)
+}
+
+/*
+ * Unlike translate, check doesn't stop at the first broken line: every parse
+ * error, codegen error (offsets, duplicate labels) and analysis finding is
+ * reported before failing. Nothing is written
+ */
+fn run_check(args: &CheckArgs) -> CliResult {
+    let (provider, inpaths) = open_inputs(&args.source)?;
+    let provider = provider.as_ref();
+    let format = args.source.diagnostics;
+    let options = args.source.options();
+    let mut parse_errors = 0;
+    let mut errors = 0;
+
+    let mut units = Vec::new();
+    for infile in vm_files(provider, &inpaths)? {
+        let mut parsed = parse_lines(provider.open(&infile)?).strict(args.source.strict());
+        let mut commands = Vec::new();
+        for result in parsed.by_ref() {
+            match result {
+                Ok(command) => commands.push(command),
+                Err(VmError::Io(err)) => return Err(err.into()),
+                Err(err) => {
+                    parse_errors += 1;
+                    report_at(provider, &infile, &Diagnostic::from_error(&err), format)?;
                 }
-            })
-            .collect();
+            }
+        }
+        for warning in parsed.take_warnings() {
+            if warn_at(provider, &infile, &warning, &args.source)? {
+                parse_errors += 1;
+            }
+        }
+        units.push(Unit::new(infile, commands));
+    }
+
+    // codegen only checks offsets and labels here, the assembly itself is thrown away
+    let mut translator = VMTranslator::with_writer(io::sink(), "", options.clone());
+    translator.write_prelude()?;
+    for unit in &units {
+        translator.update_filestem(&unit.path);
+        for command in &unit.commands {
+            if let Err(err) = translator.write_command(command.clone()) {
+                errors += 1;
+                let diagnostic = Diagnostic::from_error(&err);
+                report_at(provider, &unit.path, &diagnostic, format)?;
+            }
+        }
+    }
+    if let Err(err) = translator.check_rom() {
+        errors += 1;
+        report_at(
+            provider,
+            &units[0].path,
+            &Diagnostic::from_error(&err),
+            format,
+        )?;
+    }
+
+    let whole_program = inpaths.len() > 1 || provider.is_dir(&inpaths[0]);
+    let findings = analysis::analyze(&units, whole_program, &options);
+    errors += report_findings(provider, findings, &args.source)?;
+
+    let message = format!("check found {} error(s)", parse_errors + errors);
+    if parse_errors > 0 {
+        return Err(CliError::Parse(message));
+    }
+    if errors > 0 {
+        return Err(CliError::Semantic(message));
+    }
 
-        assert!(
-            !infiles.is_empty(),
-            "No .vm files found in the specified directory"
+    if !args.source.quiet {
+        let commands: usize = units.iter().map(|unit| unit.commands.len()).sum();
+        eprintln!(
+            "Checked {} file(s), {} command(s): no errors",
+            units.len(),
+            commands
         );
+        if let (true, Some(entry)) = (whole_program, options.bootstrap.entry()) {
+            let depth = analysis::stack_depth(&units, entry);
+            let bound = match (&depth.growing, &depth.recursive) {
+                (Some(function), _) => format!("unbounded, {} pushes in a loop", function),
+                (None, Some(function)) => {
+                    format!("at least {} words, {} is recursive", depth.words, function)
+                }
+                (None, None) => format!("at most {} words", depth.words),
+            };
+            eprintln!("Stack: {} ({})", bound, depth.calls.join(" -> "));
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * Polls the modification times of the inputs and rebuilds on any change,
+ * including files being added to or removed from a watched directory.
+ * Failed builds only print their diagnostics, the next change retries
+ */
+fn watch(mut args: WatchArgs) -> CliResult {
+    if args.translate.source.reads_stdin() {
+        return Err(CliError::Usage(
+            "watch needs files or directories to watch, not stdin".to_owned(),
+        ));
+    }
+    let quiet = args.translate.source.quiet;
+    // every rebuild replaces the output of the previous one
+    args.translate.force = true;
+
+    let interval = Duration::from_millis(args.interval);
+    let mut last = None;
+    loop {
+        let snapshot = modified_times(&args.translate.source)?;
+        if last.as_ref() != Some(&snapshot) {
+            let started = Instant::now();
+            match run_translate(&args.translate) {
+                Ok(()) if quiet => (),
+                Ok(()) => eprintln!(
+                    "[watch] rebuilt {} file(s) in {:.1?}",
+                    snapshot.len(),
+                    started.elapsed()
+                ),
+                Err(err) => eprintln!("[watch] build failed: {}", err),
+            }
+            if !quiet {
+                eprintln!("[watch] waiting for changes...");
+            }
+            last = Some(snapshot);
+        }
+
+        thread::sleep(interval);
+    }
+}
 
-        let mut translator = VMTranslator::new(inpath)?;
-        translator.write_prelude()?;
+fn modified_times(source: &SourceArgs) -> io::Result<BTreeMap<PathBuf, Option<SystemTime>>> {
+    let (provider, inpaths) = open_inputs(source)?;
 
+    let mut times = BTreeMap::new();
+    for inpath in inpaths {
+        // a missing input is a change too, the rebuild reports it
+        let Ok(infiles) = vfs::vm_inputs(provider.as_ref(), &inpath) else {
+            times.insert(inpath, None);
+            continue;
+        };
         for infile in infiles {
-            let content = fs::read_to_string(&infile)?;
-            translator.update_filestem(&infile);
+            let modified = fs::metadata(&infile).and_then(|meta| meta.modified()).ok();
+            times.insert(infile, modified);
+        }
+    }
+
+    Ok(times)
+}
+
+impl TranslateArgs {
+    fn emits(&self, kind: Emit) -> bool {
+        self.emit.contains(&kind)
+    }
+}
 
-            write_file_asm(&mut translator, content)?;
+impl SourceArgs {
+    fn reads_stdin(&self) -> bool {
+        self.stdin || self.inputs.iter().any(|input| input == "-")
+    }
+
+    fn options(&self) -> TranslationOptions {
+        let mut options = TranslationOptions {
+            strict: self.strict(),
+            ..TranslationOptions::default()
+        };
+        if self.no_bootstrap {
+            options.bootstrap = Bootstrap::None;
+        } else if let (false, Some(bootstrap)) = (self.bootstrap, self.project.bootstrap()) {
+            options.bootstrap = bootstrap;
         }
-    } else {
+
+        options
+    }
+
+    fn strict(&self) -> bool {
+        self.strict || self.project.strict == Some(true)
+    }
+}
+
+/*
+ * Source read from stdin is handled as if it were <name>.vm held in memory,
+ * the name prefixes its statics and labels diagnostics
+ */
+fn open_inputs(source: &SourceArgs) -> io::Result<(Box<dyn FileProvider>, Vec<PathBuf>)> {
+    if source.inputs.is_empty() && !source.reads_stdin() {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
-            format!("Expected a file or directory"),
+            "No inputs given, pass .vm files, directories or --stdin",
+        ));
+    }
+    if source.reads_stdin() {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+
+        let inpath = PathBuf::from(format!("{}.vm", source.name));
+        let mut provider = MemoryFs::new();
+        provider.insert(&inpath, text);
+
+        return Ok((Box::new(provider), vec![inpath]));
+    }
+
+    let mut inpaths = Vec::new();
+    for input in &source.inputs {
+        inpaths.extend(expand_glob(input)?);
+    }
+    let provider = with_os(source.project.os.as_ref(), &mut inpaths);
+
+    Ok((provider, inpaths))
+}
+
+/* the OS from hackvm.toml goes after the inputs, read from where it is */
+fn with_os(os: Option<&Os>, inpaths: &mut Vec<PathBuf>) -> Box<dyn FileProvider> {
+    match os {
+        Some(Os::Bundled) => {
+            inpaths.push(PathBuf::from(os::DIR));
+            Box::new(Overlay::new(os::files()))
+        }
+        Some(Os::Path(dir)) => {
+            inpaths.push(PathBuf::from(dir));
+            Box::new(RealFs)
+        }
+        None => Box::new(RealFs),
+    }
+}
+
+// Patterns like "project/**/*.vm" are expanded here since shells on Windows
+// leave them alone. Anything else, or a path that exists as is, is kept verbatim
+fn expand_glob(arg: &str) -> io::Result<Vec<PathBuf>> {
+    if !arg.contains(['*', '?', '[']) || Path::new(arg).exists() {
+        return Ok(vec![PathBuf::from(arg)]);
+    }
+
+    let invalid = |err: String| io::Error::new(io::ErrorKind::InvalidInput, err);
+    let mut paths = glob::glob(arg)
+        .map_err(|err| invalid(format!("Invalid pattern {}: {}", arg, err)))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| invalid(err.to_string()))?;
+    paths.sort();
+
+    if paths.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No files match {}", arg),
         ));
     }
 
+    Ok(paths)
+}
+
+/* parses and analyzes every input, reporting what's wrong along the way */
+fn load(
+    provider: &dyn FileProvider,
+    inpaths: &[PathBuf],
+    source: &SourceArgs,
+) -> CliResult<Vec<Unit>> {
+    let infiles = vm_files(provider, inpaths)?;
+    let units = parse_inputs(provider, &infiles, source)?;
+
+    let inpath = &inpaths[0];
+    let whole_program = inpaths.len() > 1 || provider.is_dir(inpath);
+    let findings = analysis::analyze(&units, whole_program, &source.options());
+    check_program(provider, inpath, findings, source)?;
+
+    Ok(units)
+}
+
+/* .vm files of every input, in order and without duplicates */
+fn vm_files(provider: &dyn FileProvider, inpaths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut infiles = Vec::new();
+    for inpath in inpaths {
+        for infile in vfs::vm_inputs(provider, inpath)? {
+            if !infiles.contains(&infile) {
+                infiles.push(infile);
+            }
+        }
+    }
+
+    Ok(infiles)
+}
+
+/* every input ends up in the one output, sharing the prelude and label counters */
+fn translate(
+    provider: &dyn FileProvider,
+    inpaths: &[PathBuf],
+    outpath: &Path,
+    args: &TranslateArgs,
+) -> CliResult {
+    let mut units = load(provider, inpaths, &args.source)?;
+
+    let inpath = &inpaths[0];
+    let options = TranslationOptions {
+        annotate: args.annotate,
+        source_map: args.source_map,
+        opt_level: args
+            .opt_level
+            .or(args.source.project.opt_level)
+            .unwrap_or_default(),
+        shared_comparisons: args.shared_comparisons,
+        compact_calls: args.compact_calls,
+        zero_locals: !args.no_zero_locals,
+        label_prefix: args.label_prefix.clone(),
+        label_scope: args.label_scope,
+        ..args.source.options()
+    };
+
+    // without an entry point any function could be the one that runs
+    let aggressive = options.opt_level >= OptLevel::Aggressive;
+    if let (true, Some(entry)) = (aggressive, options.bootstrap.entry()) {
+        let removed = optimize::remove_dead_functions(&mut units, entry);
+        if args.verbose && !removed.is_empty() {
+            eprintln!(
+                "removed {} function(s) unreachable from {}: {}",
+                removed.len(),
+                entry,
+                removed.join(", ")
+            );
+        }
+    }
+
+    // the code loads these addresses, so the map is the one the program runs with
+    let statics = analysis::allocate_statics(&units)
+        .map_err(|(path, err)| fail(provider, path, err, args.source.diagnostics))?;
+    if args.static_map {
+        print_static_map(&statics, is_stdout(outpath));
+    }
+    let filestem = inpath
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+
+    let builder = VMTranslator::builder()
+        .filestem(filestem)
+        .options(options)
+        .static_addresses(statics);
+
+    if is_stdout(outpath) && args.emits(Emit::Asm) {
+        let mut translator = builder.writer(io::stdout().lock()).build();
+        return write_translation(provider, units, &mut translator, None, args);
+    }
+
+    let dir = outpath
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    // the cache can't give back counts per command or the source map
+    let cache = if args.cache && !args.stats && !args.source_map && !is_stdout(outpath) {
+        fs::create_dir_all(dir)?;
+        Some(Cache::open(&dir.join(CACHE_DIR))?)
+    } else {
+        None
+    };
+    // the assembly is kept in memory and only its machine code written out
+    if !args.emits(Emit::Asm) {
+        let mut translator = builder.writer(Vec::new()).build();
+        write_translation(provider, units, &mut translator, cache, args)?;
+        let asm = String::from_utf8_lossy(&translator.into_inner()?).into_owned();
+        return write_hack(&asm, outpath);
+    }
+
+    fs::create_dir_all(dir)?;
+    let mut translator = builder.writer(fs::File::create(outpath)?).build();
+    let mut result = write_translation(provider, units, &mut translator, cache, args);
+    if result.is_ok() && args.source_map {
+        let asm_file = outpath.file_name().unwrap_or_default().to_string_lossy();
+        let map = translator.source_map().to_json(&asm_file);
+        result = fs::write(sourcemap::map_path(outpath), map).map_err(CliError::from);
+    }
+
+    // don't leave a half written .asm behind
+    if result.is_err() {
+        drop(translator);
+        let _ = fs::remove_file(outpath);
+    }
+
+    result
+}
+
+/* assembles what the translator wrote into a .hack file, or stdout for `-o -` */
+fn write_hack(asm: &str, outpath: &Path) -> CliResult {
+    // anything the translator writes assembles, failing here is a bug
+    let words = assembler::assemble(asm)
+        .map_err(|err| CliError::Semantic(format!("could not assemble the output: {}", err)))?;
+    let hack = assembler::to_hack(&words);
+
+    if is_stdout(outpath) {
+        io::stdout().lock().write_all(hack.as_bytes())?;
+        return Ok(());
+    }
+    if let Some(dir) = outpath.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    Ok(fs::write(outpath, hack)?)
+}
+
+// `-o -` streams the assembly to stdout
+fn is_stdout(outpath: &Path) -> bool {
+    outpath == Path::new("-")
+}
+
+fn parse_inputs(
+    provider: &dyn FileProvider,
+    infiles: &[PathBuf],
+    source: &SourceArgs,
+) -> CliResult<Vec<Unit>> {
+    let mut units = Vec::with_capacity(infiles.len());
+
+    for infile in infiles {
+        let mut commands = parse_lines(provider.open(infile)?).strict(source.strict());
+        let parsed: Result<Vec<_>, _> = commands.by_ref().collect();
+        let mut denied = false;
+        for warning in commands.take_warnings() {
+            denied |= warn_at(provider, infile, &warning, source)?;
+        }
+
+        match parsed {
+            Ok(_) if denied => {
+                return Err(CliError::Parse(format!(
+                    "could not translate {} due to previous error",
+                    infile.display()
+                )))
+            }
+            Ok(commands) => units.push(Unit::new(infile, commands)),
+            Err(err) => return Err(fail(provider, infile, err, source.diagnostics)),
+        }
+    }
+
+    Ok(units)
+}
+
+// reports the whole program checks, run before anything is written
+fn check_program(
+    provider: &dyn FileProvider,
+    inpath: &Path,
+    findings: Vec<Finding>,
+    source: &SourceArgs,
+) -> CliResult {
+    let errors = report_findings(provider, findings, source)?;
+    if errors > 0 {
+        return Err(CliError::Semantic(format!(
+            "could not translate {} due to {} previous error(s)",
+            inpath.display(),
+            errors
+        )));
+    }
+
     Ok(())
 }
 
-fn write_file_asm<W: Write>(translator: &mut VMTranslator<W>, content: String) -> io::Result<()> {
-    for (n, line) in content.lines().enumerate() {
-        let line = line.trim();
-        if line.is_empty() || line.starts_with("//") {
-            continue;
+/* reports every finding, returning how many of them are errors */
+fn report_findings(
+    provider: &dyn FileProvider,
+    findings: Vec<Finding>,
+    source: &SourceArgs,
+) -> io::Result<usize> {
+    let mut errors = 0;
+
+    for finding in findings {
+        match finding.severity {
+            Severity::Error => {
+                errors += 1;
+                let diagnostic = Diagnostic::from_error(&finding.error);
+                report_at(provider, &finding.path, &diagnostic, source.diagnostics)?;
+            }
+            Severity::Warning => {
+                if warn_at(provider, &finding.path, &finding.error, source)? {
+                    errors += 1;
+                }
+            }
         }
+    }
 
-        match parse(line) {
-            Ok(command) => {
-                translator.write_asm(command)?;
+    Ok(errors)
+}
+
+// goes to stderr when stdout carries the assembly
+fn print_static_map(statics: &StaticAllocator, to_stderr: bool) {
+    let mut map = format!(
+        "Static memory map: {} of {} slots used\n",
+        statics.len(),
+        memory::STATIC_SIZE
+    );
+    for (address, symbol) in statics.entries() {
+        map.push_str(&format!("    RAM[{}]\t{}\n", address, symbol));
+    }
+
+    if to_stderr {
+        eprint!("{}", map);
+    } else {
+        print!("{}", map);
+    }
+}
+
+fn write_translation<W: Write>(
+    provider: &dyn FileProvider,
+    units: Vec<Unit>,
+    translator: &mut VMTranslator<W>,
+    mut cache: Option<Cache>,
+    args: &TranslateArgs,
+) -> CliResult {
+    if args.provenance {
+        let mut provenance = Provenance::new();
+        provenance.add_option("bootstrap", !args.source.no_bootstrap);
+        provenance.add_option("verify", args.verify);
+        provenance.add_option("annotate", args.annotate);
+        provenance.add_option("opt-level", translator.options().opt_level);
+        provenance.add_option("shared-comparisons", args.shared_comparisons);
+        provenance.add_option("compact-calls", args.compact_calls);
+        provenance.add_option("zero-locals", !args.no_zero_locals);
+        for unit in &units {
+            provenance.add_input(&unit.path, &provider.read_to_string(&unit.path)?);
+        }
+        translator.write_header(&provenance.to_header())?;
+    }
+
+    let first = units[0].path.clone();
+    let inlines = if translator.options().opt_level >= OptLevel::Aggressive {
+        optimize::inlinable_functions(&units)
+    } else {
+        BTreeMap::new()
+    };
+    translator.inline_functions(inlines.clone());
+    translator.write_prelude()?;
+    if args.verbose {
+        eprintln!("bootstrap: {} instructions", translator.instructions());
+    }
+
+    // ids are handed out in file order, so the files can be translated in any
+    let options = translator.options().clone();
+    let statics = translator.static_addresses().clone();
+    let mut jobs = Vec::with_capacity(units.len());
+    for unit in units {
+        let ids = translator.ids_needed(&unit.commands);
+        let first = translator.reserve_ids(ids);
+        let key = Cache::key(&unit, &options, &inlines, &statics, first);
+        let cached = cache.as_mut().and_then(|cache| cache.get(&unit.path, key));
+        jobs.push((unit, first, key, cached));
+    }
+    let files: Vec<_> = jobs
+        .into_par_iter()
+        .map(|(unit, first, key, cached)| {
+            let started = Instant::now();
+            let commands = unit.commands.len();
+            let file = match cached {
+                Some(file) => Ok((file, true)),
+                None => {
+                    let filestem = unit.filestem().to_owned();
+                    let source = unit.path.display().to_string();
+                    TranslatedFile::new(
+                        unit.commands,
+                        &filestem,
+                        &source,
+                        &options,
+                        &inlines,
+                        &statics,
+                        first,
+                    )
+                    .map(|file| (file, false))
+                }
+            };
+            (unit.path, key, commands, file, started.elapsed())
+        })
+        .collect();
+
+    for (path, key, commands, file, elapsed) in files {
+        let (file, cached) =
+            file.map_err(|err| fail(provider, &path, err, args.source.diagnostics))?;
+        let instructions = file.instructions();
+        if let (Some(cache), false) = (cache.as_mut(), cached) {
+            cache.put(&path, key, &file)?;
+        }
+        translator
+            .append(file)
+            .map_err(|err| fail(provider, &path, err, args.source.diagnostics))?;
+
+        if args.verbose {
+            eprintln!(
+                "{}: {} commands -> {} instructions in {:.1?}{}",
+                path.display(),
+                commands,
+                instructions,
+                elapsed,
+                if cached { " (cached)" } else { "" }
+            );
+        }
+    }
+    if let Some(cache) = &cache {
+        cache.prune()?;
+    }
+
+    if args.verbose {
+        eprintln!("total: {} instructions", translator.instructions());
+    }
+    // reported against the first input, like the other whole program errors
+    if let Err(err) = translator.check_rom() {
+        return Err(fail(provider, &first, err, args.source.diagnostics));
+    }
+    if args.stats {
+        eprint!("{}", translator.stats());
+    }
+
+    Ok(translator.flush()?)
+}
+
+// reports the error against its file and turns it into the one the cli exits with
+fn fail(
+    provider: &dyn FileProvider,
+    infile: &Path,
+    err: VmError,
+    format: DiagnosticFormat,
+) -> CliError {
+    if let VmError::Io(err) = err {
+        return err.into();
+    }
+    if let Err(err) = report_at(provider, infile, &Diagnostic::from_error(&err), format) {
+        return err.into();
+    }
+
+    let message = format!(
+        "could not translate {} due to previous error",
+        infile.display()
+    );
+    if err.is_syntax_error() {
+        CliError::Parse(message)
+    } else {
+        CliError::Semantic(message)
+    }
+}
+
+// Reports the warning at the level hackvm.toml gives it, --quiet keeps the ones
+// left as warnings out. Returns whether it was denied and so is an error now
+fn warn_at(
+    provider: &dyn FileProvider,
+    infile: &Path,
+    warning: &VmError,
+    source: &SourceArgs,
+) -> io::Result<bool> {
+    let diagnostic = match source.project.lints.level(warning) {
+        LintLevel::Allow => return Ok(false),
+        LintLevel::Warn if source.quiet => return Ok(false),
+        LintLevel::Warn => Diagnostic::from_warning(warning),
+        LintLevel::Deny => Diagnostic::from_error(warning),
+    };
+    report_at(provider, infile, &diagnostic, source.diagnostics)?;
+
+    Ok(diagnostic.severity == Severity::Error)
+}
+
+fn report_at(
+    provider: &dyn FileProvider,
+    infile: &Path,
+    diagnostic: &Diagnostic,
+    format: DiagnosticFormat,
+) -> io::Result<()> {
+    let source = provider.read_to_string(infile)?;
+    report(diagnostic, format, &infile.display().to_string(), &source);
+
+    Ok(())
+}
+
+// human readable diagnostics go to stderr, JSON ones to stdout for tools to consume
+fn report(diagnostic: &Diagnostic, format: DiagnosticFormat, filename: &str, source: &str) {
+    match format {
+        DiagnosticFormat::Human => eprint!("{}", diagnostic.emit(format, filename, source)),
+        DiagnosticFormat::Json => print!("{}", diagnostic.emit(format, filename, source)),
+    }
+}
+
+fn verify_output(outpath: &Path, source: &SourceArgs) -> CliResult {
+    let asm = fs::read_to_string(outpath)?;
+    let format = source.diagnostics;
+
+    match verify::verify_asm(&asm) {
+        Ok(_) if source.quiet => Ok(()),
+        Ok(summary) => {
+            println!(
+                "Verified {}: {} instructions, {} labels, {} static variables",
+                outpath.display(),
+                summary.instructions,
+                summary.labels,
+                summary.variables
+            );
+            Ok(())
+        }
+        Err(errors) => {
+            for err in &errors {
+                match format {
+                    DiagnosticFormat::Human => eprintln!("{}: {}", outpath.display(), err),
+                    DiagnosticFormat::Json => {
+                        let mut diagnostic = Diagnostic::error("E0100", err.message.clone());
+                        if err.line != 0 {
+                            diagnostic = diagnostic.with_span(Span {
+                                line: err.line,
+                                column: 1,
+                                ..Span::default()
+                            });
+                        }
+                        report(&diagnostic, format, &outpath.display().to_string(), &asm);
+                    }
+                }
             }
+            Err(CliError::Semantic(format!(
+                "Verification of {} failed with {} error(s)",
+                outpath.display(),
+                errors.len()
+            )))
+        }
+    }
+}
+
+fn run_graph(args: &GraphArgs) -> CliResult {
+    let (provider, inpaths) = open_inputs(&args.source)?;
+    let units = load(provider.as_ref(), &inpaths, &args.source)?;
+    let graph = CallGraph::build(&units);
+    let text = match args.format {
+        GraphFormat::Dot => graph.to_dot(),
+        GraphFormat::Json => graph.to_json(),
+    };
+
+    match &args.output {
+        Some(output) => fs::write(output, text)?,
+        None => io::stdout().lock().write_all(text.as_bytes())?,
+    }
+
+    Ok(())
+}
+
+fn run_disasm(args: &DisasmArgs) -> CliResult {
+    let hack = fs::read_to_string(&args.input)?;
+    // lines are the .hack file's, not the assembly's
+    let fail = |err: assembler::AsmError| match err.line {
+        0 => CliError::Parse(format!("{}: {}", args.input.display(), err.message)),
+        line => CliError::Parse(format!(
+            "{}:{}: {}",
+            args.input.display(),
+            line,
+            err.message
+        )),
+    };
+    let words = assembler::from_hack(&hack).map_err(fail)?;
+    let asm = hack::to_asm(&disasm::disassemble(&words).map_err(fail)?);
+
+    match &args.output {
+        Some(output) => fs::write(output, asm)?,
+        None => io::stdout().lock().write_all(asm.as_bytes())?,
+    }
+
+    Ok(())
+}
+
+/*
+ * Loads every input like translate would and runs it on the chosen backend
+ * until it halts, then prints the RAM asked for
+ */
+fn run_program(args: &RunArgs) -> CliResult {
+    let (provider, inpaths) = open_inputs(&args.source)?;
+    let provider = provider.as_ref();
+    let units = load(provider, &inpaths, &args.source)?;
+
+    if args.backend == Backend::Vm && (args.snapshot.is_some() || args.resume.is_some()) {
+        return Err(CliError::Usage(
+            "--snapshot and --resume need --backend=asm".to_owned(),
+        ));
+    }
+    if args.backend == Backend::Asm && (args.heap || args.coverage.is_some()) {
+        return Err(CliError::Usage(
+            "--heap and --coverage need --backend=vm".to_owned(),
+        ));
+    }
+    if args.trace_every == 0 {
+        return Err(CliError::Usage(
+            "--trace-every should be above 0".to_owned(),
+        ));
+    }
+    #[cfg(feature = "gui")]
+    if args.screen && args.trace.is_some() {
+        return Err(CliError::Usage(
+            "--trace can't be used with --screen".to_owned(),
+        ));
+    }
+
+    let started = Instant::now();
+    let max_steps = args.max_steps.unwrap_or(u64::MAX);
+    let (ram, steps, sp) = match args.backend {
+        Backend::Vm => run_interpreter(provider, &inpaths, units, args, max_steps)?,
+        Backend::Asm => run_emulator(provider, &inpaths, units, args, max_steps)?,
+    };
+
+    if !args.source.quiet {
+        let unit = match args.backend {
+            Backend::Vm => "commands",
+            Backend::Asm => "instructions",
+        };
+        eprintln!(
+            "Halted after {} {} in {:.1?}, SP = {}",
+            steps,
+            unit,
+            started.elapsed(),
+            sp
+        );
+    }
+    for (start, end) in &args.print_ram {
+        for address in *start..*end {
+            println!("RAM[{}] = {}", address, ram[usize::from(address)] as i16);
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * Reads debugger commands from stdin until quit or the end of input. An empty
+ * line runs the last command again, a runtime error is reported at its
+ * command which stays the one run next
+ */
+fn debug(args: &DebugArgs) -> CliResult {
+    let (provider, inpaths) = open_inputs(&args.source)?;
+    let provider = provider.as_ref();
+    let units = load(provider, &inpaths, &args.source)?;
+    let format = args.source.diagnostics;
+    let (mut vm, paths) = interpret(provider, units, &args.source.options().bootstrap, format)?;
+    for (address, value) in &args.set {
+        vm.set_ram(*address, *value as u16);
+    }
+    vm.record(args.journal);
+
+    let mut debugger = Debugger::new(vm, args.max_steps);
+    // only stepping can fail
+    print!(
+        "{}",
+        debugger.execute(&DebugCommand::Where).unwrap_or_default()
+    );
+    let stdin = io::stdin();
+    let mut last = None;
+    loop {
+        eprint!("(hackvm) ");
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 {
+            break;
+        }
+
+        let command = match line.trim() {
+            "" => match &last {
+                Some(command) => command,
+                None => continue,
+            },
+            line => match line.parse::<DebugCommand>() {
+                Ok(command) => last.insert(command),
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    continue;
+                }
+            },
+        };
+        if *command == DebugCommand::Quit {
+            break;
+        }
 
+        match debugger.execute(command) {
+            Ok(out) => print!("{}", out),
             Err(err) => {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("Error at line {}: {}", n + 1, err),
-                ))
+                let vm = debugger.vm();
+                let path = vm
+                    .current()
+                    .and_then(|(filestem, _)| paths.iter().find(|(stem, _)| stem == filestem))
+                    .map_or(&paths[0].1, |(_, path)| path);
+                report_at(provider, path, &Diagnostic::from_error(&err), format)?;
             }
         }
     }
 
     Ok(())
 }
+
+// the RAM the program halted with, how many commands it took and the final SP
+type Halted = (Vec<u16>, u64, u16);
+
+fn run_interpreter(
+    provider: &dyn FileProvider,
+    inpaths: &[PathBuf],
+    units: Vec<Unit>,
+    args: &RunArgs,
+    max_steps: u64,
+) -> CliResult<Halted> {
+    let format = args.source.diagnostics;
+    let bootstrap = args.source.options().bootstrap;
+    let (mut vm, paths) = interpret(provider, units, &bootstrap, format)?;
+    let first = paths[0].1.clone();
+    for (address, value) in &args.set {
+        vm.set_ram(*address, *value as u16);
+    }
+    if args.profile || args.coverage.is_some() {
+        vm.count_hits();
+    }
+    if args.heap {
+        vm.track_heap();
+    }
+    if args.trace.is_some() {
+        vm.track_writes();
+    }
+
+    let driven = drive(&mut vm, args, max_steps)?;
+    // where the time went is as interesting for a program that didn't halt
+    if args.profile {
+        print!("{}", Profile::from_interpreter(&vm));
+    }
+    if let Some(heap) = vm.heap() {
+        print!("{}", heap.report(&vm));
+    }
+    if let Some(out) = &args.coverage {
+        let mut coverage = Coverage::from_interpreter(&vm);
+        for file in &mut coverage.files {
+            let stem = file.file.trim_end_matches(".vm");
+            if let Some((_, path)) = paths.iter().find(|(filestem, _)| filestem == stem) {
+                file.file = path.display().to_string();
+            }
+        }
+        write_coverage(provider, &coverage, out, args.coverage_format)?;
+    }
+    let halted = match driven {
+        Ok(halted) => halted,
+        Err(err) => {
+            let path = match vm.current() {
+                Some((filestem, _)) => paths
+                    .iter()
+                    .find(|(stem, _)| stem == filestem)
+                    .map_or(&first, |(_, path)| path),
+                None => &first,
+            };
+            report_at(provider, path, &Diagnostic::from_error(&err), format)?;
+            return Err(CliError::Semantic(format!(
+                "{} stopped after {} commands due to previous error",
+                inpaths[0].display(),
+                vm.steps()
+            )));
+        }
+    };
+    if !halted {
+        return Err(CliError::Semantic(format!(
+            "{} didn't halt within {} commands",
+            inpaths[0].display(),
+            max_steps
+        )));
+    }
+
+    let ram = (0..memory::RAM_SIZE as u16).map(|address| vm.ram(address));
+    Ok((ram.collect(), vm.steps(), vm.sp()))
+}
+
+/* translates the inputs in memory and runs what they assemble to on the CPU emulator */
+fn run_emulator(
+    provider: &dyn FileProvider,
+    inpaths: &[PathBuf],
+    units: Vec<Unit>,
+    args: &RunArgs,
+    max_steps: u64,
+) -> CliResult<Halted> {
+    let options = TranslationOptions {
+        opt_level: args
+            .opt_level
+            .or(args.source.project.opt_level)
+            .unwrap_or_default(),
+        source_map: args.profile,
+        ..args.source.options()
+    };
+    let (mut cpu, source_map) = emulate(
+        provider,
+        &inpaths[0],
+        units,
+        options,
+        args.source.diagnostics,
+    )?;
+    if let Some(path) = &args.resume {
+        let snapshot = Snapshot::decode(&fs::read_to_string(path)?).ok_or_else(|| {
+            CliError::Parse(format!("{} is not a hackvm snapshot", path.display()))
+        })?;
+        if snapshot.rom() != cpu.rom() {
+            return Err(CliError::Semantic(format!(
+                "{} was saved from another program than {}",
+                path.display(),
+                inpaths[0].display()
+            )));
+        }
+        cpu.restore(&snapshot);
+    }
+    for (address, value) in &args.set {
+        cpu.set_ram(*address, *value as u16);
+    }
+    if args.profile {
+        cpu.count_hits();
+    }
+
+    let driven = drive(&mut cpu, args, max_steps)?;
+    if args.profile {
+        print!("{}", Profile::from_source_map(cpu.hits(), &source_map));
+    }
+    if let Some(path) = &args.snapshot {
+        fs::write(path, cpu.snapshot().encode())?;
+    }
+    let halted = match driven {
+        Ok(halted) => halted,
+        Err(err) => {
+            return Err(CliError::Semantic(format!(
+                "{} stopped at ROM {} after {} instructions: {}",
+                inpaths[0].display(),
+                cpu.pc(),
+                cpu.steps(),
+                err
+            )))
+        }
+    };
+    if !halted {
+        return Err(CliError::Semantic(format!(
+            "{} didn't halt within {} instructions",
+            inpaths[0].display(),
+            max_steps
+        )));
+    }
+
+    let ram = (0..memory::RAM_SIZE as u16).map(|address| cpu.ram(address));
+    Ok((ram.collect(), cpu.steps(), cpu.sp()))
+}
+
+/*
+ * The interpreter with every input loaded and started, along with the path of
+ * every file stem to report errors at
+ */
+fn interpret(
+    provider: &dyn FileProvider,
+    units: Vec<Unit>,
+    bootstrap: &Bootstrap,
+    format: DiagnosticFormat,
+) -> CliResult<(Interpreter, Vec<(String, PathBuf)>)> {
+    let mut vm = Interpreter::new();
+    let mut paths = Vec::with_capacity(units.len());
+    for unit in units {
+        let filestem = unit.filestem().to_owned();
+        vm.add_file(&filestem, unit.commands)
+            .map_err(|err| fail(provider, &unit.path, err, format))?;
+        paths.push((filestem, unit.path));
+    }
+    // the first input stands in for errors that aren't in any command
+    vm.start(bootstrap)
+        .map_err(|err| fail(provider, &paths[0].1, err, format))?;
+
+    Ok((vm, paths))
+}
+
+/* translates the inputs in memory, without any of the output translate can add */
+fn translate_units(
+    provider: &dyn FileProvider,
+    inpath: &Path,
+    mut units: Vec<Unit>,
+    options: TranslationOptions,
+    format: DiagnosticFormat,
+) -> CliResult<(String, SourceMap)> {
+    let filestem = inpath
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let first = units[0].path.clone();
+
+    let aggressive = options.opt_level >= OptLevel::Aggressive;
+    if let (true, Some(entry)) = (aggressive, options.bootstrap.entry()) {
+        optimize::remove_dead_functions(&mut units, entry);
+    }
+    let statics = analysis::allocate_statics(&units)
+        .map_err(|(path, err)| fail(provider, path, err, format))?;
+    let mut translator = VMTranslator::builder()
+        .filestem(filestem)
+        .options(options)
+        .static_addresses(statics)
+        .writer(Vec::new())
+        .build();
+    if aggressive {
+        translator.inline_functions(optimize::inlinable_functions(&units));
+    }
+    translator.write_prelude()?;
+    for unit in units {
+        translator.start_file(unit.filestem(), &unit.path.display().to_string());
+        translator
+            .write_commands(unit.commands)
+            .map_err(|err| fail(provider, &unit.path, err, format))?;
+    }
+    translator
+        .check_rom()
+        .map_err(|err| fail(provider, &first, err, format))?;
+
+    // empty unless options.source_map is set
+    let source_map = translator.source_map().clone();
+    let asm = String::from_utf8_lossy(&translator.into_inner()?).into_owned();
+
+    Ok((asm, source_map))
+}
+
+/* the CPU emulator with the translated inputs in ROM, and the source map if options asked for one */
+fn emulate(
+    provider: &dyn FileProvider,
+    inpath: &Path,
+    units: Vec<Unit>,
+    options: TranslationOptions,
+    format: DiagnosticFormat,
+) -> CliResult<(Emulator, SourceMap)> {
+    let (asm, source_map) = translate_units(provider, inpath, units, options, format)?;
+
+    // anything the translator writes assembles, failing here is a bug
+    let cpu = Emulator::from_asm(&asm)
+        .map_err(|err| CliError::Semantic(format!("could not assemble the output: {}", err)))?;
+
+    Ok((cpu, source_map))
+}
+
+/* a machine run --trace can write the steps of */
+trait Traced: Machine {
+    /* the line --trace writes for the step that just ran at pc */
+    fn trace(&self, pc: usize) -> String;
+}
+
+impl Traced for Interpreter {
+    fn trace(&self, pc: usize) -> String {
+        trace::vm_step(self, pc)
+    }
+}
+
+impl Traced for Emulator {
+    fn trace(&self, pc: usize) -> String {
+        trace::cpu_step(self, pc)
+    }
+}
+
+/*
+ * Runs the program, in a window showing its screen if asked to. Ok(true) once
+ * it is done, by halting or by having its window closed
+ */
+#[cfg_attr(not(feature = "gui"), allow(unused_variables))]
+fn drive(
+    machine: &mut impl Traced,
+    args: &RunArgs,
+    max_steps: u64,
+) -> CliResult<Result<bool, VmError>> {
+    #[cfg(feature = "gui")]
+    if args.screen {
+        return drive_window(machine, args, max_steps);
+    }
+    if let Some(path) = &args.trace {
+        return drive_traced(machine, path, args.trace_every, max_steps);
+    }
+
+    Ok(machine
+        .tick(max_steps)
+        .map(|tick| tick.state == State::Halted))
+}
+
+/* steps one at a time, writing every nth step to the trace at path */
+fn drive_traced(
+    machine: &mut impl Traced,
+    path: &Path,
+    every: u64,
+    max_steps: u64,
+) -> CliResult<Result<bool, VmError>> {
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    let mut state = State::Running;
+    for _ in 0..max_steps {
+        let (pc, steps) = (machine.pc(), machine.steps());
+        state = match machine.step() {
+            Ok(state) => state,
+            Err(err) => {
+                out.flush()?;
+                return Ok(Err(err));
+            }
+        };
+        // stepping a halted program runs nothing
+        if machine.steps() > steps && machine.steps().is_multiple_of(every) {
+            writeln!(out, "{}", machine.trace(pc))?;
+        }
+        if state == State::Halted {
+            break;
+        }
+    }
+    out.flush()?;
+
+    Ok(Ok(state == State::Halted))
+}
+
+#[cfg(feature = "gui")]
+fn drive_window(
+    machine: &mut impl Machine,
+    args: &RunArgs,
+    max_steps: u64,
+) -> CliResult<Result<bool, VmError>> {
+    // enough to keep up with the screen at 60 frames a second
+    const STEPS_PER_FRAME: u64 = 100_000;
+
+    let title = format!("hackvm - {}", args.source.inputs.join(" "));
+    let window_error = |err: minifb::Error| io::Error::other(err.to_string());
+    let mut keys = KeyMap::new();
+    for (host, key) in &args.key_map {
+        keys.set(*host, *key);
+    }
+    let mut window = gui::Window::open(&title, keys)
+        .map_err(|err| io::Error::other(format!("could not open a window: {}", err)))?;
+    let mut left = max_steps;
+    while window.is_open() {
+        let key = window.key().map_or(0, Key::code);
+        machine.set_ram(memory::KBD, key);
+        if machine.state() != State::Halted && left > 0 {
+            let steps = left.min(STEPS_PER_FRAME);
+            left -= steps;
+            if let Err(err) = machine.tick(steps) {
+                return Ok(Err(err));
+            }
+        }
+        window.show(machine.screen()).map_err(window_error)?;
+    }
+
+    // closed before it ran out of steps, that's the user stopping it
+    Ok(Ok(machine.state() == State::Halted || left > 0))
+}
+
+/*
+ * Runs every script, writing its output file next to it like the official
+ * tools do, and fails if any of them didn't match its compare file
+ */
+fn run_tests(args: &TestArgs) -> CliResult {
+    let mut failed = 0;
+    let mut coverage = Coverage::default();
+    for path in &args.scripts {
+        if !run_test(path, args, &mut coverage)? {
+            failed += 1;
+        }
+    }
+    if let Some(out) = &args.coverage {
+        write_coverage(&RealFs, &coverage, out, args.coverage_format)?;
+    }
+
+    if failed > 0 {
+        return Err(CliError::Semantic(format!(
+            "{} of {} test scripts failed",
+            failed,
+            args.scripts.len()
+        )));
+    }
+    Ok(())
+}
+
+/*
+ * Writes the tracefile or annotated sources to out and prints the summary,
+ * the files are read back by the paths they're named by
+ */
+fn write_coverage(
+    provider: &dyn FileProvider,
+    coverage: &Coverage,
+    out: &Path,
+    format: CoverageFormat,
+) -> CliResult {
+    let text = match format {
+        CoverageFormat::Lcov => coverage.to_lcov(),
+        CoverageFormat::Annotate => {
+            let mut text = String::new();
+            for file in &coverage.files {
+                let source = provider.read_to_string(Path::new(&file.file))?;
+                text.push_str(&format!("==> {} <==\n", file.file));
+                text.push_str(&file.annotate(&source));
+            }
+            text
+        }
+    };
+    fs::write(out, text)?;
+    print!("{}", coverage);
+
+    Ok(())
+}
+
+// whether the script ran to the end with its output matching, adding the lines it ran to coverage
+fn run_test(path: &Path, args: &TestArgs, coverage: &mut Coverage) -> CliResult<bool> {
+    let text = fs::read_to_string(path)?;
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut files = TestFiles::new(
+        dir,
+        args.opt_level.unwrap_or_default(),
+        args.strict,
+        args.diagnostics,
+        args.quiet,
+    );
+    files.coverage = args.coverage.is_some();
+
+    let outcome = match Script::parse(&text).and_then(|script| script.run(&mut files)) {
+        Ok(outcome) => outcome,
+        Err(err) => {
+            eprintln!("error: {}:{}: {}", path.display(), err.line, err.message);
+            return Ok(false);
+        }
+    };
+    if let Some(output_file) = &outcome.output_file {
+        fs::write(dir.join(output_file), &outcome.output)?;
+    }
+    if let Some(ran) = &outcome.coverage {
+        let mut ran = ran.clone();
+        for file in &mut ran.files {
+            file.file = dir.join(&file.file).display().to_string();
+        }
+        coverage.merge(&ran);
+    }
+
+    let compare_file = outcome.compare_file.as_deref().unwrap_or_default();
+    if let Some(mismatch) = &outcome.mismatch {
+        eprintln!(
+            "{}: comparison failure at line {} of {}",
+            path.display(),
+            mismatch.line,
+            compare_file
+        );
+        eprintln!("  expected: {}", mismatch.expected);
+        eprintln!("  actual:   {}", mismatch.actual);
+        return Ok(false);
+    }
+    if !args.quiet {
+        match &outcome.compare_file {
+            Some(_) => eprintln!(
+                "{}: passed, {} lines match {}",
+                path.display(),
+                outcome.compared,
+                compare_file
+            ),
+            None => eprintln!("{}: ran to the end, nothing to compare to", path.display()),
+        }
+    }
+
+    Ok(true)
+}
+
+/* The programs and compare files a test script names, relative to it */
+struct TestFiles<'a> {
+    dir: &'a Path,
+    opt_level: OptLevel,
+    source: SourceArgs,
+    /* whether VM programs count their hits for --coverage */
+    coverage: bool,
+}
+
+impl ScriptFiles for TestFiles<'_> {
+    fn load(&mut self, name: Option<&str>) -> Result<Program, String> {
+        self.program(name).map_err(|err| err.to_string())
+    }
+
+    fn read(&mut self, name: &str) -> Result<String, String> {
+        fs::read_to_string(self.dir.join(name))
+            .map_err(|err| format!("Could not read {}: {}", name, err))
+    }
+}
+
+impl<'a> TestFiles<'a> {
+    fn new(
+        dir: &'a Path,
+        opt_level: OptLevel,
+        strict: bool,
+        diagnostics: DiagnosticFormat,
+        quiet: bool,
+    ) -> Self {
+        TestFiles {
+            dir,
+            opt_level,
+            // the bootstrap is only there with a Sys.init to call, see TestFiles::program
+            source: SourceArgs {
+                inputs: Vec::new(),
+                stdin: false,
+                name: String::new(),
+                strict,
+                no_bootstrap: true,
+                bootstrap: false,
+                diagnostics,
+                quiet,
+                project: Config::default(),
+            },
+            coverage: false,
+        }
+    }
+
+    /*
+     * An .asm file is what the translator makes of its sources: every .vm file
+     * next to the script for Dir.asm in Dir, like translating the directory
+     * would write, or else Foo.vm next to it. Only without any sources is it
+     * read as is, as is a .hack file. Anything else goes into the interpreter
+     */
+    fn program(&self, name: Option<&str>) -> CliResult<Program> {
+        let path = name.map_or(self.dir.to_path_buf(), |name| self.dir.join(name));
+        let format = self.source.diagnostics;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("hack") => {
+                let words = assembler::from_hack(&fs::read_to_string(&path)?)
+                    .map_err(|err| CliError::Parse(format!("{}: {}", path.display(), err)))?;
+                Ok(Program::Cpu(Emulator::new(words)))
+            }
+            Some("asm") => {
+                let file = path.with_extension("vm");
+                let dir = fs::canonicalize(self.dir)?;
+                let inpath = if dir.file_stem() == path.file_stem()
+                    && vfs::vm_inputs(&RealFs, self.dir).is_ok()
+                {
+                    self.dir.to_path_buf()
+                } else if file.is_file() {
+                    file
+                } else {
+                    let asm = fs::read_to_string(&path)?;
+                    let cpu = Emulator::from_asm(&asm)
+                        .map_err(|err| CliError::Parse(format!("{}: {}", path.display(), err)))?;
+                    return Ok(Program::Cpu(cpu));
+                };
+                // a project's OS, which hackvm build would have translated with it
+                let config = self.dir.join(CONFIG_FILE);
+                let os = match config.is_file() {
+                    true => Config::load(&config)?.os,
+                    false => None,
+                };
+                let mut inpaths = vec![inpath];
+                let provider = with_os(os.as_ref(), &mut inpaths);
+                let provider = provider.as_ref();
+
+                let units = load(provider, &inpaths, &self.source)?;
+                let options = TranslationOptions {
+                    opt_level: self.opt_level,
+                    bootstrap: test_bootstrap(&units),
+                    ..self.source.options()
+                };
+                let (cpu, _) = emulate(provider, &inpaths[0], units, options, format)?;
+                Ok(Program::Cpu(cpu))
+            }
+            _ => {
+                let units = load(&RealFs, &[path], &self.source)?;
+                let bootstrap = test_bootstrap(&units);
+                let (mut vm, _) = interpret(&RealFs, units, &bootstrap, format)?;
+                if self.coverage {
+                    vm.count_hits();
+                }
+                Ok(Program::Vm(Box::new(vm)))
+            }
+        }
+    }
+}
+
+// the official tests only expect the bootstrap with a Sys.init to call
+fn test_bootstrap(units: &[Unit]) -> Bootstrap {
+    let has_entry = units
+        .iter()
+        .flat_map(|unit| &unit.commands)
+        .any(|command| matches!(&command.node, Command::Function(name, _) if name == "Sys.init"));
+
+    if has_entry {
+        Bootstrap::Standard
+    } else {
+        Bootstrap::None
+    }
+}
+
+/*
+ * Writes Name.tst, or NameVME.tst for a VM program, and Name.cmp next to
+ * Name.toml. The script is run first the way hackvm test would, so what gets
+ * written is known to pass, and without steps in the expectation that run
+ * also finds how many the program takes, with some to spare
+ */
+fn gen_test(args: &GenTestArgs) -> CliResult {
+    let path = &args.expectation;
+    let expectation: Expectation = fs::read_to_string(path)?
+        .parse()
+        .map_err(|err| CliError::Parse(format!("{}: {}", path.display(), err)))?;
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| CliError::Usage(format!("{} has no file name", path.display())))?;
+
+    let suffix = if expectation.on_vm() { "VME" } else { "" };
+    let tst_path = dir.join(format!("{}{}.tst", stem, suffix));
+    let cmp_path = dir.join(format!("{}.cmp", stem));
+    for path in [&tst_path, &cmp_path] {
+        if !args.force && path.exists() {
+            return Err(CliError::Usage(format!(
+                "{} already exists, pass --force to overwrite it",
+                path.display()
+            )));
+        }
+    }
+
+    let compare = expectation.compare_file();
+    let mut files = ProbeFiles {
+        files: TestFiles::new(
+            dir,
+            args.opt_level.unwrap_or_default(),
+            args.strict,
+            args.diagnostics,
+            args.quiet,
+        ),
+        name: format!("{}.cmp", stem),
+        compare: compare.clone(),
+    };
+    let max_steps = expectation.steps.unwrap_or(args.max_steps);
+    let probe = expectation.script(stem, max_steps);
+    let outcome = Script::parse(&probe)
+        .and_then(|script| script.run(&mut files))
+        .map_err(|err| CliError::Semantic(format!("{}: {}", path.display(), err.message)))?;
+
+    if expectation.steps.is_none() && outcome.steps >= max_steps {
+        return Err(CliError::Semantic(format!(
+            "{}: {} didn't halt within {} steps, give steps in the expectation",
+            path.display(),
+            expectation.program,
+            max_steps
+        )));
+    }
+    if let Some(mismatch) = &outcome.mismatch {
+        return Err(CliError::Semantic(format!(
+            "{}: {} doesn't leave what's expected\n  expected: {}\n  actual:   {}",
+            path.display(),
+            expectation.program,
+            mismatch.expected,
+            mismatch.actual
+        )));
+    }
+
+    // another translator, or opt level, may take a few more steps than this one
+    let steps = expectation
+        .steps
+        .unwrap_or_else(|| (outcome.steps * 11 / 10).div_ceil(100).max(1) * 100);
+    fs::write(&tst_path, expectation.script(stem, steps))?;
+    fs::write(&cmp_path, compare)?;
+
+    if !args.quiet {
+        eprintln!(
+            "wrote {} and {}, checking {} values after {} steps",
+            tst_path.display(),
+            cmp_path.display(),
+            expectation.expect.len(),
+            steps
+        );
+    }
+    Ok(())
+}
+
+/* The test files with the compare file gen-test is yet to write */
+struct ProbeFiles<'a> {
+    files: TestFiles<'a>,
+    name: String,
+    compare: String,
+}
+
+impl ScriptFiles for ProbeFiles<'_> {
+    fn load(&mut self, name: Option<&str>) -> Result<Program, String> {
+        self.files.load(name)
+    }
+
+    fn read(&mut self, name: &str) -> Result<String, String> {
+        match name == self.name {
+            true => Ok(self.compare.clone()),
+            false => self.files.read(name),
+        }
+    }
+}
+
+fn run_lift(args: &LiftArgs) -> CliResult {
+    let asm = fs::read_to_string(&args.input)?;
+    let lifted = lift::lift(&asm).map_err(|(line, err)| {
+        CliError::Parse(format!("{}:{}: {}", args.input.display(), line, err))
+    })?;
+
+    let mut vm = String::new();
+    for command in &lifted.commands {
+        vm.push_str(&command.to_vm_string());
+        vm.push('\n');
+    }
+    match &args.output {
+        Some(output) => fs::write(output, vm)?,
+        None => io::stdout().lock().write_all(vm.as_bytes())?,
+    }
+
+    // the lift is best effort, what it couldn't place is only a warning
+    if let Some(first) = lifted.unrecognized.first() {
+        eprintln!(
+            "warning: {} instruction(s) matched no command, the first at ROM {}",
+            lifted.unrecognized.len(),
+            first
+        );
+    }
+
+    Ok(())
+}
+
+fn new_project(args: &NewArgs) -> io::Result<()> {
+    let template = args.template;
+    let root = args.name.as_path();
+    let created = scaffold::create_project(root, template)?;
+
+    println!("Created {} project {}", template.name(), root.display());
+    for path in created {
+        println!("    {}", path.display());
+    }
+
+    Ok(())
+}
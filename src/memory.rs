@@ -0,0 +1,182 @@
+use alloc::collections::BTreeMap;
+
+use crate::{prelude::*, MemorySegment, VmError};
+
+/*
+ * Standard Hack RAM layout
+ * RAM[0-4]         SP, LCL, ARG, THIS, THAT
+ * RAM[5-12]        temp segment
+ * RAM[13-15]       general purpose registers
+ * RAM[16-255]      static variables
+ * RAM[256-2047]    stack
+ * RAM[2048-16383]  heap
+ * RAM[16384-24575] screen memory map
+ * RAM[24576]       keyboard memory map
+ */
+pub const SP: u16 = 0;
+pub const LCL: u16 = 1;
+pub const ARG: u16 = 2;
+pub const THIS: u16 = 3;
+pub const THAT: u16 = 4;
+
+pub const TEMP_BASE: u16 = 5;
+pub const TEMP_SIZE: u16 = 8;
+
+pub const GENERAL_BASE: u16 = 13;
+pub const GENERAL_SIZE: u16 = 3;
+
+pub const STATIC_BASE: u16 = 16;
+pub const STATIC_SIZE: u16 = 240;
+
+pub const STACK_BASE: u16 = 256;
+pub const STACK_END: u16 = 2047;
+
+pub const HEAP_BASE: u16 = 2048;
+pub const HEAP_END: u16 = 16383;
+
+pub const SCREEN: u16 = 16384;
+pub const SCREEN_SIZE: u16 = 8192;
+pub const SCREEN_WIDTH: usize = 512;
+pub const SCREEN_HEIGHT: usize = 256;
+
+pub const KBD: u16 = 24576;
+
+pub const RAM_SIZE: usize = 32768;
+pub const ROM_SIZE: usize = 32768;
+
+/* A-instructions only hold 15 bits */
+pub const MAX_CONSTANT: u16 = 32767;
+
+/*
+ * Frame saved by `call` right below the callee's LCL:
+ * LCL - 5 -> return address
+ * LCL - 4 -> caller LCL
+ * LCL - 3 -> caller ARG
+ * LCL - 2 -> caller THIS
+ * LCL - 1 -> caller THAT
+ */
+pub const FRAME_SIZE: u16 = 5;
+pub const RETURN_ADDRESS_OFFSET: u16 = 5;
+/* restored by `return` in this order, paired with their distance below LCL */
+pub const SAVED_POINTERS: [(&str, u16); 4] = [("THAT", 1), ("THIS", 2), ("ARG", 3), ("LCL", 4)];
+
+pub fn is_screen(address: u16) -> bool {
+    (SCREEN..SCREEN + SCREEN_SIZE).contains(&address)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Frame {
+    pub lcl: u16,
+    pub arg: u16,
+    pub this: u16,
+    pub that: u16,
+    /* first RAM address given to the current file's static variables */
+    pub static_base: u16,
+}
+
+impl Frame {
+    /* ARG of a callee once `call` has pushed the saved frame on top of its n_args arguments */
+    pub fn callee_arg(sp: u16, n_args: u16) -> u16 {
+        sp.wrapping_sub(n_args).wrapping_sub(FRAME_SIZE)
+    }
+
+    /* address holding the return address of the function running with this frame */
+    pub fn return_address_slot(&self) -> u16 {
+        self.lcl.wrapping_sub(RETURN_ADDRESS_OFFSET)
+    }
+}
+
+impl MemorySegment {
+    /* number of addressable slots for segments with a fixed size */
+    pub fn capacity(&self) -> Option<u16> {
+        match self {
+            MemorySegment::Static => Some(STATIC_SIZE),
+            MemorySegment::Temp => Some(TEMP_SIZE),
+            MemorySegment::Pointer => Some(2),
+            _ => None,
+        }
+    }
+
+    /* RAM address of segment[offset] under the given frame, None for constants or out of range offsets */
+    pub fn address_of(&self, offset: u16, frame: &Frame) -> Option<u16> {
+        if let Some(capacity) = self.capacity() {
+            if offset >= capacity {
+                return None;
+            }
+        }
+
+        let address = match self {
+            MemorySegment::Local => frame.lcl.checked_add(offset)?,
+            MemorySegment::Argument => frame.arg.checked_add(offset)?,
+            MemorySegment::This => frame.this.checked_add(offset)?,
+            MemorySegment::That => frame.that.checked_add(offset)?,
+            MemorySegment::Static => frame.static_base.checked_add(offset)?,
+            MemorySegment::Temp => TEMP_BASE + offset,
+            MemorySegment::Pointer => THIS + offset,
+            MemorySegment::Constant => return None,
+        };
+
+        if usize::from(address) < RAM_SIZE {
+            Some(address)
+        } else {
+            None
+        }
+    }
+}
+
+/*
+ * Hands out static addresses RAM[16] onwards, in order of first use of each
+ * File.index across the whole program. The translator emits these addresses
+ * and the interpreter keeps its statics at them, the assembler never sees the
+ * File.index symbols
+ */
+#[derive(Debug, Clone, Default)]
+pub struct StaticAllocator {
+    symbols: Vec<String>,
+    addresses: BTreeMap<String, u16>,
+}
+
+impl StaticAllocator {
+    pub fn new() -> Self {
+        StaticAllocator::default()
+    }
+
+    /* address of file.index, allocating it on first use */
+    pub fn allocate(&mut self, file: &str, index: u16) -> Result<u16, VmError> {
+        let symbol = format!("{}.{}", file, index);
+        if let Some(address) = self.addresses.get(&symbol) {
+            return Ok(*address);
+        }
+
+        let slot = self.symbols.len() as u16;
+        if slot >= STATIC_SIZE {
+            return Err(VmError::StaticOverflow(symbol));
+        }
+
+        let address = STATIC_BASE + slot;
+        self.addresses.insert(symbol.clone(), address);
+        self.symbols.push(symbol);
+
+        Ok(address)
+    }
+
+    pub fn address(&self, file: &str, index: u16) -> Option<u16> {
+        self.addresses.get(&format!("{}.{}", file, index)).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /* (address, symbol) pairs in address order */
+    pub fn entries(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.symbols
+            .iter()
+            .enumerate()
+            .map(|(slot, symbol)| (STATIC_BASE + slot as u16, symbol.as_str()))
+    }
+}
@@ -0,0 +1,807 @@
+#[cfg(feature = "std")]
+use alloc::collections::{BTreeMap, BTreeSet};
+
+#[cfg(feature = "std")]
+use crate::analysis::{self, Unit};
+use crate::{
+    hack::{at, jump, set, Address, Comp, Dest, HackInstr, Jump},
+    memory::{self, StaticAllocator},
+    pointer,
+    prelude::*,
+    push_d, return_asm, Command, MemorySegment, Span, Spanned, TranslationOptions, VmError,
+};
+
+/*
+ * `push a i` straight followed by `pop b j` copies a value from one segment to
+ * another. Instead of a round trip through the stack the value moves through D,
+ * which leaves SP alone and takes about half the instructions
+ */
+pub fn move_asm(
+    from: (MemorySegment, u16),
+    to: (MemorySegment, u16),
+    (statics, filestem): (&mut StaticAllocator, &str),
+) -> Result<Vec<HackInstr>, VmError> {
+    let mut asm = load_asm(from, statics, filestem)?;
+    asm.extend(store_asm(to, statics, filestem)?);
+
+    Ok(asm)
+}
+
+// D = segment[offset]
+fn load_asm(
+    (segment, offset): (MemorySegment, u16),
+    statics: &mut StaticAllocator,
+    filestem: &str,
+) -> Result<Vec<HackInstr>, VmError> {
+    let asm = match segment {
+        MemorySegment::Constant if offset == 0 => vec![set(Dest::D, Comp::Zero)],
+        MemorySegment::Constant if offset == 1 => vec![set(Dest::D, Comp::One)],
+        MemorySegment::Constant => vec![at(offset), set(Dest::D, Comp::A)],
+        MemorySegment::Static => vec![
+            at(statics.allocate(filestem, offset)?),
+            set(Dest::D, Comp::M),
+        ],
+        MemorySegment::Temp => vec![at(memory::TEMP_BASE + offset), set(Dest::D, Comp::M)],
+        MemorySegment::Pointer => vec![at(pointer(offset)), set(Dest::D, Comp::M)],
+        _ if offset == 0 => vec![
+            at(segment.to_label()?),
+            set(Dest::A, Comp::M),
+            set(Dest::D, Comp::M),
+        ],
+        _ => vec![
+            at(offset),
+            set(Dest::D, Comp::A),
+            at(segment.to_label()?),
+            set(Dest::A, Comp::DPlusM),
+            set(Dest::D, Comp::M),
+        ],
+    };
+
+    Ok(asm)
+}
+
+// segment[offset] = D
+fn store_asm(
+    (segment, offset): (MemorySegment, u16),
+    statics: &mut StaticAllocator,
+    filestem: &str,
+) -> Result<Vec<HackInstr>, VmError> {
+    let asm = match segment {
+        MemorySegment::Constant => {
+            return Err(VmError::BadSegment {
+                segment: "constant".to_owned(),
+                reason: "pop operation cannot be performed for a constant".to_owned(),
+            })
+        }
+        MemorySegment::Static => vec![
+            at(statics.allocate(filestem, offset)?),
+            set(Dest::M, Comp::D),
+        ],
+        MemorySegment::Temp => vec![at(memory::TEMP_BASE + offset), set(Dest::M, Comp::D)],
+        MemorySegment::Pointer => vec![at(pointer(offset)), set(Dest::M, Comp::D)],
+        // stepping A up beats computing the address for the first few slots
+        _ if offset <= 3 => {
+            let mut asm = vec![at(segment.to_label()?), set(Dest::A, Comp::M)];
+            asm.extend((0..offset).map(|_| set(Dest::A, Comp::APlusOne)));
+            asm.push(set(Dest::M, Comp::D));
+            asm
+        }
+        _ => vec![
+            at("R13"),
+            set(Dest::M, Comp::D),
+            at(offset),
+            set(Dest::D, Comp::A),
+            at(segment.to_label()?),
+            set(Dest::D, Comp::DPlusM),
+            at("R14"),
+            set(Dest::M, Comp::D),
+            at("R13"),
+            set(Dest::D, Comp::M),
+            at("R14"),
+            set(Dest::A, Comp::M),
+            set(Dest::M, Comp::D),
+        ],
+    };
+
+    Ok(asm)
+}
+
+/* `neg; neg` and `not; not` leave the value as it was, both commands go */
+pub fn cancel_unary_pairs(commands: Vec<Spanned<Command>>) -> Vec<Spanned<Command>> {
+    let mut kept: Vec<Spanned<Command>> = Vec::with_capacity(commands.len());
+
+    for command in commands {
+        match (kept.last().map(|last| &last.node), &command.node) {
+            (Some(Command::Neg), Command::Neg) | (Some(Command::Not), Command::Not) => {
+                kept.pop();
+            }
+            _ => kept.push(command),
+        }
+    }
+
+    kept
+}
+
+/*
+ * Evaluates arithmetic on constants ahead of time, `push constant 4`,
+ * `push constant 5`, `add` becomes `push constant 9`. Values wrap at 16 bits
+ * like on the Hack CPU, and comparisons subtract first just like the code
+ * emitted for them, so folding never changes what a program computes
+ */
+pub fn fold_constants(commands: Vec<Spanned<Command>>) -> Vec<Spanned<Command>> {
+    let mut folded: Vec<Spanned<Command>> = Vec::with_capacity(commands.len());
+    // values of the constants at the end of folded and how many commands make up each
+    let mut constants: Vec<(i16, usize)> = Vec::new();
+
+    for command in commands {
+        let operands = match &command.node {
+            Command::Push(MemorySegment::Constant, value) => {
+                constants.push((*value as i16, 1));
+                folded.push(command);
+                continue;
+            }
+            Command::Neg | Command::Not => 1,
+            Command::Add
+            | Command::Sub
+            | Command::And
+            | Command::Or
+            | Command::Eq
+            | Command::Lt
+            | Command::Gt => 2,
+            _ => 0,
+        };
+
+        if operands == 0 || constants.len() < operands {
+            constants.clear();
+            folded.push(command);
+            continue;
+        }
+
+        let args = constants.split_off(constants.len() - operands);
+        let value = match (&command.node, args.as_slice()) {
+            (Command::Neg, [(x, _)]) => x.wrapping_neg(),
+            (Command::Not, [(x, _)]) => !x,
+            (Command::Add, [(x, _), (y, _)]) => x.wrapping_add(*y),
+            (Command::Sub, [(x, _), (y, _)]) => x.wrapping_sub(*y),
+            (Command::And, [(x, _), (y, _)]) => x & y,
+            (Command::Or, [(x, _), (y, _)]) => x | y,
+            (Command::Eq, [(x, _), (y, _)]) => truth(x.wrapping_sub(*y) == 0),
+            (Command::Lt, [(x, _), (y, _)]) => truth(x.wrapping_sub(*y) < 0),
+            (Command::Gt, [(x, _), (y, _)]) => truth(x.wrapping_sub(*y) > 0),
+            _ => unreachable!("operand counts match the commands above"),
+        };
+
+        let consumed: usize = args.iter().map(|(_, len)| len).sum::<usize>() + 1;
+        let start = folded.len() + 1 - consumed;
+        let span = folded[start].span.to(command.span);
+        let replacement = push_value(value, span);
+
+        // negative results take two commands, only worth it if that's fewer than before
+        if replacement.len() < consumed {
+            folded.truncate(start);
+            constants.push((value, replacement.len()));
+            folded.extend(replacement);
+        } else {
+            constants.push((value, consumed));
+            folded.push(command);
+        }
+    }
+
+    folded
+}
+
+fn truth(condition: bool) -> i16 {
+    if condition {
+        -1
+    } else {
+        0
+    }
+}
+
+// push constant only takes 0..=32767, negative values are pushed as !(-value - 1)
+fn push_value(value: i16, span: Span) -> Vec<Spanned<Command>> {
+    if value >= 0 {
+        return vec![Spanned::new(
+            Command::Push(MemorySegment::Constant, value as u16),
+            span,
+        )];
+    }
+
+    vec![
+        Spanned::new(Command::Push(MemorySegment::Constant, !value as u16), span),
+        Spanned::new(Command::Not, span),
+    ]
+}
+
+/* Code a command can end with that the next one might undo, longest first */
+pub fn deferrable() -> [Vec<HackInstr>; 2] {
+    [push_d().to_vec(), increment_sp()]
+}
+
+fn increment_sp() -> Vec<HackInstr> {
+    vec![at("SP"), set(Dest::M, Comp::MPlusOne)]
+}
+
+fn decrement_sp() -> Vec<HackInstr> {
+    vec![at("SP"), set(Dest::M, Comp::MMinusOne)]
+}
+
+/*
+ * Neighbouring commands keep moving SP back and forth, a push ends with
+ * `@SP M=M+1` and the add after it starts with `@SP M=M-1`. Those pairs cancel,
+ * and a value pushed from D and popped straight back into D never needs to
+ * touch the stack at all. Neither pattern holds a label, so nothing can jump
+ * into the middle of one
+ */
+pub fn cancel_stack_adjustments(asm: Vec<HackInstr>) -> Vec<HackInstr> {
+    let mut round_trip = push_d().to_vec();
+    round_trip.extend(decrement_sp());
+    round_trip.extend([set(Dest::A, Comp::M), set(Dest::D, Comp::M)]);
+    let mut back_and_forth = increment_sp();
+    back_and_forth.extend(decrement_sp());
+
+    // the longer pattern goes first, it starts with the shorter one's increment
+    let asm = replace(asm, &round_trip, &[]);
+    replace(asm, &back_and_forth, &[at("SP")])
+}
+
+// every non-overlapping pattern from the start replaced with with
+fn replace(asm: Vec<HackInstr>, pattern: &[HackInstr], with: &[HackInstr]) -> Vec<HackInstr> {
+    let mut out = Vec::with_capacity(asm.len());
+    let mut rest = asm.as_slice();
+    while !rest.is_empty() {
+        if rest.starts_with(pattern) {
+            out.extend_from_slice(with);
+            rest = &rest[pattern.len()..];
+        } else {
+            out.push(rest[0].clone());
+            rest = &rest[1..];
+        }
+    }
+
+    out
+}
+
+/*
+ * Drops @ instructions loading what A already holds. a is what A held before
+ * the code and is left as what it holds after. Labels forget it since a jump
+ * could arrive there with anything in A
+ */
+pub fn skip_reloads(asm: Vec<HackInstr>, a: &mut Option<Address>) -> Vec<HackInstr> {
+    let mut out = Vec::with_capacity(asm.len());
+
+    for instr in asm {
+        match &instr {
+            HackInstr::AInstr(address) if a.as_ref() == Some(address) => continue,
+            HackInstr::AInstr(address) => *a = Some(address.clone()),
+            HackInstr::CInstr { dest, .. } if dest.a => *a = None,
+            HackInstr::Label(_) => *a = None,
+            HackInstr::CInstr { .. } => (),
+        }
+        out.push(instr);
+    }
+
+    out
+}
+
+/*
+ * eq / lt / gt straight followed by if-goto only decides a jump, the boolean
+ * it would push is tested and popped right away. Jumping on x - y directly
+ * takes half the instructions
+ */
+pub fn compare_and_jump(comparison: &Command, label: &str) -> Vec<HackInstr> {
+    let mut asm = decrement_sp();
+    asm.extend([set(Dest::A, Comp::M), set(Dest::D, Comp::M)]);
+    asm.extend(decrement_sp());
+    asm.extend([
+        set(Dest::A, Comp::M),
+        set(Dest::D, Comp::MMinusD),
+        at(label.to_owned()),
+        jump(Comp::D, crate::comparison_jump(comparison)),
+    ]);
+
+    asm
+}
+
+/* Shared eq / lt / gt routines and the jump instruction each one tests with */
+const COMPARISONS: [(&str, Jump); 3] = [
+    ("VM_EQ", Jump::Jeq),
+    ("VM_LT", Jump::Jlt),
+    ("VM_GT", Jump::Jgt),
+];
+
+/*
+ * Routines some options make the code jump to instead of writing the same
+ * instructions out at every use. They sit in the prelude and are jumped over,
+ * None when no option needs them
+ */
+pub fn runtime(options: &TranslationOptions) -> Option<Vec<HackInstr>> {
+    if !options.shared_comparisons && !options.compact_calls {
+        return None;
+    }
+
+    let mut asm = vec![at("VM_RUNTIME_END"), jump(Comp::Zero, Jump::Jmp)];
+    if options.shared_comparisons {
+        asm.extend(comparison_routines());
+    }
+    if options.compact_calls {
+        asm.extend(call_routines());
+    }
+    asm.push(HackInstr::Label("VM_RUNTIME_END".to_owned()));
+
+    Some(asm)
+}
+
+/*
+ * Every comparison written out in full takes about 20 instructions. With
+ * shared comparisons each eq / lt / gt jumps to one of these instead, leaving
+ * the return address in R15. They pop y, compare it against x in place and
+ * jump back
+ */
+fn comparison_routines() -> Vec<HackInstr> {
+    let mut asm = Vec::new();
+
+    for (routine, condition) in COMPARISONS {
+        let is_true = format!("{}_TRUE", routine);
+        asm.extend([
+            HackInstr::Label(routine.to_owned()),
+            at("SP"),
+            set(Dest::AM, Comp::MMinusOne),
+            set(Dest::D, Comp::M),
+            set(Dest::A, Comp::AMinusOne),
+            set(Dest::D, Comp::MMinusD),
+            set(Dest::M, Comp::MinusOne),
+            at(&is_true),
+            jump(Comp::D, condition),
+            at("SP"),
+            set(Dest::A, Comp::MMinusOne),
+            set(Dest::M, Comp::Zero),
+            HackInstr::Label(is_true),
+            at("R15"),
+            set(Dest::A, Comp::M),
+            jump(Comp::Zero, Jump::Jmp),
+        ]);
+    }
+
+    asm
+}
+
+/* jump to the shared routine for comparison, which comes back to return_label */
+pub fn comparison_call(comparison: &Command, return_label: &str) -> Vec<HackInstr> {
+    let routine = match comparison {
+        Command::Eq => "VM_EQ",
+        Command::Lt => "VM_LT",
+        _ => "VM_GT",
+    };
+
+    vec![
+        at(return_label.to_owned()),
+        set(Dest::D, Comp::A),
+        at("R15"),
+        set(Dest::M, Comp::D),
+        at(routine),
+        jump(Comp::Zero, Jump::Jmp),
+        HackInstr::Label(return_label.to_owned()),
+    ]
+}
+
+/*
+ * A call written out in full saves the frame at every call site, about 45
+ * instructions. With compact calls the site only passes what differs between
+ * calls to $CALL$: the return address in D, nArgs in R13 and the callee in R14
+ */
+pub fn compact_call(function: &str, n_args: u16, return_label: &str) -> Vec<HackInstr> {
+    vec![
+        at(n_args),
+        set(Dest::D, Comp::A),
+        at("R13"),
+        set(Dest::M, Comp::D),
+        at(function.to_owned()),
+        set(Dest::D, Comp::A),
+        at("R14"),
+        set(Dest::M, Comp::D),
+        at(return_label.to_owned()),
+        set(Dest::D, Comp::A),
+        at("$CALL$"),
+        jump(Comp::Zero, Jump::Jmp),
+        HackInstr::Label(return_label.to_owned()),
+    ]
+}
+
+/*
+ * `call f n` straight followed by `return` doesn't need a frame of its own, f
+ * can return to where the current function would have. The frame the current
+ * function was called with is copied above the arguments, the whole block is
+ * moved down to ARG and f is jumped to with the stack it would have had if our
+ * caller had called it, so tail recursion runs in constant stack space
+ */
+pub fn tail_call(function: &str, n_args: u16, label: &str) -> Vec<HackInstr> {
+    let block = n_args + memory::FRAME_SIZE;
+
+    let mut asm = vec![
+        at("LCL"),
+        set(Dest::D, Comp::M),
+        at(memory::FRAME_SIZE),
+        set(Dest::D, Comp::DMinusA),
+        at("R13"),
+        set(Dest::M, Comp::D),
+        at("SP"),
+        set(Dest::D, Comp::M),
+        at("R14"),
+        set(Dest::M, Comp::D),
+        at(memory::FRAME_SIZE),
+        set(Dest::D, Comp::A),
+        at("R15"),
+        set(Dest::M, Comp::D),
+    ];
+    asm.extend(copy_asm(&format!("{}.frame", label)));
+    asm.extend([
+        at(memory::FRAME_SIZE),
+        set(Dest::D, Comp::A),
+        at("SP"),
+        set(Dest::M, Comp::DPlusM),
+        at("SP"),
+        set(Dest::D, Comp::M),
+        at(block),
+        set(Dest::D, Comp::DMinusA),
+        at("R13"),
+        set(Dest::M, Comp::D),
+        at("ARG"),
+        set(Dest::D, Comp::M),
+        at("R14"),
+        set(Dest::M, Comp::D),
+        at(block),
+        set(Dest::D, Comp::A),
+        at("R15"),
+        set(Dest::M, Comp::D),
+    ]);
+    asm.extend(copy_asm(&format!("{}.args", label)));
+    asm.extend([
+        at("ARG"),
+        set(Dest::D, Comp::M),
+        at(block),
+        set(Dest::D, Comp::DPlusA),
+        at("LCL"),
+        set(Dest::M, Comp::D),
+        at("SP"),
+        set(Dest::M, Comp::D),
+        at(function.to_owned()),
+        jump(Comp::Zero, Jump::Jmp),
+    ]);
+
+    asm
+}
+
+// copies R15 words from where R13 points to where R14 does, lowest first
+fn copy_asm(label: &str) -> Vec<HackInstr> {
+    vec![
+        HackInstr::Label(label.to_owned()),
+        at("R13"),
+        set(Dest::A, Comp::M),
+        set(Dest::D, Comp::M),
+        at("R14"),
+        set(Dest::A, Comp::M),
+        set(Dest::M, Comp::D),
+        at("R13"),
+        set(Dest::M, Comp::MPlusOne),
+        at("R14"),
+        set(Dest::M, Comp::MPlusOne),
+        at("R15"),
+        set(Dest::MD, Comp::MMinusOne),
+        at(label.to_owned()),
+        jump(Comp::D, Jump::Jgt),
+    ]
+}
+
+// $CALL$ pushes the frame, points ARG and LCL at the callee's and jumps to it,
+// $RETURN$ is the code every return would have been
+fn call_routines() -> Vec<HackInstr> {
+    let mut asm = vec![
+        HackInstr::Label("$CALL$".to_owned()),
+        at("SP"),
+        set(Dest::AM, Comp::MPlusOne),
+        set(Dest::A, Comp::AMinusOne),
+        set(Dest::M, Comp::D),
+    ];
+    for pointer in ["LCL", "ARG", "THIS", "THAT"] {
+        asm.extend([
+            at(pointer),
+            set(Dest::D, Comp::M),
+            at("SP"),
+            set(Dest::AM, Comp::MPlusOne),
+            set(Dest::A, Comp::AMinusOne),
+            set(Dest::M, Comp::D),
+        ]);
+    }
+    asm.extend([
+        at("R13"),
+        set(Dest::D, Comp::M),
+        at(memory::FRAME_SIZE),
+        set(Dest::D, Comp::DPlusA),
+        at("SP"),
+        set(Dest::D, Comp::MMinusD),
+        at("ARG"),
+        set(Dest::M, Comp::D),
+        at("SP"),
+        set(Dest::D, Comp::M),
+        at("LCL"),
+        set(Dest::M, Comp::D),
+        at("R14"),
+        set(Dest::A, Comp::M),
+        jump(Comp::Zero, Jump::Jmp),
+    ]);
+
+    asm.push(HackInstr::Label("$RETURN$".to_owned()));
+    asm.extend(return_asm());
+
+    asm
+}
+
+/*
+ * Drops the functions nothing reachable from entry calls, mostly the parts of
+ * the OS a program doesn't use. Returns the names of the removed functions
+ */
+#[cfg(feature = "std")]
+pub fn remove_dead_functions(units: &mut [Unit], entry: &str) -> Vec<String> {
+    let reachable: BTreeSet<String> = analysis::reachable_functions(units, entry)
+        .into_iter()
+        .map(str::to_owned)
+        .collect();
+    let mut removed = Vec::new();
+
+    for unit in units {
+        let mut live = true;
+        unit.commands.retain(|command| {
+            if let Command::Function(name, _) = &command.node {
+                live = reachable.contains(name);
+                if !live {
+                    removed.push(name.clone());
+                }
+            }
+            live
+        });
+    }
+
+    removed
+}
+
+/* Inlined code longer than this stays a call, a call site alone takes about 45 instructions */
+pub const INLINE_THRESHOLD: usize = 60;
+
+/*
+ * A function simple enough to be inlined. Inlined, the body works on a frame
+ * built on top of the arguments the caller pushed: the pointers the body
+ * changes, so they can be restored, then the locals
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlineFunction {
+    /* statics in the body belong to the file the function was defined in */
+    pub filestem: String,
+    pub n_locals: u16,
+    /* everything up to the final return */
+    pub body: Vec<Command>,
+    /* arguments the body reads, the highest one used plus one */
+    pub arguments: u16,
+    pub saved_pointers: Vec<&'static str>,
+}
+
+impl InlineFunction {
+    /* slots the frame takes above the arguments */
+    pub fn frame_size(&self) -> u16 {
+        self.saved_pointers.len() as u16 + self.n_locals
+    }
+
+    pub fn prologue(&self) -> Vec<HackInstr> {
+        let mut asm = Vec::new();
+        for pointer in &self.saved_pointers {
+            asm.extend([at(*pointer), set(Dest::D, Comp::M)]);
+            asm.extend(push_d());
+        }
+        for _ in 0..self.n_locals {
+            asm.extend([
+                at("SP"),
+                set(Dest::A, Comp::M),
+                set(Dest::M, Comp::Zero),
+                at("SP"),
+                set(Dest::M, Comp::MPlusOne),
+            ]);
+        }
+
+        asm
+    }
+
+    /*
+     * Does what return would: the value on top of the stack replaces the
+     * arguments and the saved pointers are put back. depth is the number of
+     * slots between the first argument and SP
+     */
+    pub fn epilogue(&self, n_args: u16, depth: u16) -> Vec<HackInstr> {
+        if depth == 1 {
+            return Vec::new();
+        }
+
+        let mut asm = vec![
+            at("SP"),
+            set(Dest::A, Comp::MMinusOne),
+            set(Dest::D, Comp::M),
+            at("R13"),
+            set(Dest::M, Comp::D),
+        ];
+        for (idx, pointer) in self.saved_pointers.iter().enumerate() {
+            asm.extend(frame_address(depth - n_args - idx as u16));
+            asm.extend([set(Dest::D, Comp::M), at(*pointer), set(Dest::M, Comp::D)]);
+        }
+        asm.extend([
+            at(depth - 1),
+            set(Dest::D, Comp::A),
+            at("SP"),
+            set(Dest::M, Comp::MMinusD),
+            at("R13"),
+            set(Dest::D, Comp::M),
+            at("SP"),
+            set(Dest::A, Comp::MMinusOne),
+            set(Dest::M, Comp::D),
+        ]);
+
+        asm
+    }
+}
+
+/*
+ * Functions worth inlining: no labels, jumps or calls, a single return at the
+ * end, and a body that keeps to its own part of the stack
+ */
+#[cfg(feature = "std")]
+pub fn inlinable_functions(units: &[Unit]) -> BTreeMap<String, InlineFunction> {
+    let mut inlines = BTreeMap::new();
+
+    for unit in units {
+        let mut commands = unit.commands.iter().map(|command| &command.node).peekable();
+
+        while let Some(command) = commands.next() {
+            let Command::Function(name, n_locals) = command else {
+                continue;
+            };
+            let mut body = Vec::new();
+            while let Some(command) =
+                commands.next_if(|next| !matches!(next, Command::Function(..)))
+            {
+                body.push(command.clone());
+            }
+
+            if let Some(inline) = inline_function(unit.filestem(), *n_locals, body) {
+                inlines.insert(name.clone(), inline);
+            }
+        }
+    }
+
+    inlines
+}
+
+#[cfg(feature = "std")]
+fn inline_function(
+    filestem: &str,
+    n_locals: u16,
+    mut body: Vec<Command>,
+) -> Option<InlineFunction> {
+    if body.pop() != Some(Command::Return) {
+        return None;
+    }
+
+    let mut arguments = 0;
+    let mut saved_pointers = Vec::new();
+    // values the body has on the stack, it must never take more than it pushed
+    let mut depth: u16 = 0;
+
+    for command in &body {
+        match command {
+            Command::Label(_)
+            | Command::Goto(_)
+            | Command::IfGoto(_)
+            | Command::Call(..)
+            | Command::Return => return None,
+            Command::Push(MemorySegment::Argument, offset)
+            | Command::Pop(MemorySegment::Argument, offset) => {
+                arguments = arguments.max(offset + 1);
+            }
+            Command::Push(MemorySegment::Local, offset)
+            | Command::Pop(MemorySegment::Local, offset)
+                if *offset >= n_locals =>
+            {
+                return None
+            }
+            Command::Pop(MemorySegment::Pointer, offset) => {
+                let pointer = pointer(*offset);
+                if !saved_pointers.contains(&pointer) {
+                    saved_pointers.push(pointer);
+                }
+            }
+            _ => (),
+        }
+
+        let (popped, pushed) = stack_effect(command);
+        depth = depth.checked_sub(popped)? + pushed;
+    }
+
+    (depth > 0).then(|| InlineFunction {
+        filestem: filestem.to_owned(),
+        n_locals,
+        body,
+        arguments,
+        saved_pointers,
+    })
+}
+
+/* values a command takes off the stack and puts on it */
+pub fn stack_effect(command: &Command) -> (u16, u16) {
+    match command {
+        Command::Push(..) => (0, 1),
+        Command::Pop(..) | Command::IfGoto(_) => (1, 0),
+        Command::Neg | Command::Not => (1, 1),
+        Command::Add
+        | Command::Sub
+        | Command::And
+        | Command::Or
+        | Command::Eq
+        | Command::Lt
+        | Command::Gt => (2, 1),
+        Command::Call(_, n_args) => (*n_args, 1),
+        Command::Label(_) | Command::Goto(_) | Command::Function(..) | Command::Return => (0, 0),
+    }
+}
+
+// A = SP - distance
+fn frame_address(distance: u16) -> Vec<HackInstr> {
+    if distance <= 3 {
+        let mut asm = vec![at("SP"), set(Dest::A, Comp::M)];
+        asm.extend((0..distance).map(|_| set(Dest::A, Comp::AMinusOne)));
+        asm
+    } else {
+        vec![
+            at("SP"),
+            set(Dest::D, Comp::M),
+            at(distance),
+            set(Dest::A, Comp::DMinusA),
+        ]
+    }
+}
+
+/* pushes the slot distance below SP */
+pub fn frame_push(distance: u16) -> Vec<HackInstr> {
+    let mut asm = frame_address(distance);
+    asm.push(set(Dest::D, Comp::M));
+    asm.extend(push_d());
+
+    asm
+}
+
+/* pops into the slot distance below SP, as it was before the pop */
+pub fn frame_pop(distance: u16) -> Vec<HackInstr> {
+    let mut asm = vec![
+        at("SP"),
+        set(Dest::AM, Comp::MMinusOne),
+        set(Dest::D, Comp::M),
+    ];
+    if distance <= 4 {
+        asm.extend((1..distance).map(|_| set(Dest::A, Comp::AMinusOne)));
+        asm.push(set(Dest::M, Comp::D));
+    } else {
+        asm.extend([
+            at("R13"),
+            set(Dest::M, Comp::D),
+            at("SP"),
+            set(Dest::D, Comp::M),
+            at(distance - 1),
+            set(Dest::D, Comp::DMinusA),
+            at("R14"),
+            set(Dest::M, Comp::D),
+            at("R13"),
+            set(Dest::D, Comp::M),
+            at("R14"),
+            set(Dest::A, Comp::M),
+            set(Dest::M, Comp::D),
+        ]);
+    }
+
+    asm
+}
@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use crate::vfs::MemoryFs;
+
+/*
+ * A small OS built into hackvm, for `[os] bundled = true` in hackvm.toml. It
+ * has Sys.init, which runs Main.main, and every function of
+ * analysis::OS_FUNCTIONS, written straight in VM code: the heap is never
+ * reused and Output has no font, so it prints nothing. The nand2tetris OS
+ * does all of it, `[os] path` points at it instead
+ */
+pub const FILES: [(&str, &str); 8] = [
+    ("Array.vm", include_str!("../os/Array.vm")),
+    ("Keyboard.vm", include_str!("../os/Keyboard.vm")),
+    ("Math.vm", include_str!("../os/Math.vm")),
+    ("Memory.vm", include_str!("../os/Memory.vm")),
+    ("Output.vm", include_str!("../os/Output.vm")),
+    ("Screen.vm", include_str!("../os/Screen.vm")),
+    ("String.vm", include_str!("../os/String.vm")),
+    ("Sys.vm", include_str!("../os/Sys.vm")),
+];
+
+/* where the files are in diagnostics, no real directory is called that */
+pub const DIR: &str = "hackvm:os";
+
+/* the files under DIR, for reading along with the project's */
+pub fn files() -> MemoryFs {
+    let mut files = MemoryFs::new();
+    for (name, source) in FILES {
+        files.insert(Path::new(DIR).join(name), source);
+    }
+
+    files
+}
@@ -0,0 +1,63 @@
+use core::fmt;
+
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::VmError;
+
+/*
+ * Where a translator writes its assembly. Every io::Write is one with std,
+ * anything that only implements fmt::Write, like a String, can be wrapped in a
+ * FmtWriter. The translator buffers on its own, so writes come in large chunks
+ */
+pub trait AsmWrite {
+    type Error: Into<VmError>;
+
+    fn write_asm(&mut self, asm: &str) -> Result<(), Self::Error>;
+    fn flush_asm(&mut self) -> Result<(), Self::Error>;
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> AsmWrite for W {
+    type Error = io::Error;
+
+    fn write_asm(&mut self, asm: &str) -> Result<(), Self::Error> {
+        self.write_all(asm.as_bytes())
+    }
+
+    fn flush_asm(&mut self) -> Result<(), Self::Error> {
+        self.flush()
+    }
+}
+
+/* Translates into a fmt::Write, e.g. a String, without needing std */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FmtWriter<W>(pub W);
+
+impl<W: fmt::Write> AsmWrite for FmtWriter<W> {
+    type Error = fmt::Error;
+
+    fn write_asm(&mut self, asm: &str) -> Result<(), Self::Error> {
+        self.0.write_str(asm)
+    }
+
+    fn flush_asm(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/* Throws the assembly away, for checking a program without writing it anywhere */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Discard;
+
+impl AsmWrite for Discard {
+    type Error = fmt::Error;
+
+    fn write_asm(&mut self, _asm: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn flush_asm(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
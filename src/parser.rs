@@ -0,0 +1,434 @@
+use core::{
+    ops::Range,
+    str::{FromStr, SplitInclusive},
+};
+#[cfg(feature = "std")]
+use std::io::BufRead;
+
+use crate::{memory, prelude::*, Command, MemorySegment, Span, Spanned, VmError};
+
+/*
+ * Per-line state shared by every way of parsing a source: skips blank
+ * lines and comments and tags commands and errors with their span.
+ * Block comments can span several lines so their state is carried over.
+ */
+#[derive(Debug, Default)]
+struct LineParser {
+    line_no: usize,
+    /* byte offset of the next line in the source */
+    offset: usize,
+    /* where the currently open block comment started */
+    block_comment: Option<Span>,
+    finished: bool,
+    /* trailing tokens are errors instead of warnings */
+    strict: bool,
+    warnings: Vec<VmError>,
+}
+
+impl LineParser {
+    /* raw_line may still carry its line terminator */
+    fn parse_line(&mut self, raw_line: &str) -> Option<Result<Spanned<Command>, VmError>> {
+        self.line_no += 1;
+        let line_start = self.offset;
+        self.offset += raw_line.len();
+        let line = raw_line.trim_end_matches(['\n', '\r']);
+
+        let mut in_block = self.block_comment.is_some();
+        let code = strip_comments(line, &mut in_block);
+        self.block_comment = match (self.block_comment, in_block) {
+            (None, true) => {
+                let opened = line.rfind("/*").unwrap_or(0);
+                Some(Span::in_line(
+                    self.line_no,
+                    line_start,
+                    line,
+                    opened,
+                    opened + 2,
+                ))
+            }
+            (open, true) => open,
+            (_, false) => None,
+        };
+
+        let trimmed = code.trim();
+        if trimmed.is_empty() {
+            return None;
+        }
+
+        let span_of = |range: Range<usize>| {
+            Span::in_line(self.line_no, line_start, line, range.start, range.end)
+        };
+        let start = code.len() - code.trim_start().len();
+        let end = start + trimmed.len();
+
+        let command = match parse_code(&code) {
+            Ok(command) => command,
+            Err((range, err)) => return Some(Err(err.at(span_of(range)))),
+        };
+
+        if let Some((range, keyword)) = trailing_tokens(&code) {
+            let err = VmError::TrailingTokens {
+                keyword: keyword.to_owned(),
+                extra: code[range.clone()].to_owned(),
+            }
+            .at(span_of(range));
+            if self.strict {
+                return Some(Err(err));
+            }
+            self.warnings.push(err);
+        }
+
+        Some(Ok(Spanned::new(command, span_of(start..end))))
+    }
+
+    /* called once the source is exhausted, reports a block comment left open */
+    fn finish(&mut self) -> Option<Result<Spanned<Command>, VmError>> {
+        if self.finished {
+            return None;
+        }
+        self.finished = true;
+
+        let open = self.block_comment?;
+        Some(Err(VmError::MalformedLine {
+            line: "/*".to_owned(),
+            reason: "Unterminated block comment".to_owned(),
+        }
+        .at(open)))
+    }
+}
+
+/* Parses a whole .vm source held in memory */
+#[derive(Debug)]
+pub struct Parser<'a> {
+    lines: SplitInclusive<'a, char>,
+    state: LineParser,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Parser {
+            lines: source.split_inclusive('\n'),
+            state: LineParser::default(),
+        }
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.state.strict = strict;
+        self
+    }
+
+    /* warnings for the lines parsed so far */
+    pub fn take_warnings(&mut self) -> Vec<VmError> {
+        core::mem::take(&mut self.state.warnings)
+    }
+
+    pub fn parse_all(self) -> Result<Vec<Spanned<Command>>, VmError> {
+        self.collect()
+    }
+}
+
+impl Iterator for Parser<'_> {
+    type Item = Result<Spanned<Command>, VmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(line) = self.lines.next() else {
+                return self.state.finish();
+            };
+            if let Some(result) = self.state.parse_line(line) {
+                return Some(result);
+            }
+        }
+    }
+}
+
+/* Streams commands out of a reader one line at a time, without loading the whole source */
+#[cfg(feature = "std")]
+pub fn parse_lines<R: BufRead>(reader: R) -> ParseLines<R> {
+    ParseLines {
+        reader,
+        buf: String::new(),
+        state: LineParser::default(),
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ParseLines<R> {
+    reader: R,
+    buf: String,
+    state: LineParser,
+}
+
+#[cfg(feature = "std")]
+impl<R> ParseLines<R> {
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.state.strict = strict;
+        self
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<VmError> {
+        core::mem::take(&mut self.state.warnings)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: BufRead> Iterator for ParseLines<R> {
+    type Item = Result<Spanned<Command>, VmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_line(&mut self.buf) {
+                Ok(0) => return self.state.finish(),
+                Ok(_) => (),
+                Err(err) => return Some(Err(VmError::Io(err))),
+            }
+            if let Some(result) = self.state.parse_line(&self.buf) {
+                return Some(result);
+            }
+        }
+    }
+}
+
+/* Parses a single command, errors carry the span of the offending token within the line */
+pub fn parse(line: &str) -> Result<Command, VmError> {
+    line.parse()
+}
+
+/* "push local 2".parse::<Command>(), comments are skipped like in a file */
+impl FromStr for Command {
+    type Err = VmError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let code = strip_comments(line, &mut false);
+        parse_code(&code)
+            .map_err(|(range, err)| err.at(Span::in_line(1, 0, line, range.start, range.end)))
+    }
+}
+
+/* tokens of a comment free line with their byte ranges */
+fn tokenize(code: &str) -> Vec<(Range<usize>, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    for (idx, c) in code.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(token_start)) => {
+                tokens.push((token_start..idx, &code[token_start..idx]));
+                start = None;
+            }
+            (false, None) => start = Some(idx),
+            _ => (),
+        }
+    }
+    if let Some(token_start) = start {
+        tokens.push((token_start..code.len(), &code[token_start..]));
+    }
+
+    tokens
+}
+
+fn parse_code(code: &str) -> Result<Command, (Range<usize>, VmError)> {
+    let line = code.trim();
+    let tokens = tokenize(code);
+    let malformed = |range: Range<usize>, reason: String| {
+        (
+            range,
+            VmError::MalformedLine {
+                line: line.to_owned(),
+                reason,
+            },
+        )
+    };
+
+    let operand = |idx: usize| {
+        tokens.get(idx).cloned().ok_or_else(|| {
+            let end = code.trim_end().len();
+            malformed(end..end, format!("Missing operand {}", idx))
+        })
+    };
+    let number = |idx: usize| {
+        let (range, token) = operand(idx)?;
+        token
+            .parse::<u16>()
+            .map_err(|err| malformed(range, err.to_string()))
+    };
+    let segment = |idx: usize| {
+        let (range, token) = operand(idx)?;
+        MemorySegment::from_str(token).map_err(|err| (range, err))
+    };
+    // 15-bit values only, anything past that can't be loaded with @value
+    let constant = |idx: usize| {
+        let (range, token) = operand(idx)?;
+        match token.parse::<u16>() {
+            Ok(value) if value <= memory::MAX_CONSTANT => Ok(value),
+            Err(err) if !token.bytes().all(|b| b.is_ascii_digit()) => {
+                Err(malformed(range, err.to_string()))
+            }
+            _ => Err((range, VmError::ConstantOutOfRange(token.to_owned()))),
+        }
+    };
+    let name = |idx: usize| {
+        let (range, token) = operand(idx)?;
+        match validate_identifier(token) {
+            Ok(()) => Ok(token.to_owned()),
+            Err((at, reason)) => {
+                let start = range.start + at;
+                let end = start + token[at..].chars().next().map_or(0, char::len_utf8);
+                Err((
+                    start..end,
+                    VmError::BadIdentifier {
+                        name: token.to_owned(),
+                        reason,
+                    },
+                ))
+            }
+        }
+    };
+
+    let Some((keyword_range, keyword)) = tokens.first().cloned() else {
+        return Err(malformed(0..0, "Empty command".to_owned()));
+    };
+
+    if let Some((arity, expected)) = operands(keyword) {
+        let found = tokens.len() - 1;
+        if found < arity {
+            let end = code.trim_end().len();
+            return Err(malformed(
+                end..end,
+                format!(
+                    "`{}` expects {}, got {} operand{}",
+                    keyword,
+                    expected,
+                    found,
+                    if found == 1 { "" } else { "s" }
+                ),
+            ));
+        }
+    }
+
+    let command = match keyword {
+        "push" => match segment(1)? {
+            MemorySegment::Constant => Command::Push(MemorySegment::Constant, constant(2)?),
+            segment => Command::Push(segment, number(2)?),
+        },
+        "pop" => match segment(1)? {
+            MemorySegment::Constant => {
+                return Err((
+                    operand(1)?.0,
+                    VmError::BadSegment {
+                        segment: "constant".to_owned(),
+                        reason: "pop operation cannot be performed for a constant".to_owned(),
+                    },
+                ))
+            }
+            segment => Command::Pop(segment, number(2)?),
+        },
+
+        "add" => Command::Add,
+        "sub" => Command::Sub,
+        "neg" => Command::Neg,
+        "not" => Command::Not,
+        "or" => Command::Or,
+        "and" => Command::And,
+        "eq" => Command::Eq,
+        "lt" => Command::Lt,
+        "gt" => Command::Gt,
+
+        "label" => Command::Label(name(1)?),
+        "goto" => Command::Goto(name(1)?),
+        "if-goto" => Command::IfGoto(name(1)?),
+
+        "function" => Command::Function(name(1)?, number(2)?),
+        "call" => Command::Call(name(1)?, number(2)?),
+        "return" => Command::Return,
+
+        _ => return Err((keyword_range, VmError::UnknownCommand(keyword.to_owned()))),
+    };
+
+    Ok(command)
+}
+
+/*
+ * Labels and function names are made of letters, digits, `_`, `.` and `:`
+ * and can't start with a digit. Errors carry the byte index of the bad char
+ */
+fn validate_identifier(name: &str) -> Result<(), (usize, String)> {
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err((0, "identifiers must not start with a digit".to_owned()));
+    }
+
+    match name
+        .char_indices()
+        .find(|(_, c)| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':')))
+    {
+        Some((idx, c)) => Err((idx, format!("illegal character `{}`", c))),
+        None => Ok(()),
+    }
+}
+
+/* range of whatever follows a complete command, along with the command keyword */
+fn trailing_tokens(code: &str) -> Option<(Range<usize>, &str)> {
+    let tokens = tokenize(code);
+    let (_, keyword) = tokens.first()?;
+    let (arity, _) = operands(keyword)?;
+    let (first, _) = tokens.get(arity + 1)?;
+    let (last, _) = tokens.last()?;
+
+    Some((first.start..last.end, keyword))
+}
+
+/* number of operands a keyword takes and how to describe them */
+fn operands(keyword: &str) -> Option<(usize, &'static str)> {
+    let arity = match keyword {
+        "push" | "pop" => (2, "a segment and an offset"),
+        "label" | "goto" | "if-goto" => (1, "a label"),
+        "function" => (2, "a function name and a number of locals"),
+        "call" => (2, "a function name and a number of arguments"),
+        "add" | "sub" | "neg" | "not" | "or" | "and" | "eq" | "lt" | "gt" | "return" => {
+            (0, "no operands")
+        }
+        _ => return None,
+    };
+
+    Some(arity)
+}
+
+/*
+ * Blanks out `//` comments and the parts of the line covered by block comments,
+ * keeping byte offsets intact so spans still point into the original line.
+ * in_block tracks whether a block comment is open at the start / end of the line
+ */
+fn strip_comments(line: &str, in_block: &mut bool) -> String {
+    let bytes = line.as_bytes();
+    let mut code = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        let rest = &bytes[idx..];
+        if *in_block {
+            if rest.starts_with(b"*/") {
+                *in_block = false;
+                code.extend_from_slice(b"  ");
+                idx += 2;
+            } else {
+                code.push(b' ');
+                idx += 1;
+            }
+        } else if rest.starts_with(b"//") {
+            code.resize(bytes.len(), b' ');
+            break;
+        } else if rest.starts_with(b"/*") {
+            *in_block = true;
+            code.extend_from_slice(b"  ");
+            idx += 2;
+        } else {
+            code.push(bytes[idx]);
+            idx += 1;
+        }
+    }
+
+    // only whole chars outside of comments are copied so this is still valid utf-8
+    String::from_utf8_lossy(&code).into_owned()
+}
@@ -0,0 +1,245 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::{fmt::Write, mem};
+
+use crate::{
+    memory::StaticAllocator, optimize::InlineFunction, output::AsmWrite, output::FmtWriter,
+    prelude::*, sourcemap::SourceMap, Command, Spanned, TranslationOptions, TranslationStats,
+    VMTranslator, VmError,
+};
+
+/*
+ * Translating the files of a program apart from each other, e.g. on several
+ * threads, and stitching them back together in order:
+ *
+ *   let first = translator.reserve_ids(translator.ids_needed(&commands));
+ *   let statics = translator.static_addresses();
+ *   let file = TranslatedFile::new(commands, "Main", "Main.vm", &options, &inlines, statics, first)?;
+ *   translator.append(file)?;
+ *
+ * Return addresses and comparison labels are numbered across the whole
+ * program, so every file gets a block of ids reserved up front, in file order.
+ * The blocks are as large as a file could need, which leaves gaps in the
+ * numbering where optimizations fused or inlined the code taking an id. The
+ * static addresses are allocated up front as well, see
+ * VMTranslatorBuilder::static_addresses
+ */
+
+/* Where the return addresses and comparison labels of some code start, or how many it takes */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Ids {
+    pub returns: u16,
+    pub labels: u16,
+}
+
+/* One file translated on its own, for VMTranslator::append */
+#[derive(Debug, Clone, Default)]
+pub struct TranslatedFile {
+    asm: String,
+    lines: usize,
+    last_code_line: usize,
+    stats: TranslationStats,
+    statics: BTreeSet<String>,
+    /* scoped labels, to catch ones declared in two files */
+    labels: BTreeSet<String>,
+    source_map: SourceMap,
+}
+
+impl TranslatedFile {
+    /*
+     * Translates the commands of one file like write_commands would, numbering
+     * from first on. inlines are the functions the whole program inlines and
+     * statics the addresses of its static variables
+     */
+    pub fn new(
+        commands: Vec<Spanned<Command>>,
+        filestem: &str,
+        source: &str,
+        options: &TranslationOptions,
+        inlines: &BTreeMap<String, InlineFunction>,
+        statics: &StaticAllocator,
+        first: Ids,
+    ) -> Result<Self, VmError> {
+        let mut translator =
+            VMTranslator::with_writer(FmtWriter(String::new()), filestem, options.clone());
+        translator.static_addresses = statics.clone();
+        translator.start_file(filestem, source);
+        translator.inline_functions(inlines.clone());
+        translator.reserve_ids(first);
+        translator.write_commands(commands)?;
+
+        let mut file = TranslatedFile {
+            lines: translator.lines,
+            last_code_line: translator.last_code_line,
+            stats: mem::take(&mut translator.stats),
+            statics: mem::take(&mut translator.statics),
+            labels: mem::take(&mut translator.labels),
+            source_map: mem::take(&mut translator.source_map),
+            ..TranslatedFile::default()
+        };
+        let FmtWriter(asm) = translator.into_inner()?;
+        file.asm = asm;
+
+        Ok(file)
+    }
+
+    pub fn asm(&self) -> &str {
+        &self.asm
+    }
+
+    pub fn instructions(&self) -> usize {
+        self.stats.instructions
+    }
+
+    /*
+     * The file as text, for keeping it between runs. Counts per command and
+     * the source map are left out, so stats and source_map are only complete
+     * for files translated in this run:
+     *
+     *   lines\t52
+     *   last_code_line\t50
+     *   instructions\t300
+     *   calls\t2
+     *   comparisons\t1
+     *   static\tMain.0
+     *   label\tMain.fibonacci$END
+     *   function\tMain.fibonacci\t250
+     *   file\tMain.vm\t300
+     *   asm
+     *   <the assembly>
+     */
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "lines\t{}", self.lines);
+        let _ = writeln!(out, "last_code_line\t{}", self.last_code_line);
+        let _ = writeln!(out, "instructions\t{}", self.stats.instructions);
+        let _ = writeln!(out, "calls\t{}", self.stats.calls);
+        let _ = writeln!(out, "comparisons\t{}", self.stats.comparisons);
+        for name in &self.statics {
+            let _ = writeln!(out, "static\t{}", name);
+        }
+        for label in &self.labels {
+            let _ = writeln!(out, "label\t{}", label);
+        }
+        for (function, count) in &self.stats.by_function {
+            let _ = writeln!(out, "function\t{}\t{}", function, count);
+        }
+        for (file, count) in &self.stats.by_file {
+            let _ = writeln!(out, "file\t{}\t{}", file, count);
+        }
+        out.push_str("asm\n");
+        out.push_str(&self.asm);
+
+        out
+    }
+
+    /* reads back what encode wrote, None if it is malformed */
+    pub fn decode(text: &str) -> Option<Self> {
+        let mut file = TranslatedFile::default();
+        let mut rest = text;
+
+        loop {
+            let (line, next) = rest.split_once('\n')?;
+            rest = next;
+            if line == "asm" {
+                file.asm = rest.to_owned();
+                return Some(file);
+            }
+
+            let mut fields = line.split('\t');
+            let (key, value) = (fields.next()?, fields.next()?);
+            // the count after function and file names
+            let count = fields.next().and_then(|count| count.parse::<usize>().ok());
+            match key {
+                "lines" => file.lines = value.parse().ok()?,
+                "last_code_line" => file.last_code_line = value.parse().ok()?,
+                "instructions" => file.stats.instructions = value.parse().ok()?,
+                "calls" => file.stats.calls = value.parse().ok()?,
+                "comparisons" => file.stats.comparisons = value.parse().ok()?,
+                "static" => {
+                    file.statics.insert(value.to_owned());
+                }
+                "label" => {
+                    file.labels.insert(value.to_owned());
+                }
+                "function" => {
+                    file.stats.by_function.insert(value.to_owned(), count?);
+                }
+                "file" => {
+                    file.stats.by_file.insert(value.to_owned(), count?);
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl<W: AsmWrite> VMTranslator<W> {
+    /* the most ids the commands of a file can take, each call and comparison one at most */
+    pub fn ids_needed(&self, commands: &[Spanned<Command>]) -> Ids {
+        let mut ids = Ids::default();
+        for command in commands {
+            match &command.node {
+                Command::Call(function, _) => {
+                    ids.returns += 1;
+                    // an inlined body numbers its own comparisons
+                    if let Some(inline) = self.inlines.get(function) {
+                        ids.labels +=
+                            inline.body.iter().filter(|c| is_comparison(c)).count() as u16;
+                    }
+                }
+                comparison if is_comparison(comparison) => ids.labels += 1,
+                _ => {}
+            }
+        }
+
+        ids
+    }
+
+    /* skips the next ids, leaving them to code numbered elsewhere, and returns the first of them */
+    pub fn reserve_ids(&mut self, ids: Ids) -> Ids {
+        let first = Ids {
+            returns: self.ret_idx,
+            labels: self.label_strategy.reserve(ids.labels),
+        };
+        self.ret_idx += ids.returns;
+
+        first
+    }
+
+    /* writes a file translated with TranslatedFile::new after everything so far */
+    pub fn append(&mut self, file: TranslatedFile) -> Result<(), VmError> {
+        self.emit_deferred();
+        if let Some(label) = file.labels.intersection(&self.labels).next() {
+            return Err(VmError::DuplicateLabel {
+                label: label.clone(),
+                function: None,
+            });
+        }
+
+        let (lines, instructions) = (self.lines, self.stats.instructions);
+        for mut mapping in file.source_map.mappings {
+            mapping.asm_lines = (mapping.asm_lines.0 + lines, mapping.asm_lines.1 + lines);
+            mapping.rom = mapping
+                .rom
+                .map(|(first, last)| (first + instructions, last + instructions));
+            self.source_map.push(mapping);
+        }
+        self.stats.merge(&file.stats);
+        self.statics.extend(file.statics);
+        self.stats.static_slots = self.statics.len();
+        self.labels.extend(file.labels);
+        if file.last_code_line > 0 {
+            self.last_code_line = lines + file.last_code_line;
+        }
+        self.lines += file.lines;
+        self.curr_function = None;
+        self.a_register = None;
+
+        self.buffer.push_str(&file.asm);
+        self.write_out().map_err(Into::into)
+    }
+}
+
+fn is_comparison(command: &Command) -> bool {
+    matches!(command, Command::Eq | Command::Lt | Command::Gt)
+}
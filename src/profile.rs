@@ -0,0 +1,144 @@
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+use crate::{interpreter::Interpreter, prelude::*, sourcemap::SourceMap, Command};
+
+/* What code outside of any function is counted under: the bootstrap and shared runtime */
+pub const NO_FUNCTION: &str = "(runtime)";
+
+/*
+ * Where a program spent its time by function, from how often each of its
+ * instructions or commands ran, see count_hits. A function's calls are the
+ * times its first instruction ran, code inlined into a caller is the caller's
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    /* most steps first, ties by name */
+    pub functions: Vec<FunctionProfile>,
+    pub total: u64,
+    /* what a step is, instructions or commands */
+    pub unit: &'static str,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionProfile {
+    pub name: String,
+    pub steps: u64,
+    pub calls: u64,
+}
+
+impl Profile {
+    /*
+     * From the hits of every address and what's at it: the function it's in
+     * and whether that's where the function is entered
+     */
+    pub fn new<'a>(
+        hits: &[u64],
+        unit: &'static str,
+        function_at: impl Fn(usize) -> Option<(&'a str, bool)>,
+    ) -> Self {
+        let mut by_name: BTreeMap<&str, FunctionProfile> = BTreeMap::new();
+        for (address, count) in hits.iter().enumerate() {
+            let (name, entry) = function_at(address).unwrap_or((NO_FUNCTION, false));
+            let function = by_name.entry(name).or_insert_with(|| FunctionProfile {
+                name: name.to_owned(),
+                ..FunctionProfile::default()
+            });
+            function.steps += count;
+            if entry {
+                function.calls += count;
+            }
+        }
+
+        let mut functions: Vec<_> = by_name
+            .into_values()
+            .filter(|function| function.steps > 0)
+            .collect();
+        functions.sort_by(|a, b| b.steps.cmp(&a.steps).then_with(|| a.name.cmp(&b.name)));
+        Profile {
+            total: functions.iter().map(|function| function.steps).sum(),
+            functions,
+            unit,
+        }
+    }
+
+    /* the CPU emulator's hits, with the functions from the source map of its translation */
+    pub fn from_source_map(hits: &[u64], map: &SourceMap) -> Self {
+        let mut owners: Vec<Option<(&str, bool)>> = vec![None; hits.len()];
+        let mut function = None;
+        // a function with no locals has no code of its own, it's entered at the next command's
+        let mut entering = false;
+        for mapping in &map.mappings {
+            if let Some(rest) = mapping.command.strip_prefix("function ") {
+                function = rest.split(' ').next();
+                entering = true;
+            }
+            let Some(slots) = mapping
+                .rom
+                .and_then(|(first, last)| owners.get_mut(first..=last))
+            else {
+                continue;
+            };
+            for (offset, owner) in slots.iter_mut().enumerate() {
+                *owner = function.map(|name| (name, entering && offset == 0));
+            }
+            entering = false;
+        }
+
+        Profile::new(hits, "instructions", |address| owners[address])
+    }
+
+    /* the interpreter's hits, every command belonging to the function command before it */
+    pub fn from_interpreter(vm: &Interpreter) -> Self {
+        let hits = vm.hits();
+        let mut owners: Vec<Option<(&str, bool)>> = Vec::with_capacity(hits.len());
+        let mut function = None;
+        for pc in 0..hits.len() {
+            let mut entry = false;
+            if let Some((_, command)) = vm.command_at(pc) {
+                if let Command::Function(name, _) = command.node {
+                    function = Some(name.as_str());
+                    entry = true;
+                }
+            }
+            owners.push(function.map(|name| (name, entry)));
+        }
+
+        Profile::new(hits, "commands", |pc| owners[pc])
+    }
+}
+
+/*
+ * function                      instructions       %     calls   per call
+ * Main.fibonacci                        1203   72.0%         9      133.7
+ * ...
+ * total                                 1671
+ */
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .functions
+            .iter()
+            .map(|function| function.name.len())
+            .fold(24, usize::max);
+
+        writeln!(
+            f,
+            "{:<width$} {:>12} {:>7} {:>9} {:>10}",
+            "function", self.unit, "%", "calls", "per call"
+        )?;
+        for function in &self.functions {
+            let share = function.steps as f64 * 100.0 / self.total.max(1) as f64;
+            write!(
+                f,
+                "{:<width$} {:>12} {:>6.1}% {:>9}",
+                function.name, function.steps, share, function.calls
+            )?;
+            match function.calls {
+                0 => writeln!(f)?,
+                calls => writeln!(f, " {:>10.1}", function.steps as f64 / calls as f64)?,
+            }
+        }
+        writeln!(f, "{:<width$} {:>12}", "total", self.total)
+    }
+}
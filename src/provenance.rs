@@ -0,0 +1,183 @@
+use std::{fmt::Write, path::Path};
+
+use crate::vfs::FileProvider;
+
+const HEADER_START: &str = "// hackvm provenance";
+const HEADER_END: &str = "// end provenance";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputRecord {
+    pub path: String,
+    pub hash: u64,
+}
+
+/*
+ * Records how an .asm file was produced: translator version, options and the
+ * hashes of every input. Written as a comment block at the top of the output
+ * and free of timestamps so identical runs produce identical headers.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    pub version: String,
+    pub options: Vec<(String, String)>,
+    pub inputs: Vec<InputRecord>,
+}
+
+impl Default for Provenance {
+    fn default() -> Self {
+        Provenance {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            options: Vec::new(),
+            inputs: Vec::new(),
+        }
+    }
+}
+
+impl Provenance {
+    pub fn new() -> Self {
+        Provenance::default()
+    }
+
+    pub fn add_option(&mut self, name: &str, value: impl ToString) {
+        self.options.push((name.to_owned(), value.to_string()));
+    }
+
+    pub fn add_input(&mut self, path: &Path, content: &str) {
+        self.inputs.push(InputRecord {
+            path: path.display().to_string(),
+            hash: content_hash(content.as_bytes()),
+        });
+    }
+
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        hasher.update(self.version.as_bytes());
+        for (name, value) in &self.options {
+            hasher.update(name.as_bytes());
+            hasher.update(b"=");
+            hasher.update(value.as_bytes());
+        }
+        for input in &self.inputs {
+            hasher.update(input.path.as_bytes());
+            hasher.update(&input.hash.to_le_bytes());
+        }
+
+        hasher.finish()
+    }
+
+    pub fn to_header(&self) -> String {
+        let mut header = format!("{}\n// version: {}\n", HEADER_START, self.version);
+        for (name, value) in &self.options {
+            let _ = writeln!(header, "// option: {}={}", name, value);
+        }
+        for input in &self.inputs {
+            let _ = writeln!(header, "// input: {:016x} {}", input.hash, input.path);
+        }
+        let _ = writeln!(header, "// fingerprint: {:016x}", self.fingerprint());
+        header.push_str(HEADER_END);
+        header.push('\n');
+
+        header
+    }
+
+    /* Reads the header back from generated assembly, checking it wasn't edited by hand */
+    pub fn parse(asm: &str) -> Result<Provenance, String> {
+        let mut lines = asm
+            .lines()
+            .map(str::trim)
+            .skip_while(|line| line.is_empty());
+        if lines.next() != Some(HEADER_START) {
+            return Err("No provenance header found".to_owned());
+        }
+
+        let mut provenance = Provenance {
+            version: String::new(),
+            options: Vec::new(),
+            inputs: Vec::new(),
+        };
+        let mut fingerprint = None;
+
+        for line in lines.by_ref() {
+            if line == HEADER_END {
+                break;
+            }
+
+            let entry = line
+                .strip_prefix("// ")
+                .ok_or_else(|| format!("Malformed provenance line {}", line))?;
+            let (key, value) = entry
+                .split_once(": ")
+                .ok_or_else(|| format!("Malformed provenance line {}", line))?;
+
+            match key {
+                "version" => provenance.version = value.to_owned(),
+                "option" => {
+                    let (name, value) = value
+                        .split_once('=')
+                        .ok_or_else(|| format!("Malformed option {}", value))?;
+                    provenance.options.push((name.to_owned(), value.to_owned()));
+                }
+                "input" => {
+                    let (hash, path) = value
+                        .split_once(' ')
+                        .ok_or_else(|| format!("Malformed input {}", value))?;
+                    provenance.inputs.push(InputRecord {
+                        path: path.to_owned(),
+                        hash: parse_hex(hash)?,
+                    });
+                }
+                "fingerprint" => fingerprint = Some(parse_hex(value)?),
+                _ => return Err(format!("Unknown provenance entry {}", key)),
+            }
+        }
+
+        match fingerprint {
+            Some(fingerprint) if fingerprint == provenance.fingerprint() => Ok(provenance),
+            Some(_) => Err("Provenance fingerprint doesn't match its contents".to_owned()),
+            None => Err("Provenance header has no fingerprint".to_owned()),
+        }
+    }
+
+    /* Inputs that changed or disappeared since the output was generated */
+    pub fn stale_inputs(&self, provider: &dyn FileProvider) -> Vec<&InputRecord> {
+        self.inputs
+            .iter()
+            .filter(
+                |input| match provider.read_to_string(Path::new(&input.path)) {
+                    Ok(content) => content_hash(content.as_bytes()) != input.hash,
+                    Err(_) => true,
+                },
+            )
+            .collect()
+    }
+}
+
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+// FNV-1a is stable across platforms and releases, unlike std's DefaultHasher
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf29ce484222325)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn parse_hex(value: &str) -> Result<u64, String> {
+    u64::from_str_radix(value, 16).map_err(|_| format!("Malformed hash {}", value))
+}
@@ -0,0 +1,251 @@
+/*
+ * The translator as a Python module, for scripting over many submissions at
+ * once. Build it with maturin, which also turns on pyo3's extension-module:
+ *
+ *   maturin develop --features python,pyo3/extension-module
+ *
+ * and from Python:
+ *
+ *   import hackvm
+ *   asm = hackvm.translate(source, "Main", opt_level=1)
+ *   for diagnostic in hackvm.diagnostics(source, "Main.vm"):
+ *       print(diagnostic)
+ */
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyValueError},
+    prelude::*,
+};
+
+use crate::{
+    analysis::{self, Unit},
+    diagnostics::{Diagnostic, Severity},
+    output::Discard,
+    translate_str, Bootstrap, Command, OptLevel, Parser, Spanned, TranslationOptions, VMTranslator,
+    VmError,
+};
+
+// raised with the rendered diagnostic and the Diagnostic itself as its args
+create_exception!(hackvm, TranslationError, PyException);
+
+/*
+ * A parsed command with the arguments the book's Parser API gives it: arg1 is
+ * the segment, label or function name, or the command itself for arithmetic,
+ * arg2 the index, local count or argument count
+ */
+#[pyclass(name = "Command", module = "hackvm", frozen, get_all)]
+#[derive(Debug, Clone)]
+pub struct PyCommand {
+    keyword: String,
+    arg1: Option<String>,
+    arg2: Option<u16>,
+    line: usize,
+    column: usize,
+    text: String,
+}
+
+#[pymethods]
+impl PyCommand {
+    fn __str__(&self) -> String {
+        self.text.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Command {} at {}:{}>", self.text, self.line, self.column)
+    }
+}
+
+impl From<Spanned<Command>> for PyCommand {
+    fn from(command: Spanned<Command>) -> Self {
+        let text = command.node.to_string();
+        let keyword = text.split(' ').next().unwrap_or_default().to_owned();
+        let (arg1, arg2) = match command.node {
+            Command::Push(segment, index) | Command::Pop(segment, index) => {
+                (Some(segment.to_string()), Some(index))
+            }
+            Command::Label(label) | Command::Goto(label) | Command::IfGoto(label) => {
+                (Some(label), None)
+            }
+            Command::Function(name, count) | Command::Call(name, count) => {
+                (Some(name), Some(count))
+            }
+            Command::Return => (None, None),
+            _ => (Some(keyword.clone()), None),
+        };
+
+        PyCommand {
+            keyword,
+            arg1,
+            arg2,
+            line: command.span.line,
+            column: command.span.column,
+            text,
+        }
+    }
+}
+
+/* An error or warning about a file, line and column are None for the whole file */
+#[pyclass(name = "Diagnostic", module = "hackvm", frozen, get_all)]
+#[derive(Debug, Clone)]
+pub struct PyDiagnostic {
+    filename: String,
+    severity: String,
+    code: String,
+    message: String,
+    help: Option<String>,
+    line: Option<usize>,
+    column: Option<usize>,
+    /* as the command line prints it, pointing into the source */
+    rendered: String,
+}
+
+#[pymethods]
+impl PyDiagnostic {
+    fn __str__(&self) -> String {
+        self.rendered.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<Diagnostic {}[{}]: {}>",
+            self.severity, self.code, self.message
+        )
+    }
+}
+
+impl PyDiagnostic {
+    fn new(diagnostic: Diagnostic, filename: &str, source: &str) -> Self {
+        PyDiagnostic {
+            filename: filename.to_owned(),
+            severity: diagnostic.severity.to_string(),
+            code: diagnostic.code.to_owned(),
+            rendered: diagnostic.render(filename, source),
+            line: diagnostic.span.map(|span| span.line),
+            column: diagnostic.span.map(|span| span.column),
+            message: diagnostic.message,
+            help: diagnostic.help,
+        }
+    }
+
+    fn from_error(err: &VmError, filename: &str, source: &str) -> Self {
+        PyDiagnostic::new(Diagnostic::from_error(err), filename, source)
+    }
+}
+
+fn raise(err: &VmError, filename: &str, source: &str) -> PyErr {
+    let diagnostic = PyDiagnostic::from_error(err, filename, source);
+    TranslationError::new_err((diagnostic.rendered.clone(), diagnostic))
+}
+
+/*
+ * Translates a single file, filestem names its statics. Raises
+ * TranslationError on the first error
+ */
+#[pyfunction]
+#[pyo3(signature = (source, filestem = "Main", *, bootstrap = true, opt_level = 0, strict = false, annotate = false))]
+fn translate(
+    py: Python<'_>,
+    source: &str,
+    filestem: &str,
+    bootstrap: bool,
+    opt_level: u8,
+    strict: bool,
+    annotate: bool,
+) -> PyResult<String> {
+    let options = TranslationOptions {
+        bootstrap: if bootstrap {
+            Bootstrap::Standard
+        } else {
+            Bootstrap::None
+        },
+        opt_level: OptLevel::try_from(opt_level).map_err(PyValueError::new_err)?,
+        strict,
+        annotate,
+        ..TranslationOptions::default()
+    };
+
+    // nothing here touches Python objects, so other threads can grade meanwhile
+    py.allow_threads(|| translate_str(source, filestem, &options))
+        .map_err(|err| raise(&err, &format!("{}.vm", filestem), source))
+}
+
+/* The commands of a file, raising TranslationError on the first line that doesn't parse */
+#[pyfunction]
+#[pyo3(signature = (source, filename = "Main.vm", *, strict = false))]
+fn parse_program(source: &str, filename: &str, strict: bool) -> PyResult<Vec<PyCommand>> {
+    let commands = Parser::new(source)
+        .strict(strict)
+        .parse_all()
+        .map_err(|err| raise(&err, filename, source))?;
+
+    Ok(commands.into_iter().map(PyCommand::from).collect())
+}
+
+/*
+ * Everything `hackvm check` reports for a single file: every parse error and
+ * warning, codegen errors and the analysis findings, in that order
+ */
+#[pyfunction]
+#[pyo3(signature = (source, filename = "Main.vm", *, bootstrap = true, strict = false))]
+fn diagnostics(source: &str, filename: &str, bootstrap: bool, strict: bool) -> Vec<PyDiagnostic> {
+    let options = TranslationOptions {
+        bootstrap: if bootstrap {
+            Bootstrap::Standard
+        } else {
+            Bootstrap::None
+        },
+        strict,
+        ..TranslationOptions::default()
+    };
+    let mut found = Vec::new();
+
+    let mut parser = Parser::new(source).strict(strict);
+    let mut commands = Vec::new();
+    for result in parser.by_ref() {
+        match result {
+            Ok(command) => commands.push(command),
+            Err(err) => found.push(PyDiagnostic::from_error(&err, filename, source)),
+        }
+    }
+    for warning in parser.take_warnings() {
+        let diagnostic = Diagnostic::from_warning(&warning);
+        found.push(PyDiagnostic::new(diagnostic, filename, source));
+    }
+
+    let filestem = filename.strip_suffix(".vm").unwrap_or(filename);
+    let mut translator = VMTranslator::with_writer(Discard, filestem, options.clone());
+    for command in &commands {
+        if let Err(err) = translator.write_command(command.clone()) {
+            found.push(PyDiagnostic::from_error(&err, filename, source));
+        }
+    }
+    if let Err(err) = translator.check_rom() {
+        found.push(PyDiagnostic::from_error(&err, filename, source));
+    }
+
+    let units = [Unit::new(filename, commands)];
+    for finding in analysis::analyze(&units, false, &options) {
+        let diagnostic = match finding.severity {
+            Severity::Error => Diagnostic::from_error(&finding.error),
+            Severity::Warning => Diagnostic::from_warning(&finding.error),
+        };
+        found.push(PyDiagnostic::new(diagnostic, filename, source));
+    }
+
+    found
+}
+
+#[pymodule]
+fn hackvm(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(translate, module)?)?;
+    module.add_function(wrap_pyfunction!(parse_program, module)?)?;
+    module.add_function(wrap_pyfunction!(diagnostics, module)?)?;
+    module.add_class::<PyCommand>()?;
+    module.add_class::<PyDiagnostic>()?;
+    module.add(
+        "TranslationError",
+        module.py().get_type::<TranslationError>(),
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Template {
+    /* Main.main computing a value, run by a Sys.init of its own, no OS needed */
+    Bare,
+    /* Main.main on top of the OS bundled into hackvm, which provides Sys.init */
+    WithOs,
+    /* keyboard driven loop drawing straight into the screen memory map */
+    Game,
+}
+
+pub const TEMPLATE_NAMES: [&str; 3] = ["bare", "with-os", "game"];
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let template = match s {
+            "bare" => Template::Bare,
+            "with-os" => Template::WithOs,
+            "game" => Template::Game,
+            _ => {
+                return Err(format!(
+                    "Unknown template {} (expected one of: {})",
+                    s,
+                    TEMPLATE_NAMES.join(", ")
+                ))
+            }
+        };
+
+        Ok(template)
+    }
+}
+
+impl Template {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Template::Bare => "bare",
+            Template::WithOs => "with-os",
+            Template::Game => "game",
+        }
+    }
+
+    fn files(&self, project: &str) -> Vec<(String, String)> {
+        let mut files = match self {
+            Template::Bare => vec![
+                ("Main.vm".to_owned(), BARE_MAIN.to_owned()),
+                ("Sys.vm".to_owned(), SYS.to_owned()),
+            ],
+            Template::WithOs => vec![("Main.vm".to_owned(), OS_MAIN.to_owned())],
+            Template::Game => vec![
+                ("Main.vm".to_owned(), GAME_MAIN.to_owned()),
+                ("Sys.vm".to_owned(), SYS.to_owned()),
+            ],
+        };
+
+        files.push((format!("{}.tst", project), self.test_script(project)));
+        files.push((format!("{}.cmp", project), self.compare_file().to_owned()));
+        files.push(("hackvm.toml".to_owned(), self.config(project)));
+
+        files
+    }
+
+    fn test_script(&self, project: &str) -> String {
+        let (setup, ticks, outputs) = match self {
+            Template::Bare => ("", 300, "RAM[16]%D1.6.1"),
+            Template::WithOs => ("", 20000, "RAM[16]%D1.6.1"),
+            // pretend a key is held down so the first screen word gets filled
+            Template::Game => ("set RAM[24576] 75,\n", 1000, "RAM[16384]%D0.10.0"),
+        };
+
+        format!(
+            "load {project}.asm,\n\
+            output-file {project}.out,\n\
+            compare-to {project}.cmp,\n\
+            output-list {outputs};\n\
+            {setup}\
+            repeat {ticks} {{\n\tticktock;\n}}\n\
+            output;\n",
+        )
+    }
+
+    fn compare_file(&self) -> &'static str {
+        match self {
+            Template::Bare | Template::WithOs => "|RAM[16] |\n|     15 |\n",
+            Template::Game => "|RAM[16384]|\n|        -1|\n",
+        }
+    }
+
+    fn config(&self, project: &str) -> String {
+        let mut config = format!(
+            "[project]\nname = \"{}\"\ntemplate = \"{}\"\n\n\
+            [build]\ninputs = [\".\"]\nbootstrap = true\n",
+            project,
+            self.name()
+        );
+
+        if *self == Template::WithOs {
+            config.push_str(
+                "\n[os]\nbundled = true\n\
+                # or a directory of OS .vm files, like the nand2tetris tools/OS\n\
+                # path = \"os\"\n",
+            );
+        }
+
+        config
+    }
+}
+
+pub fn create_project(root: &Path, template: Template) -> io::Result<Vec<PathBuf>> {
+    let project = root
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid project name {}", root.display()),
+            )
+        })?;
+
+    if !project
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        || project.starts_with(|c: char| c.is_ascii_digit())
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Project name {} must only contain letters, digits, '_' and '-' and not start with a digit",
+                project
+            ),
+        ));
+    }
+
+    if root.exists() && fs::read_dir(root)?.next().is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists and is not empty", root.display()),
+        ));
+    }
+
+    fs::create_dir_all(root)?;
+
+    let mut created = Vec::new();
+    for (filename, content) in template.files(project) {
+        let path = root.join(filename);
+        fs::write(&path, content)?;
+        created.push(path);
+    }
+
+    Ok(created)
+}
+
+// what the OS's Sys.init would do for bare and game, minus initializing the OS
+const SYS: &str = "\
+// The bootstrap code starts running the Sys.init function, which runs
+// Main.main and keeps the value it returns in static 0 (RAM[16]).
+function Sys.init 0
+\tcall Main.main 0
+\tpop static 0
+label END
+\tgoto END
+";
+
+const BARE_MAIN: &str = "\
+// Computes 7 + 8 and returns the result to Sys.init.
+function Main.main 0
+\tpush constant 7
+\tpush constant 8
+\tadd
+\treturn
+";
+
+const OS_MAIN: &str = "\
+// Sys.init comes with the OS bundled into hackvm, see [os] in hackvm.toml.
+// It runs Main.main once the OS is initialized.
+function Main.main 0
+\tpush constant 3
+\tpush constant 5
+\tcall Math.multiply 2
+\tpop static 0
+\tpush constant 0
+\treturn
+";
+
+const GAME_MAIN: &str = "\
+// Fills the top left corner of the screen while any key is held down
+// and clears it again once the key is released.
+function Main.main 0
+label FRAME
+\tpush constant 24576
+\tpop pointer 1
+\tpush that 0
+\tpush constant 0
+\teq
+\tif-goto CLEAR
+\tpush constant 16384
+\tpop pointer 1
+\tpush constant 0
+\tnot
+\tpop that 0
+\tgoto FRAME
+label CLEAR
+\tpush constant 16384
+\tpop pointer 1
+\tpush constant 0
+\tpop that 0
+\tgoto FRAME
+";
@@ -0,0 +1,24 @@
+use crate::memory::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+pub const BLACK: u32 = 0x000000;
+pub const WHITE: u32 = 0xffffff;
+
+/*
+ * Turns the screen memory map into one 0RGB pixel per dot, row by row. Every
+ * row is 32 words, the lowest bit of each the leftmost of its 16 dots, and a
+ * set bit is black
+ */
+pub fn render(screen: &[u16], pixels: &mut [u32]) {
+    for (word, dots) in screen
+        .iter()
+        .zip(pixels.chunks_exact_mut(16))
+        .take(SCREEN_WIDTH * SCREEN_HEIGHT / 16)
+    {
+        for (bit, pixel) in dots.iter_mut().enumerate() {
+            *pixel = match word >> bit & 1 {
+                1 => BLACK,
+                _ => WHITE,
+            };
+        }
+    }
+}
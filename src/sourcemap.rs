@@ -0,0 +1,84 @@
+use core::fmt::Write;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+
+use crate::{diagnostics::json_string, prelude::*};
+
+/* Where the code of one VM command ended up */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    pub source: String,
+    /* 1-based line of the command in source */
+    pub line: usize,
+    pub command: String,
+    /* 1-based lines of the .asm file holding its code, both ends included */
+    pub asm_lines: (usize, usize),
+    /* ROM addresses of its instructions, both ends included. None for labels */
+    pub rom: Option<(usize, usize)>,
+}
+
+/*
+ * Maps the generated assembly back to the VM commands it came from, written
+ * next to the output as Foo.asm.map:
+ *
+ * {"version":1,"file":"Foo.asm","mappings":[
+ *   {"source":"Foo.vm","line":17,"command":"push local 2","asm":[40,49],"rom":[31,40]}
+ * ]}
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap::default()
+    }
+
+    pub fn push(&mut self, mapping: Mapping) {
+        self.mappings.push(mapping);
+    }
+
+    /* the command whose code holds ROM address pc */
+    pub fn lookup_rom(&self, pc: usize) -> Option<&Mapping> {
+        self.mappings.iter().find(
+            |mapping| matches!(mapping.rom, Some((first, last)) if (first..=last).contains(&pc)),
+        )
+    }
+
+    pub fn to_json(&self, asm_file: &str) -> String {
+        let mut out = format!(
+            "{{\"version\":1,\"file\":{},\"mappings\":[",
+            json_string(asm_file)
+        );
+
+        for (idx, mapping) in self.mappings.iter().enumerate() {
+            let rom = match mapping.rom {
+                Some((first, last)) => format!("[{},{}]", first, last),
+                None => "null".to_owned(),
+            };
+            let _ = write!(
+                out,
+                "{}\n  {{\"source\":{},\"line\":{},\"command\":{},\"asm\":[{},{}],\"rom\":{}}}",
+                if idx == 0 { "" } else { "," },
+                json_string(&mapping.source),
+                mapping.line,
+                json_string(&mapping.command),
+                mapping.asm_lines.0,
+                mapping.asm_lines.1,
+                rom
+            );
+        }
+        out.push_str("\n]}\n");
+
+        out
+    }
+}
+
+/* Foo.asm -> Foo.asm.map */
+#[cfg(feature = "std")]
+pub fn map_path(asm_path: &Path) -> PathBuf {
+    let mut path = asm_path.as_os_str().to_owned();
+    path.push(".map");
+    PathBuf::from(path)
+}
@@ -0,0 +1,71 @@
+use core::fmt;
+
+/* Location of a piece of source text */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    /* 1-based line */
+    pub line: usize,
+    /* 1-based column, counted in chars */
+    pub column: usize,
+    /* byte range in the whole source */
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /* span of line_text[start..end] for a line beginning at byte line_start of the source */
+    pub fn in_line(
+        line: usize,
+        line_start: usize,
+        line_text: &str,
+        start: usize,
+        end: usize,
+    ) -> Self {
+        Span {
+            line,
+            column: line_text[..start].chars().count() + 1,
+            start: line_start + start,
+            end: line_start + end,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /* smallest span covering both */
+    pub fn to(&self, other: Span) -> Span {
+        if other.start < self.start {
+            return other.to(*self);
+        }
+
+        Span {
+            end: self.end.max(other.end),
+            ..*self
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Spanned { node, span }
+    }
+}
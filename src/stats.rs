@@ -0,0 +1,106 @@
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+use crate::{memory::STATIC_SIZE, prelude::*};
+
+/* What a translation produced, gathered by VMTranslator as it writes */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TranslationStats {
+    pub instructions: usize,
+    /* instructions of the SP setup and entry call */
+    pub bootstrap: usize,
+    /* instructions per command keyword, e.g. push or call */
+    pub by_command: BTreeMap<&'static str, usize>,
+    /* instructions per function, code outside of functions goes under its file stem */
+    pub by_function: BTreeMap<String, usize>,
+    pub by_file: BTreeMap<String, usize>,
+    pub calls: usize,
+    /* eq, lt and gt commands */
+    pub comparisons: usize,
+    /* distinct static variables */
+    pub static_slots: usize,
+}
+
+impl TranslationStats {
+    /* adds the counts of code written after this, static_slots is left to the caller */
+    pub fn merge(&mut self, other: &TranslationStats) {
+        self.instructions += other.instructions;
+        self.bootstrap += other.bootstrap;
+        for (keyword, count) in &other.by_command {
+            *self.by_command.entry(keyword).or_default() += count;
+        }
+        for (function, count) in &other.by_function {
+            *self.by_function.entry(function.clone()).or_default() += count;
+        }
+        for (file, count) in &other.by_file {
+            *self.by_file.entry(file.clone()).or_default() += count;
+        }
+        self.calls += other.calls;
+        self.comparisons += other.comparisons;
+    }
+
+    /* the n functions with the most instructions, largest first */
+    pub fn largest_functions(&self, n: usize) -> Vec<(String, usize)> {
+        largest(&self.by_function)
+            .into_iter()
+            .take(n)
+            .map(|(function, size)| (function.to_owned(), size))
+            .collect()
+    }
+}
+
+// counts sorted largest first, ties by name
+fn largest<K: AsRef<str> + Ord>(counts: &BTreeMap<K, usize>) -> Vec<(&str, usize)> {
+    let mut counts: Vec<_> = counts
+        .iter()
+        .map(|(key, count)| (key.as_ref(), *count))
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    counts
+}
+
+/*
+ * instructions            1234 (bootstrap 52)
+ * calls                     10
+ * comparisons                4
+ * static slots               3 of 240
+ *
+ * per command
+ *     push                                  600
+ *     ...
+ */
+impl fmt::Display for TranslationStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<20}{:>8} (bootstrap {})",
+            "instructions", self.instructions, self.bootstrap
+        )?;
+        writeln!(f, "{:<20}{:>8}", "calls", self.calls)?;
+        writeln!(f, "{:<20}{:>8}", "comparisons", self.comparisons)?;
+        writeln!(
+            f,
+            "{:<20}{:>8} of {}",
+            "static slots", self.static_slots, STATIC_SIZE
+        )?;
+
+        let tables = [
+            ("per command", largest(&self.by_command)),
+            ("per function", largest(&self.by_function)),
+            ("per file", largest(&self.by_file)),
+        ];
+        for (title, counts) in tables {
+            let width = counts
+                .iter()
+                .map(|(name, _)| name.len())
+                .fold(32, usize::max);
+            writeln!(f, "\n{}", title)?;
+            for (name, count) in counts {
+                writeln!(f, "    {:<width$} {:>8}", name, count)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use std::{fmt::Write as _, path::Path, str::FromStr};
+
+use toml::{Table, Value};
+
+use crate::testscript;
+
+/*
+ * What a translated program should leave in memory, from which hackvm gen-test
+ * writes a .tst script and .cmp file the official emulators accept:
+ *
+ * program = "FibonacciElement.asm"
+ * steps = 6000
+ *
+ * [set]
+ * "RAM[0]" = 256
+ *
+ * [expect]
+ * "RAM[0]" = 262
+ * "RAM[261]" = 3
+ *
+ * program is a file next to the expectation, or "." for every .vm file in its
+ * directory. .asm and .hack programs are tested on the CPU emulator and can
+ * only name RAM[n], .vm ones on the VM emulator which also knows sp, local[2]
+ * and the like. Without steps the program is run to find how many it takes
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Expectation {
+    pub program: String,
+    pub steps: Option<u64>,
+    /* in the order they're written, so the script reads like the file */
+    pub set: Vec<(String, i16)>,
+    pub expect: Vec<(String, i16)>,
+}
+
+impl Expectation {
+    /* whether the script is for the VM emulator rather than the CPU one */
+    pub fn on_vm(&self) -> bool {
+        let ext = Path::new(&self.program).extension();
+        !matches!(ext.and_then(|ext| ext.to_str()), Some("asm" | "hack"))
+    }
+
+    /* the script writing name.out and comparing it to name.cmp after steps steps */
+    pub fn script(&self, name: &str, steps: u64) -> String {
+        let mut script = String::new();
+        let load = match self.program.as_str() {
+            "." => String::new(),
+            program => format!(" {}", program),
+        };
+        let columns: Vec<String> = self
+            .expect
+            .iter()
+            .map(|(variable, _)| column(variable))
+            .collect();
+
+        let _ = writeln!(script, "// Generated by hackvm gen-test\n");
+        let _ = writeln!(script, "load{},", load);
+        let _ = writeln!(script, "output-file {}.out,", name);
+        let _ = writeln!(script, "compare-to {}.cmp,", name);
+        let _ = writeln!(script, "output-list {};\n", columns.join(" "));
+
+        for (n, (variable, value)) in self.set.iter().enumerate() {
+            let end = if n + 1 == self.set.len() { ';' } else { ',' };
+            let _ = writeln!(script, "set {} {}{}", variable, value, end);
+        }
+        if !self.set.is_empty() {
+            script.push('\n');
+        }
+
+        let step = if self.on_vm() { "vmstep" } else { "ticktock" };
+        let _ = writeln!(script, "repeat {} {{\n  {};\n}}\n", steps, step);
+        script.push_str("output;\n");
+
+        script
+    }
+
+    pub fn compare_file(&self) -> String {
+        let columns: Vec<_> = self
+            .expect
+            .iter()
+            .map(|(variable, value)| (column(variable), *value))
+            .collect();
+
+        // every name was checked when parsing, the columns can't fail
+        testscript::compare_file(&columns).unwrap_or_default()
+    }
+}
+
+impl FromStr for Expectation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let table: Table = s
+            .parse()
+            .map_err(|err: toml::de::Error| err.message().to_owned())?;
+        let mut expectation = Expectation::default();
+        let mut program = None;
+
+        for (key, value) in &table {
+            match key.as_str() {
+                "program" => {
+                    let name = value
+                        .as_str()
+                        .ok_or_else(|| "program should be a string".to_owned())?;
+                    if name != "." && Path::new(name).components().count() != 1 {
+                        return Err(format!(
+                            "program {} should be a file next to the expectation, or .",
+                            name
+                        ));
+                    }
+                    program = Some(name.to_owned());
+                }
+                "steps" => {
+                    let steps = value
+                        .as_integer()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| "steps should be a number above 0".to_owned())?;
+                    expectation.steps = Some(steps as u64);
+                }
+                "set" => expectation.set = values(key, value)?,
+                "expect" => expectation.expect = values(key, value)?,
+                _ => return Err(format!("Unknown key {}", key)),
+            }
+        }
+
+        expectation.program = program.ok_or_else(|| "Missing program".to_owned())?;
+        if expectation.expect.is_empty() {
+            return Err("[expect] should name at least one value".to_owned());
+        }
+        let on_vm = expectation.on_vm();
+        for (variable, _) in expectation.set.iter().chain(&expectation.expect) {
+            testscript::check_variable(variable, on_vm)?;
+        }
+
+        Ok(expectation)
+    }
+}
+
+// decimal, wide enough for -32768 and the whole name in the header
+fn column(variable: &str) -> String {
+    format!("{}%D1.{}.1", variable, variable.len().max(6))
+}
+
+fn values(section: &str, value: &Value) -> Result<Vec<(String, i16)>, String> {
+    let fields = value
+        .as_table()
+        .ok_or_else(|| format!("[{}] should be a table", section))?;
+
+    fields
+        .iter()
+        .map(|(key, value)| {
+            value
+                .as_integer()
+                .and_then(|n| i16::try_from(n).ok())
+                .map(|n| (key.clone(), n))
+                .ok_or_else(|| {
+                    format!(
+                        "{}.{} should be a number from -32768 to 32767",
+                        section, key
+                    )
+                })
+        })
+        .collect()
+}
@@ -0,0 +1,787 @@
+use core::{fmt, str::FromStr};
+
+use crate::{
+    coverage::Coverage,
+    emulator::Emulator,
+    interpreter::{Interpreter, State},
+    memory::{self, TEMP_BASE},
+    prelude::*,
+    VmError,
+};
+
+/*
+ * Test scripts in the format of the official nand2tetris tools:
+ *
+ *   load SimpleAdd.asm,
+ *   output-file SimpleAdd.out,
+ *   compare-to SimpleAdd.cmp,
+ *   output-list RAM[0]%D2.6.2 RAM[256]%D2.6.2;
+ *   set RAM[0] 256,
+ *   repeat 60 {
+ *     ticktock;
+ *   }
+ *   output;
+ *
+ * `load` of an .asm or .hack file runs the program on the CPU emulator, one
+ * instruction a ticktock. `load` of .vm files, a directory or nothing at all
+ * runs it on the VM interpreter, one command a vmstep. Echo and breakpoints
+ * are read and ignored
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    statements: Vec<(usize, Statement)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Statement {
+    /* None loads the directory the script is in */
+    Load(Option<String>),
+    OutputFile(String),
+    CompareTo(String),
+    OutputList(Vec<Column>),
+    Set(Variable, u16),
+    Output,
+    Ticktock,
+    VmStep,
+    /* runs forever, or until the program halts, without a count */
+    Repeat(Option<u64>, Vec<(usize, Statement)>),
+    While(Condition, Vec<(usize, Statement)>),
+    Ignored,
+}
+
+/* RAM[256]%D2.6.2: what is printed, how and with how much padding */
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Column {
+    name: String,
+    variable: Variable,
+    format: Format,
+    left: usize,
+    width: usize,
+    right: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Decimal,
+    Hex,
+    Binary,
+    String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Variable {
+    Ram(u16),
+    /* RAM[RAM[pointer] + index], local[2] and the like */
+    Indexed(u16, u16),
+    A,
+    D,
+    Pc,
+    Time,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Condition {
+    variable: Variable,
+    op: Comparison,
+    value: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/* A script error, or a runtime one at the statement that ran into it */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError {
+    /* 1-based line in the script */
+    pub line: usize,
+    pub message: String,
+}
+
+/* A loaded program with what runs it */
+pub enum Program {
+    Cpu(Emulator),
+    Vm(Box<Interpreter>),
+}
+
+/* How the script is given the files it names, relative to where it is */
+pub trait ScriptFiles {
+    /* the program load names, None for every .vm file next to the script */
+    fn load(&mut self, name: Option<&str>) -> Result<Program, String>;
+
+    fn read(&mut self, name: &str) -> Result<String, String>;
+}
+
+/* The output written so far, and the first line that didn't match the compare file */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Outcome {
+    pub output: String,
+    pub output_file: Option<String>,
+    pub compare_file: Option<String>,
+    /* lines of output compared */
+    pub compared: usize,
+    pub mismatch: Option<Mismatch>,
+    /* instructions or commands the program ran */
+    pub steps: u64,
+    /* lines of a VM program that ran, when it was loaded counting its hits */
+    pub coverage: Option<Coverage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /* 1-based line of the compare file */
+    pub line: usize,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl Script {
+    pub fn parse(source: &str) -> Result<Script, ScriptError> {
+        let tokens = tokenize(source)?;
+        let mut tokens = Tokens { tokens, pos: 0 };
+        let statements = tokens.statements(false)?;
+
+        Ok(Script { statements })
+    }
+
+    /*
+     * Runs the script, stopping at the first output line that doesn't match
+     * the compare file. The output file is left to the caller to write
+     */
+    pub fn run(&self, files: &mut impl ScriptFiles) -> Result<Outcome, ScriptError> {
+        let mut runner = Runner {
+            files,
+            program: None,
+            columns: Vec::new(),
+            compare: None,
+            outcome: Outcome::default(),
+        };
+        runner.run(&self.statements)?;
+        runner.outcome.steps = match &runner.program {
+            Some(Program::Cpu(cpu)) => cpu.steps(),
+            Some(Program::Vm(vm)) => vm.steps(),
+            None => 0,
+        };
+        if let Some(Program::Vm(vm)) = &runner.program {
+            if !vm.hits().is_empty() {
+                runner.outcome.coverage = Some(Coverage::from_interpreter(vm));
+            }
+        }
+
+        Ok(runner.outcome)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Text(String),
+    /* , ; and ! all end a statement, the official tools pause on some of them */
+    End,
+    Open,
+    Close,
+}
+
+struct Tokens {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ScriptError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|c| *c != '\n').is_some() {},
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let start = line;
+                let mut last = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if last == '*' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            last = c;
+                        }
+                        None => return Err(error(start, "Unterminated comment".to_owned())),
+                    }
+                }
+            }
+            ',' | ';' | '!' => tokens.push((line, Token::End)),
+            '{' => tokens.push((line, Token::Open)),
+            '}' => tokens.push((line, Token::Close)),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\n') | None => {
+                            return Err(error(line, "Unterminated string".to_owned()))
+                        }
+                        Some(c) => text.push(c),
+                    }
+                }
+                tokens.push((line, Token::Text(text)));
+            }
+            // comparisons stand alone even without spaces around them
+            '<' | '>' | '=' => {
+                let mut op = String::from(c);
+                if let Some(next) = chars.next_if(|next| matches!(next, '>' | '=')) {
+                    op.push(next);
+                }
+                tokens.push((line, Token::Word(op)));
+            }
+            c => {
+                let mut word = String::from(c);
+                while let Some(c) = chars.next_if(|c| {
+                    !c.is_whitespace()
+                        && !matches!(c, ',' | ';' | '!' | '{' | '}' | '"' | '<' | '>' | '=')
+                }) {
+                    word.push(c);
+                }
+                tokens.push((line, Token::Word(word)));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+impl Tokens {
+    // until the end of the script, or the } closing a block
+    fn statements(&mut self, block: bool) -> Result<Vec<(usize, Statement)>, ScriptError> {
+        let mut statements = Vec::new();
+        loop {
+            match self.next() {
+                None if block => return Err(error(self.line(), "Missing }".to_owned())),
+                None => return Ok(statements),
+                Some((_, Token::Close)) if block => return Ok(statements),
+                Some((_, Token::End)) => {}
+                Some((line, Token::Word(word))) => {
+                    let statement = self.statement(line, &word)?;
+                    statements.push((line, statement));
+                }
+                Some((line, token)) => {
+                    return Err(error(line, format!("Expected a command, found {}", token)))
+                }
+            }
+        }
+    }
+
+    fn statement(&mut self, line: usize, command: &str) -> Result<Statement, ScriptError> {
+        let statement = match command {
+            "load" => Statement::Load(self.word_if_any()),
+            "output-file" => Statement::OutputFile(self.word(line, "a file name")?),
+            "compare-to" => Statement::CompareTo(self.word(line, "a file name")?),
+            "output-list" => {
+                let mut columns = Vec::new();
+                while let Some(column) = self.word_if_any() {
+                    columns.push(column.parse().map_err(|err| error(line, err))?);
+                }
+                Statement::OutputList(columns)
+            }
+            "set" => {
+                let variable = self.word(line, "a variable")?;
+                let variable = variable.parse().map_err(|err| error(line, err))?;
+                let value =
+                    parse_value(&self.word(line, "a value")?).map_err(|err| error(line, err))?;
+                Statement::Set(variable, value)
+            }
+            "output" => Statement::Output,
+            "ticktock" | "tock" => Statement::Ticktock,
+            "tick" => Statement::Ignored,
+            "vmstep" => Statement::VmStep,
+            "repeat" => {
+                let count = match self.word_if_any() {
+                    Some(count) => Some(count.parse().map_err(|_| {
+                        error(line, format!("Expected a repeat count, found {}", count))
+                    })?),
+                    None => None,
+                };
+                self.open(line)?;
+                Statement::Repeat(count, self.statements(true)?)
+            }
+            "while" => {
+                let variable = self.word(line, "a variable")?;
+                let variable = variable.parse().map_err(|err| error(line, err))?;
+                let op = self.word(line, "a comparison")?;
+                let op = op.parse().map_err(|err| error(line, err))?;
+                let value =
+                    parse_value(&self.word(line, "a value")?).map_err(|err| error(line, err))?;
+                self.open(line)?;
+                let condition = Condition {
+                    variable,
+                    op,
+                    value,
+                };
+                Statement::While(condition, self.statements(true)?)
+            }
+            "echo" => {
+                self.next();
+                Statement::Ignored
+            }
+            "breakpoint" => {
+                self.next();
+                self.next();
+                Statement::Ignored
+            }
+            "clear-echo" | "clear-breakpoints" => Statement::Ignored,
+            _ => return Err(error(line, format!("Unknown command {}", command))),
+        };
+
+        Ok(statement)
+    }
+
+    fn next(&mut self) -> Option<(usize, Token)> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn line(&self) -> usize {
+        self.tokens.last().map_or(1, |(line, _)| *line)
+    }
+
+    fn word_if_any(&mut self) -> Option<String> {
+        match self.tokens.get(self.pos) {
+            Some((_, Token::Word(word))) => {
+                self.pos += 1;
+                Some(word.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn word(&mut self, line: usize, expected: &str) -> Result<String, ScriptError> {
+        self.word_if_any()
+            .ok_or_else(|| error(line, format!("Expected {}", expected)))
+    }
+
+    fn open(&mut self, line: usize) -> Result<(), ScriptError> {
+        match self.next() {
+            Some((_, Token::Open)) => Ok(()),
+            _ => Err(error(line, "Expected {".to_owned())),
+        }
+    }
+}
+
+// 256, -1, %X1F, %B101 or %D-1
+fn parse_value(s: &str) -> Result<u16, String> {
+    let invalid = || format!("Invalid value {}", s);
+    let (radix, digits) = match s.strip_prefix('%') {
+        Some(rest) if rest.starts_with('X') => (16, &rest[1..]),
+        Some(rest) if rest.starts_with('B') => (2, &rest[1..]),
+        Some(rest) if rest.starts_with('D') => (10, &rest[1..]),
+        Some(_) => return Err(invalid()),
+        None => (10, s),
+    };
+    let value = i32::from_str_radix(digits, radix).map_err(|_| invalid())?;
+    if !(i32::from(i16::MIN)..=i32::from(u16::MAX)).contains(&value) {
+        return Err(format!("{} doesn't fit in 16 bits", s));
+    }
+
+    Ok(value as u16)
+}
+
+impl FromStr for Variable {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || format!("Unknown variable {}", s);
+        let (name, index) = match s.split_once('[') {
+            Some((name, index)) => {
+                let index = index.strip_suffix(']').ok_or_else(unknown)?;
+                let index: u16 = index.parse().map_err(|_| unknown())?;
+                (name, Some(index))
+            }
+            None => (s, None),
+        };
+
+        let variable = match (name, index) {
+            ("RAM", Some(address)) if usize::from(address) < memory::RAM_SIZE => {
+                Variable::Ram(address)
+            }
+            ("temp", Some(index)) if index < memory::TEMP_SIZE => Variable::Ram(TEMP_BASE + index),
+            ("local", Some(index)) => Variable::Indexed(memory::LCL, index),
+            ("argument", Some(index)) => Variable::Indexed(memory::ARG, index),
+            ("this", Some(index)) => Variable::Indexed(memory::THIS, index),
+            ("that", Some(index)) => Variable::Indexed(memory::THAT, index),
+            ("sp", None) => Variable::Ram(memory::SP),
+            ("local", None) => Variable::Ram(memory::LCL),
+            ("argument", None) => Variable::Ram(memory::ARG),
+            ("this", None) => Variable::Ram(memory::THIS),
+            ("that", None) => Variable::Ram(memory::THAT),
+            ("A", None) => Variable::A,
+            ("D", None) => Variable::D,
+            ("PC", None) => Variable::Pc,
+            ("time", None) => Variable::Time,
+            _ => return Err(unknown()),
+        };
+
+        Ok(variable)
+    }
+}
+
+// the format defaults to %B1.16.1 like in the official tools
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, format) = s.split_once('%').unwrap_or((s, "B1.16.1"));
+        let invalid = || format!("Invalid output format {}", s);
+
+        let mut chars = format.chars();
+        let kind = match chars.next() {
+            Some('D') => Format::Decimal,
+            Some('X') => Format::Hex,
+            Some('B') => Format::Binary,
+            Some('S') => Format::String,
+            _ => return Err(invalid()),
+        };
+        let padding: Vec<usize> = chars
+            .as_str()
+            .split('.')
+            .map(|n| n.parse().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        let [left, width, right] = padding[..] else {
+            return Err(invalid());
+        };
+
+        Ok(Column {
+            name: name.to_owned(),
+            variable: name.parse()?,
+            format: kind,
+            left,
+            width,
+            right,
+        })
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "=" => Ok(Comparison::Eq),
+            "<>" => Ok(Comparison::Ne),
+            "<" => Ok(Comparison::Lt),
+            "<=" => Ok(Comparison::Le),
+            ">" => Ok(Comparison::Gt),
+            ">=" => Ok(Comparison::Ge),
+            _ => Err(format!("Unknown comparison {}", s)),
+        }
+    }
+}
+
+struct Runner<'a, F> {
+    files: &'a mut F,
+    program: Option<Program>,
+    columns: Vec<Column>,
+    /* lines of the compare file */
+    compare: Option<Vec<String>>,
+    outcome: Outcome,
+}
+
+impl<F: ScriptFiles> Runner<'_, F> {
+    fn run(&mut self, statements: &[(usize, Statement)]) -> Result<(), ScriptError> {
+        for (line, statement) in statements {
+            if self.outcome.mismatch.is_some() {
+                break;
+            }
+            self.execute(*line, statement)?;
+        }
+
+        Ok(())
+    }
+
+    // errors in a block are reported at the statement inside it, not the loop
+    fn execute(&mut self, line: usize, statement: &Statement) -> Result<(), ScriptError> {
+        let at = |message: String| error(line, message);
+        match statement {
+            Statement::Load(name) => {
+                self.program = Some(self.files.load(name.as_deref()).map_err(at)?)
+            }
+            Statement::OutputFile(name) => self.outcome.output_file = Some(name.clone()),
+            Statement::CompareTo(name) => {
+                let text = self.files.read(name).map_err(at)?;
+                self.compare = Some(text.lines().map(str::to_owned).collect());
+                self.outcome.compare_file = Some(name.clone());
+            }
+            Statement::OutputList(columns) => {
+                self.columns = columns.clone();
+                let header = self.columns.iter().map(Column::header).collect();
+                self.write(header);
+            }
+            Statement::Set(variable, value) => self.set(*variable, *value).map_err(at)?,
+            Statement::Output => {
+                let mut cells = Vec::with_capacity(self.columns.len());
+                for column in &self.columns {
+                    cells.push(column.cell(self.get(column.variable).map_err(at)?));
+                }
+                self.write(cells);
+            }
+            Statement::Ticktock => match self.program_mut().map_err(at)? {
+                Program::Cpu(cpu) => {
+                    cpu.step().map_err(|err| at(runtime(err, cpu.steps())))?;
+                }
+                Program::Vm(_) => {
+                    return Err(at(
+                        "ticktock needs an .asm or .hack file loaded, vmstep runs .vm files"
+                            .to_owned(),
+                    ))
+                }
+            },
+            Statement::VmStep => match self.program_mut().map_err(at)? {
+                Program::Vm(vm) => {
+                    vm.step().map_err(|err| at(runtime(err, vm.steps())))?;
+                }
+                Program::Cpu(_) => {
+                    return Err(at(
+                        "vmstep needs .vm files loaded, ticktock runs .asm and .hack files"
+                            .to_owned(),
+                    ))
+                }
+            },
+            Statement::Repeat(Some(count), body) => {
+                // stepping a halted program does nothing, however many times
+                let steps = body.iter().all(|(_, statement)| {
+                    matches!(statement, Statement::Ticktock | Statement::VmStep)
+                });
+                for _ in 0..*count {
+                    if steps && self.halted() {
+                        break;
+                    }
+                    self.run(body)?;
+                }
+            }
+            Statement::Repeat(None, body) => {
+                while !self.halted() && self.outcome.mismatch.is_none() {
+                    self.run(body)?;
+                }
+            }
+            // a halted program won't change its mind, the loop ends with it
+            Statement::While(condition, body) => {
+                while self.holds(condition).map_err(at)?
+                    && !self.halted()
+                    && self.outcome.mismatch.is_none()
+                {
+                    self.run(body)?;
+                }
+            }
+            Statement::Ignored => {}
+        }
+
+        Ok(())
+    }
+
+    fn program_mut(&mut self) -> Result<&mut Program, String> {
+        self.program
+            .as_mut()
+            .ok_or_else(|| "Nothing is loaded yet".to_owned())
+    }
+
+    fn halted(&self) -> bool {
+        match &self.program {
+            Some(Program::Cpu(cpu)) => cpu.state() == State::Halted,
+            Some(Program::Vm(vm)) => vm.state() == State::Halted,
+            None => true,
+        }
+    }
+
+    fn holds(&self, condition: &Condition) -> Result<bool, String> {
+        let value = self.get(condition.variable)? as i16;
+        let expected = condition.value as i16;
+
+        Ok(match condition.op {
+            Comparison::Eq => value == expected,
+            Comparison::Ne => value != expected,
+            Comparison::Lt => value < expected,
+            Comparison::Le => value <= expected,
+            Comparison::Gt => value > expected,
+            Comparison::Ge => value >= expected,
+        })
+    }
+
+    fn get(&self, variable: Variable) -> Result<u16, String> {
+        let program = self
+            .program
+            .as_ref()
+            .ok_or_else(|| "Nothing is loaded yet".to_owned())?;
+        let ram = |address: u16| match program {
+            Program::Cpu(cpu) => cpu.ram(address),
+            Program::Vm(vm) => vm.ram(address),
+        };
+
+        match (variable, program) {
+            (Variable::Ram(address), _) => Ok(ram(address)),
+            (Variable::Indexed(pointer, index), _) => Ok(ram(ram(pointer).wrapping_add(index))),
+            (Variable::A, Program::Cpu(cpu)) => Ok(cpu.a()),
+            (Variable::D, Program::Cpu(cpu)) => Ok(cpu.d()),
+            (Variable::Pc, Program::Cpu(cpu)) => Ok(cpu.pc() as u16),
+            (Variable::Time, Program::Cpu(cpu)) => Ok(cpu.steps() as u16),
+            (Variable::Time, Program::Vm(vm)) => Ok(vm.steps() as u16),
+            (_, Program::Vm(_)) => {
+                Err("A, D and PC are only there with an .asm or .hack file loaded".to_owned())
+            }
+        }
+    }
+
+    fn set(&mut self, variable: Variable, value: u16) -> Result<(), String> {
+        let address = match variable {
+            Variable::Ram(address) => address,
+            Variable::Indexed(pointer, index) => {
+                self.get(Variable::Ram(pointer))?.wrapping_add(index)
+            }
+            _ => return Err("Only RAM and the VM segments can be set".to_owned()),
+        };
+        match self.program_mut()? {
+            Program::Cpu(cpu) => cpu.set_ram(address, value),
+            Program::Vm(vm) => vm.set_ram(address, value),
+        }
+
+        Ok(())
+    }
+
+    // one line of |cells|, checked against the compare file as it's written
+    fn write(&mut self, cells: Vec<String>) {
+        let mut line = String::from("|");
+        for cell in cells {
+            line.push_str(&cell);
+            line.push('|');
+        }
+        self.outcome.output.push_str(&line);
+        self.outcome.output.push('\n');
+
+        let Some(compare) = &self.compare else {
+            return;
+        };
+        let n = self.outcome.compared;
+        let expected = compare.get(n).map_or("", String::as_str);
+        if !same_cells(expected, &line) {
+            self.outcome.mismatch = Some(Mismatch {
+                line: n + 1,
+                expected: expected.to_owned(),
+                actual: line,
+            });
+        }
+        self.outcome.compared += 1;
+    }
+}
+
+/* Checks name is a variable a script for the CPU emulator, or the VM one, can use */
+pub fn check_variable(name: &str, on_vm: bool) -> Result<(), String> {
+    match name.parse()? {
+        Variable::Ram(_) | Variable::Indexed(..) if on_vm => Ok(()),
+        Variable::Ram(_) if name.starts_with("RAM[") => Ok(()),
+        _ if on_vm => Err(format!("{} is not in RAM or a VM segment", name)),
+        // sp, local and the rest only mean something to the VM emulator
+        _ => Err(format!("{} is not a RAM[n] address", name)),
+    }
+}
+
+/*
+ * The compare file for an output-list, as RAM[0]%D1.6.1 and the like: the
+ * header and a line with the values
+ */
+pub fn compare_file(columns: &[(String, i16)]) -> Result<String, String> {
+    let mut header = String::from("|");
+    let mut line = String::from("|");
+    for (column, value) in columns {
+        let column: Column = column.parse()?;
+        header.push_str(&column.header());
+        header.push('|');
+        line.push_str(&column.cell(*value as u16));
+        line.push('|');
+    }
+
+    Ok(format!("{}\n{}\n", header, line))
+}
+
+impl Column {
+    // the name centered over the cell, cut short if it doesn't fit
+    fn header(&self) -> String {
+        let width = self.left + self.width + self.right;
+        let name: String = self.name.chars().take(width).collect();
+        let space = width - name.chars().count();
+        let before = space / 2;
+        format!(
+            "{}{}{}",
+            " ".repeat(before),
+            name,
+            " ".repeat(space - before)
+        )
+    }
+
+    fn cell(&self, value: u16) -> String {
+        let text = match self.format {
+            Format::Decimal | Format::String => format!("{}", value as i16),
+            Format::Hex => format!("{:04X}", value),
+            // the low bits if the column is too narrow for all 16
+            Format::Binary => {
+                let bits = format!("{:016b}", value);
+                bits[16 - self.width.min(16)..].to_owned()
+            }
+        };
+        format!(
+            "{}{:>width$}{}",
+            " ".repeat(self.left),
+            text,
+            " ".repeat(self.right),
+            width = self.width
+        )
+    }
+}
+
+// spacing differs between the tools, what's in the cells has to be the same
+fn same_cells(expected: &str, actual: &str) -> bool {
+    let cells = |line: &str| {
+        line.trim()
+            .trim_matches('|')
+            .split('|')
+            .map(|cell| cell.trim().to_owned())
+            .collect::<Vec<_>>()
+    };
+    cells(expected) == cells(actual)
+}
+
+fn runtime(err: VmError, steps: u64) -> String {
+    format!("{} after {} steps", err, steps)
+}
+
+fn error(line: usize, message: String) -> ScriptError {
+    ScriptError { line, message }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{}", word),
+            Token::Text(text) => write!(f, "\"{}\"", text),
+            Token::End => write!(f, "end of statement"),
+            Token::Open => write!(f, "{{"),
+            Token::Close => write!(f, "}}"),
+        }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tst line {}: {}", self.line, self.message)
+    }
+}
+
+impl core::error::Error for ScriptError {}
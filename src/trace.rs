@@ -0,0 +1,57 @@
+use core::fmt::Write as _;
+
+use crate::{diagnostics::json_string, emulator::Emulator, interpreter::Interpreter, prelude::*};
+
+/*
+ * A step of a run as a line of JSON, for tools reading a trace back or
+ * diffing it against another emulator's. step counts from the start of the
+ * run, pc is where the step ran, sp and the values of the addresses it wrote
+ * are what it left. VM steps carry their source, CPU steps the registers:
+ *
+ *   {"step":1,"pc":4,"sp":257,"writes":[[256,7],[0,257]],"file":"Main","line":3,"command":"push constant 7"}
+ *   {"step":1,"pc":4,"sp":256,"writes":[],"a":7,"d":0}
+ */
+pub fn vm_step(vm: &Interpreter, pc: usize) -> String {
+    let mut line = step(vm.steps(), pc, vm.sp(), vm.last_writes(), |address| {
+        vm.ram(address)
+    });
+    if let Some((filestem, command)) = vm.command_at(pc) {
+        let _ = write!(
+            line,
+            ",\"file\":{},\"line\":{},\"command\":{}",
+            json_string(filestem),
+            command.span.line,
+            json_string(&command.node.to_string())
+        );
+    }
+    line.push('}');
+
+    line
+}
+
+pub fn cpu_step(cpu: &Emulator, pc: usize) -> String {
+    let writes: Vec<u16> = cpu.last_write().into_iter().collect();
+    let mut line = step(cpu.steps(), pc, cpu.sp(), &writes, |address| {
+        cpu.ram(address)
+    });
+    let _ = write!(line, ",\"a\":{},\"d\":{}}}", cpu.a(), cpu.d());
+
+    line
+}
+
+// the fields both share, left open for the rest
+fn step(steps: u64, pc: usize, sp: u16, writes: &[u16], ram: impl Fn(u16) -> u16) -> String {
+    let mut line = format!(
+        "{{\"step\":{},\"pc\":{},\"sp\":{},\"writes\":[",
+        steps, pc, sp
+    );
+    for (n, address) in writes.iter().enumerate() {
+        if n > 0 {
+            line.push(',');
+        }
+        let _ = write!(line, "[{},{}]", address, ram(*address));
+    }
+    line.push(']');
+
+    line
+}
@@ -0,0 +1,206 @@
+use std::{collections::HashMap, fmt};
+
+use crate::memory::ROM_SIZE;
+
+const PREDEFINED_SYMBOLS: [&str; 23] = [
+    "SP", "LCL", "ARG", "THIS", "THAT", "R0", "R1", "R2", "R3", "R4", "R5", "R6", "R7", "R8", "R9",
+    "R10", "R11", "R12", "R13", "R14", "R15", "SCREEN", "KBD",
+];
+
+const COMPS: [&str; 28] = [
+    "0", "1", "-1", "D", "A", "!D", "!A", "-D", "-A", "D+1", "A+1", "D-1", "A-1", "D+A", "D-A",
+    "A-D", "D&A", "D|A", "M", "!M", "-M", "M+1", "M-1", "D+M", "D-M", "M-D", "D&M", "D|M",
+];
+
+const JUMPS: [&str; 7] = ["JGT", "JEQ", "JGE", "JLT", "JNE", "JLE", "JMP"];
+
+#[derive(Debug)]
+pub struct VerifyError {
+    /* 1-based line in the assembly file, 0 for whole program errors */
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct AsmSummary {
+    pub instructions: usize,
+    pub labels: usize,
+    pub variables: usize,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "asm line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+/*
+ * Re-parses emitted assembly and checks that
+ * - every instruction is well formed
+ * - every label is defined exactly once
+ * - every referenced symbol is a label, a predefined symbol or a static variable
+ * - the program fits in the ROM
+ */
+pub fn verify_asm(asm: &str) -> Result<AsmSummary, Vec<VerifyError>> {
+    let mut errors = Vec::new();
+    let mut summary = AsmSummary::default();
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut references: Vec<(&str, usize)> = Vec::new();
+
+    for (n, line) in asm.lines().enumerate() {
+        let n = n + 1;
+        let line = match line.find("//") {
+            Some(idx) => &line[..idx],
+            None => line,
+        }
+        .trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(label) = line.strip_prefix('(') {
+            let Some(label) = label.strip_suffix(')') else {
+                errors.push(error(n, format!("Unterminated label declaration {}", line)));
+                continue;
+            };
+
+            if !is_symbol(label) {
+                errors.push(error(n, format!("Invalid label name {}", label)));
+            } else if PREDEFINED_SYMBOLS.contains(&label) {
+                errors.push(error(
+                    n,
+                    format!("Label {} shadows a predefined symbol", label),
+                ));
+            } else if let Some(first) = labels.get(label) {
+                errors.push(error(
+                    n,
+                    format!("Label {} already defined at line {}", label, first),
+                ));
+            } else {
+                labels.insert(label, n);
+            }
+        } else if let Some(value) = line.strip_prefix('@') {
+            summary.instructions += 1;
+            if value.starts_with(|c: char| c.is_ascii_digit()) {
+                if !matches!(value.parse::<u16>(), Ok(addr) if addr < 0x8000) {
+                    errors.push(error(
+                        n,
+                        format!("Invalid A-instruction constant {}", value),
+                    ));
+                }
+            } else if is_symbol(value) {
+                references.push((value, n));
+            } else {
+                errors.push(error(n, format!("Invalid symbol {}", value)));
+            }
+        } else {
+            summary.instructions += 1;
+            if let Err(message) = verify_c_instr(line) {
+                errors.push(error(n, message));
+            }
+        }
+    }
+
+    let mut variables = Vec::new();
+    for (symbol, n) in references {
+        if labels.contains_key(symbol) || PREDEFINED_SYMBOLS.contains(&symbol) {
+            continue;
+        }
+
+        if is_static_symbol(symbol) {
+            if !variables.contains(&symbol) {
+                variables.push(symbol);
+            }
+        } else {
+            errors.push(error(
+                n,
+                format!("Referenced symbol {} is never defined", symbol),
+            ));
+        }
+    }
+
+    if summary.instructions > ROM_SIZE {
+        errors.push(error(
+            0,
+            format!(
+                "Program has {} instructions but the ROM only holds {}",
+                summary.instructions, ROM_SIZE
+            ),
+        ));
+    }
+
+    if errors.is_empty() {
+        summary.labels = labels.len();
+        summary.variables = variables.len();
+        Ok(summary)
+    } else {
+        Err(errors)
+    }
+}
+
+fn verify_c_instr(instr: &str) -> Result<(), String> {
+    let (dest, rest) = match instr.split_once('=') {
+        Some((dest, rest)) => (Some(dest), rest),
+        None => (None, instr),
+    };
+    let (comp, jump) = match rest.split_once(';') {
+        Some((comp, jump)) => (comp, Some(jump)),
+        None => (rest, None),
+    };
+
+    if let Some(dest) = dest {
+        let mut seen = String::new();
+        for reg in dest.chars() {
+            if !"ADM".contains(reg) || seen.contains(reg) {
+                return Err(format!("Invalid destination {} in {}", dest, instr));
+            }
+            seen.push(reg);
+        }
+        if seen.is_empty() {
+            return Err(format!("Empty destination in {}", instr));
+        }
+    }
+
+    if !COMPS.contains(&comp) {
+        return Err(format!("Invalid computation {} in {}", comp, instr));
+    }
+
+    if let Some(jump) = jump {
+        if !JUMPS.contains(&jump) {
+            return Err(format!("Invalid jump {} in {}", jump, instr));
+        }
+    }
+
+    if dest.is_none() && jump.is_none() {
+        return Err(format!("Instruction {} has no effect", instr));
+    }
+
+    Ok(())
+}
+
+fn is_symbol(symbol: &str) -> bool {
+    !symbol.is_empty()
+        && !symbol.starts_with(|c: char| c.is_ascii_digit())
+        && symbol
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.$:".contains(c))
+}
+
+// <filestem>.<index>, which the assembler allocates, in assembly not written by translate
+fn is_static_symbol(symbol: &str) -> bool {
+    match symbol.rsplit_once('.') {
+        Some((stem, idx)) => {
+            !stem.is_empty() && !idx.is_empty() && idx.chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+fn error(line: usize, message: String) -> VerifyError {
+    VerifyError { line, message }
+}
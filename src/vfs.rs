@@ -0,0 +1,216 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Cursor},
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+
+/* smaller files are read faster than they are mapped */
+#[cfg(feature = "mmap")]
+const MMAP_THRESHOLD: u64 = 1024 * 1024;
+
+/*
+ * Source of the .vm inputs, so translation can run over unsaved editor buffers
+ * or synthetic file trees as well as the real file system
+ */
+pub trait FileProvider {
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+    /* buffered reader over the file, for streaming large sources */
+    fn open(&self, path: &Path) -> io::Result<Box<dyn BufRead + '_>> {
+        Ok(Box::new(Cursor::new(self.read_to_string(path)?)))
+    }
+    /* entries directly inside the directory, sorted by path */
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+    fn is_file(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RealFs;
+
+impl FileProvider for RealFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    #[cfg(not(feature = "mmap"))]
+    fn open(&self, path: &Path) -> io::Result<Box<dyn BufRead + '_>> {
+        Ok(Box::new(BufReader::new(File::open(path)?)))
+    }
+
+    // large files are mapped, the parser then reads lines straight out of the page cache
+    #[cfg(feature = "mmap")]
+    fn open(&self, path: &Path) -> io::Result<Box<dyn BufRead + '_>> {
+        let file = File::open(path)?;
+        if file.metadata()?.len() < MMAP_THRESHOLD {
+            return Ok(Box::new(BufReader::new(file)));
+        }
+        // SAFETY: the map is only read. Like any mapped reader, a file truncated by
+        // another process meanwhile faults instead of failing with an error
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Box::new(Cursor::new(map)))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort();
+
+        Ok(entries)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MemoryFs {
+    files: BTreeMap<PathBuf, String>,
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        MemoryFs::default()
+    }
+
+    pub fn insert(&mut self, path: impl Into<PathBuf>, content: impl Into<String>) {
+        self.files.insert(path.into(), content.into());
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Option<String> {
+        self.files.remove(path)
+    }
+}
+
+impl FileProvider for MemoryFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.files.get(path).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} not found", path.display()),
+            )
+        })
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        if !self.is_dir(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not a directory", path.display()),
+            ));
+        }
+
+        let mut entries: Vec<PathBuf> = self
+            .files
+            .keys()
+            .filter_map(|file| {
+                let rest = file.strip_prefix(path).ok()?;
+                let first = rest.components().next()?;
+                Some(path.join(first))
+            })
+            .collect();
+        entries.dedup();
+
+        Ok(entries)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.files
+            .keys()
+            .any(|file| file != path && file.starts_with(path))
+    }
+}
+
+/* Files in memory in front of the real file system, like the bundled OS next to a project */
+#[derive(Debug, Default, Clone)]
+pub struct Overlay {
+    files: MemoryFs,
+}
+
+impl Overlay {
+    pub fn new(files: MemoryFs) -> Self {
+        Overlay { files }
+    }
+}
+
+impl FileProvider for Overlay {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        match self.files.is_file(path) {
+            true => self.files.read_to_string(path),
+            false => RealFs.read_to_string(path),
+        }
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn BufRead + '_>> {
+        match self.files.is_file(path) {
+            true => self.files.open(path),
+            false => RealFs.open(path),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        match self.files.is_dir(path) {
+            true => self.files.read_dir(path),
+            false => RealFs.read_dir(path),
+        }
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.is_file(path) || RealFs.is_file(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.files.is_dir(path) || RealFs.is_dir(path)
+    }
+}
+
+/* The .vm files to translate for a path: the file itself or every .vm file directly inside a directory */
+pub fn vm_inputs(provider: &dyn FileProvider, inpath: &Path) -> io::Result<Vec<PathBuf>> {
+    if provider.is_file(inpath) {
+        if !is_vm_file(inpath) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Expected .vm file, got {}", inpath.display()),
+            ));
+        }
+
+        Ok(vec![inpath.to_path_buf()])
+    } else if provider.is_dir(inpath) {
+        let infiles: Vec<_> = provider
+            .read_dir(inpath)?
+            .into_iter()
+            .filter(|path| is_vm_file(path) && provider.is_file(path))
+            .collect();
+
+        if infiles.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No .vm files found in {}", inpath.display()),
+            ));
+        }
+
+        Ok(infiles)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Expected a file or directory, got {}", inpath.display()),
+        ))
+    }
+}
+
+fn is_vm_file(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some("vm")
+}
@@ -0,0 +1,137 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    diagnostics::Diagnostic, prelude::*, translate_str, Bootstrap, OptLevel, TranslationOptions,
+};
+
+/*
+ * The options a browser can set, with the defaults of the command line. From
+ * JavaScript:
+ *
+ *   const options = new Options();
+ *   options.bootstrap = false;
+ *   options.opt_level = 1;
+ *   const result = translate(source, "Main", options);
+ */
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Options {
+    /* SP = 256 and a call to Sys.init */
+    pub bootstrap: bool,
+    pub annotate: bool,
+    /* 0, 1 or 2, like -O<level> */
+    pub opt_level: u8,
+    pub strict: bool,
+    pub shared_comparisons: bool,
+    pub compact_calls: bool,
+    pub zero_locals: bool,
+    #[wasm_bindgen(getter_with_clone)]
+    pub label_prefix: String,
+}
+
+#[wasm_bindgen]
+impl Options {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Options::default()
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        let defaults = TranslationOptions::default();
+        Options {
+            bootstrap: true,
+            annotate: defaults.annotate,
+            opt_level: defaults.opt_level as u8,
+            strict: defaults.strict,
+            shared_comparisons: defaults.shared_comparisons,
+            compact_calls: defaults.compact_calls,
+            zero_locals: defaults.zero_locals,
+            label_prefix: defaults.label_prefix,
+        }
+    }
+}
+
+impl From<&Options> for TranslationOptions {
+    fn from(options: &Options) -> Self {
+        TranslationOptions {
+            bootstrap: if options.bootstrap {
+                Bootstrap::Standard
+            } else {
+                Bootstrap::None
+            },
+            annotate: options.annotate,
+            // levels past 2 get everything there is, like -O3 would elsewhere
+            opt_level: match options.opt_level {
+                0 => OptLevel::None,
+                1 => OptLevel::Safe,
+                _ => OptLevel::Aggressive,
+            },
+            strict: options.strict,
+            shared_comparisons: options.shared_comparisons,
+            compact_calls: options.compact_calls,
+            zero_locals: options.zero_locals,
+            label_prefix: options.label_prefix.clone(),
+            ..TranslationOptions::default()
+        }
+    }
+}
+
+/* The assembly, or the diagnostics saying why there is none */
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Translation {
+    filename: String,
+    source: String,
+    asm: Option<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[wasm_bindgen]
+impl Translation {
+    #[wasm_bindgen(getter)]
+    pub fn ok(&self) -> bool {
+        self.asm.is_some()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn asm(&self) -> Option<String> {
+        self.asm.clone()
+    }
+
+    /* a JSON array of objects in the format of --diagnostics json */
+    #[wasm_bindgen(getter)]
+    pub fn diagnostics(&self) -> String {
+        let diagnostics: Vec<String> = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_json(&self.filename))
+            .collect();
+        format!("[{}]", diagnostics.join(","))
+    }
+
+    /* the diagnostics as the command line prints them, pointing into the source */
+    pub fn rendered(&self) -> String {
+        self.diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.render(&self.filename, &self.source))
+            .collect()
+    }
+}
+
+/* translate_str for JavaScript, filestem names the statics and the file in diagnostics */
+#[wasm_bindgen]
+pub fn translate(source: &str, filestem: &str, options: &Options) -> Translation {
+    let filename = format!("{}.vm", filestem);
+    let (asm, diagnostics) = match translate_str(source, filestem, &options.into()) {
+        Ok(asm) => (Some(asm), Vec::new()),
+        Err(err) => (None, vec![Diagnostic::from_error(&err)]),
+    };
+    Translation {
+        filename,
+        source: source.to_owned(),
+        asm,
+        diagnostics,
+    }
+}
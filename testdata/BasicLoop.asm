@@ -1,9 +1,3 @@
-@256
-D=A
-@SP
-M=D
-
-
 @0
 D=A
 @SP
@@ -14,17 +8,17 @@ M=M+1
 
 @LCL
 D=M
-@13
+@R13
 M=D
 @0
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
@@ -65,17 +59,17 @@ M=M+1
 
 @LCL
 D=M
-@13
+@R13
 M=D
 @0
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
@@ -111,17 +105,17 @@ M=M+1
 
 @ARG
 D=M
-@13
+@R13
 M=D
 @0
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
@@ -142,6 +136,7 @@ A=M
 D=M
 @LOOP
 D;JNE
+
 @0
 D=A
 @LCL
@@ -8,17 +8,17 @@ M=M+1
 
 @LCL
 D=M
-@13
+@R13
 M=D
 @0
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
@@ -40,33 +40,33 @@ M=M+1
 
 @ARG
 D=M
-@13
+@R13
 M=D
 @2
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
 @ARG
 D=M
-@13
+@R13
 M=D
 @1
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
@@ -80,17 +80,17 @@ M=M+1
 
 @THIS
 D=M
-@13
+@R13
 M=D
 @6
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
@@ -112,33 +112,33 @@ M=M+1
 
 @THAT
 D=M
-@13
+@R13
 M=D
 @5
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
 @THAT
 D=M
-@13
+@R13
 M=D
 @2
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
@@ -0,0 +1,98 @@
+@256
+D=A
+@SP
+M=D
+
+@Sys.init$ret.0
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@LCL
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@ARG
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@THIS
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@THAT
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@SP
+D=M
+@LCL
+M=D
+@SP
+D=M
+@0
+D=D-A
+@5
+D=D-A
+@ARG
+M=D
+@Sys.init
+0;JMP
+(Sys.init$ret.0)
+
+@7
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@8
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@SP
+M=M-1
+A=M
+D=M
+@SP
+M=M-1
+A=M
+M=D+M
+@SP
+M=M+1
+
+@SP
+M=M-1
+A=M
+D=M
+@5
+M=D
+
+@5
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
@@ -0,0 +1,226 @@
+@9
+D=A
+@LCL
+A=M
+M=D
+
+@6
+D=A
+
+@SP
+A=M
+M=D
+@SP
+A=M
+M=!M
+
+@SP
+M=M+1
+@LCL
+D=M
+@R13
+M=D
+@1
+D=A
+@R13
+M=D+M
+@SP
+M=M-1
+A=M
+D=M
+@R13
+A=M
+M=D
+
+@7
+D=A
+
+@SP
+A=M
+M=D
+@SP
+A=M
+M=-M
+
+@SP
+M=M+1
+@LCL
+D=M
+@R13
+M=D
+@2
+D=A
+@R13
+M=D+M
+@SP
+M=M-1
+A=M
+D=M
+@R13
+A=M
+M=D
+
+@12
+D=A
+
+@SP
+A=M
+M=D
+@SP
+A=M
+M=!M
+
+@SP
+M=M+1
+@LCL
+D=M
+@R13
+M=D
+@3
+D=A
+@R13
+M=D+M
+@SP
+M=M-1
+A=M
+D=M
+@R13
+A=M
+M=D
+
+@9
+D=A
+@R13
+M=D
+@4
+D=A
+@LCL
+D=D+M
+@R14
+M=D
+@R13
+D=M
+@R14
+A=M
+M=D
+
+@32767
+D=A
+
+@SP
+A=M
+M=D
+@SP
+A=M
+M=!M
+
+@SP
+M=M+1
+@LCL
+D=M
+@R13
+M=D
+@5
+D=A
+@R13
+M=D+M
+@SP
+M=M-1
+A=M
+D=M
+@R13
+A=M
+M=D
+
+@25535
+D=A
+
+@SP
+A=M
+M=D
+@SP
+A=M
+M=!M
+
+@SP
+M=M+1
+@LCL
+D=M
+@R13
+M=D
+@6
+D=A
+@R13
+M=D+M
+@SP
+M=M-1
+A=M
+D=M
+@R13
+A=M
+M=D
+
+D=0
+
+@SP
+A=M
+M=D
+@SP
+A=M
+M=!M
+
+@SP
+M=M+1
+@LCL
+D=M
+@R13
+M=D
+@7
+D=A
+@R13
+M=D+M
+@SP
+M=M-1
+A=M
+D=M
+@R13
+A=M
+M=D
+
+@0
+D=A
+@LCL
+A=D+M
+D=M
+
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@3
+D=A
+
+@SP
+M=M-1
+A=M
+M=D+M
+
+@SP
+M=M+1
+@LCL
+D=M
+@R13
+M=D
+@8
+D=A
+@R13
+M=D+M
+@SP
+M=M-1
+A=M
+D=M
+@R13
+A=M
+M=D
+
@@ -3,7 +3,6 @@ D=A
 @SP
 M=D
 
-
 @Sys.init$ret.0
 D=A
 @SP
@@ -98,17 +97,18 @@ M=-1
 (JUMP_END_0)
 @SP
 M=M+1
+
 @SP
 M=M-1
 A=M
 D=M
-@N_LT_2
+@Main.fibonacci$N_LT_2
 D;JNE
 
-@N_GE_2
+@Main.fibonacci$N_GE_2
 0;JMP
 
-(N_LT_2)
+(Main.fibonacci$N_LT_2)
 
 @0
 D=A
@@ -178,7 +178,7 @@ M=D
 A=M
 0;JMP
 
-(N_GE_2)
+(Main.fibonacci$N_GE_2)
 
 @0
 D=A
@@ -471,8 +471,8 @@ M=D
 0;JMP
 (Main.fibonacci$ret.3)
 
-(END)
+(Sys.init$END)
 
-@END
+@Sys.init$END
 0;JMP
 
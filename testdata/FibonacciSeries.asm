@@ -1,9 +1,3 @@
-@256
-D=A
-@SP
-M=D
-
-
 @1
 D=A
 @ARG
@@ -32,17 +26,17 @@ M=M+1
 
 @THAT
 D=M
-@13
+@R13
 M=D
 @0
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
@@ -56,17 +50,17 @@ M=M+1
 
 @THAT
 D=M
-@13
+@R13
 M=D
 @1
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
@@ -102,17 +96,17 @@ M=M+1
 
 @ARG
 D=M
-@13
+@R13
 M=D
 @0
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
@@ -176,17 +170,17 @@ M=M+1
 
 @THAT
 D=M
-@13
+@R13
 M=D
 @2
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
@@ -256,17 +250,17 @@ M=M+1
 
 @ARG
 D=M
-@13
+@R13
 M=D
 @0
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
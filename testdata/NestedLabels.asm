@@ -0,0 +1,677 @@
+@256
+D=A
+@SP
+M=D
+
+@Sys.init$ret.0
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@LCL
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@ARG
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@THIS
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@THAT
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@SP
+D=M
+@LCL
+M=D
+@SP
+D=M
+@0
+D=D-A
+@5
+D=D-A
+@ARG
+M=D
+@Sys.init
+0;JMP
+(Sys.init$ret.0)
+
+(Main.outer)
+@SP
+A=M
+M=0
+@SP
+M=M+1
+
+@0
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@LCL
+D=M
+@R13
+M=D
+@0
+D=A
+@R13
+M=D+M
+@SP
+M=M-1
+A=M
+D=M
+@R13
+A=M
+M=D
+
+(Main.outer$LOOP)
+
+@0
+D=A
+@ARG
+A=D+M
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@0
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@SP
+M=M-1
+A=M
+D=M
+@SP
+M=M-1
+A=M
+D=M-D
+@JUMP_START_0
+D;JEQ
+@SP
+A=M
+M=0
+@JUMP_END_0
+0;JMP
+(JUMP_START_0)
+@SP
+A=M
+M=-1
+(JUMP_END_0)
+@SP
+M=M+1
+
+@SP
+M=M-1
+A=M
+D=M
+@Main.outer$END
+D;JNE
+
+@0
+D=A
+@LCL
+A=D+M
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@0
+D=A
+@ARG
+A=D+M
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@Main.inner$ret.1
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@LCL
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@ARG
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@THIS
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@THAT
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@SP
+D=M
+@LCL
+M=D
+@SP
+D=M
+@1
+D=D-A
+@5
+D=D-A
+@ARG
+M=D
+@Main.inner
+0;JMP
+(Main.inner$ret.1)
+
+@SP
+M=M-1
+A=M
+D=M
+@SP
+M=M-1
+A=M
+M=D+M
+@SP
+M=M+1
+
+@LCL
+D=M
+@R13
+M=D
+@0
+D=A
+@R13
+M=D+M
+@SP
+M=M-1
+A=M
+D=M
+@R13
+A=M
+M=D
+
+@0
+D=A
+@ARG
+A=D+M
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@1
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@SP
+M=M-1
+A=M
+D=M
+@SP
+M=M-1
+A=M
+M=M-D
+@SP
+M=M+1
+
+@ARG
+D=M
+@R13
+M=D
+@0
+D=A
+@R13
+M=D+M
+@SP
+M=M-1
+A=M
+D=M
+@R13
+A=M
+M=D
+
+@Main.outer$LOOP
+0;JMP
+
+(Main.outer$END)
+
+@0
+D=A
+@LCL
+A=D+M
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@LCL
+D=M
+@R13
+M=D
+@5
+D=D-A
+A=D
+D=M
+@R14
+M=D
+@SP
+M=M-1
+A=M
+D=M
+@ARG
+A=M
+M=D
+@ARG
+D=M+1
+@SP
+M=D
+@R13
+D=M
+@1
+D=D-A
+A=D
+D=M
+@THAT
+M=D
+@R13
+D=M
+@2
+D=D-A
+A=D
+D=M
+@THIS
+M=D
+@R13
+D=M
+@3
+D=D-A
+A=D
+D=M
+@ARG
+M=D
+@R13
+D=M
+@4
+D=D-A
+A=D
+D=M
+@LCL
+M=D
+@R14
+A=M
+0;JMP
+
+(Main.inner)
+@SP
+A=M
+M=0
+@SP
+M=M+1
+
+@0
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@LCL
+D=M
+@R13
+M=D
+@0
+D=A
+@R13
+M=D+M
+@SP
+M=M-1
+A=M
+D=M
+@R13
+A=M
+M=D
+
+(Main.inner$LOOP)
+
+@0
+D=A
+@ARG
+A=D+M
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@0
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@SP
+M=M-1
+A=M
+D=M
+@SP
+M=M-1
+A=M
+D=M-D
+@JUMP_START_1
+D;JEQ
+@SP
+A=M
+M=0
+@JUMP_END_1
+0;JMP
+(JUMP_START_1)
+@SP
+A=M
+M=-1
+(JUMP_END_1)
+@SP
+M=M+1
+
+@SP
+M=M-1
+A=M
+D=M
+@Main.inner$END
+D;JNE
+
+@0
+D=A
+@LCL
+A=D+M
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@2
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@SP
+M=M-1
+A=M
+D=M
+@SP
+M=M-1
+A=M
+M=D+M
+@SP
+M=M+1
+
+@LCL
+D=M
+@R13
+M=D
+@0
+D=A
+@R13
+M=D+M
+@SP
+M=M-1
+A=M
+D=M
+@R13
+A=M
+M=D
+
+@0
+D=A
+@ARG
+A=D+M
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@1
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@SP
+M=M-1
+A=M
+D=M
+@SP
+M=M-1
+A=M
+M=M-D
+@SP
+M=M+1
+
+@ARG
+D=M
+@R13
+M=D
+@0
+D=A
+@R13
+M=D+M
+@SP
+M=M-1
+A=M
+D=M
+@R13
+A=M
+M=D
+
+@Main.inner$LOOP
+0;JMP
+
+(Main.inner$END)
+
+@0
+D=A
+@LCL
+A=D+M
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@LCL
+D=M
+@R13
+M=D
+@5
+D=D-A
+A=D
+D=M
+@R14
+M=D
+@SP
+M=M-1
+A=M
+D=M
+@ARG
+A=M
+M=D
+@ARG
+D=M+1
+@SP
+M=D
+@R13
+D=M
+@1
+D=D-A
+A=D
+D=M
+@THAT
+M=D
+@R13
+D=M
+@2
+D=D-A
+A=D
+D=M
+@THIS
+M=D
+@R13
+D=M
+@3
+D=D-A
+A=D
+D=M
+@ARG
+M=D
+@R13
+D=M
+@4
+D=D-A
+A=D
+D=M
+@LCL
+M=D
+@R14
+A=M
+0;JMP
+
+(Sys.init)
+
+@3
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@Main.outer$ret.2
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@LCL
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@ARG
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@THIS
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@THAT
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@SP
+D=M
+@LCL
+M=D
+@SP
+D=M
+@1
+D=D-A
+@5
+D=D-A
+@ARG
+M=D
+@Main.outer
+0;JMP
+(Main.outer$ret.2)
+
+@SP
+M=M-1
+A=M
+D=M
+@5
+M=D
+
+(Sys.init$LOOP)
+
+@Sys.init$LOOP
+0;JMP
+
@@ -38,17 +38,17 @@ M=M+1
 
 @THIS
 D=M
-@13
+@R13
 M=D
 @2
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
@@ -62,17 +62,17 @@ M=M+1
 
 @THAT
 D=M
-@13
+@R13
 M=D
 @6
 D=A
-@13
+@R13
 M=D+M
 @SP
 M=M-1
 A=M
 D=M
-@13
+@R13
 A=M
 M=D
 
@@ -1,9 +1,3 @@
-@256
-D=A
-@SP
-M=D
-
-
 (SimpleFunction.test)
 @SP
 A=M
@@ -105,7 +99,9 @@ D=M
 @R13
 M=D
 @5
-A=D-A
+D=D-A
+A=D
+D=M
 @R14
 M=D
 @SP
@@ -116,26 +112,38 @@ D=M
 A=M
 M=D
 @ARG
-D=M
+D=M+1
 @SP
-M=D+1
+M=D
 @R13
-AM=M-1
+D=M
+@1
+D=D-A
+A=D
 D=M
 @THAT
 M=D
 @R13
-AM=M-1
+D=M
+@2
+D=D-A
+A=D
 D=M
 @THIS
 M=D
 @R13
-AM=M-1
+D=M
+@3
+D=D-A
+A=D
 D=M
 @ARG
 M=D
 @R13
-AM=M-1
+D=M
+@4
+D=D-A
+A=D
 D=M
 @LCL
 M=D
@@ -36,6 +36,7 @@ M=-1
 (JUMP_END_0)
 @SP
 M=M+1
+
 @17
 D=A
 @SP
@@ -74,6 +75,7 @@ M=-1
 (JUMP_END_1)
 @SP
 M=M+1
+
 @16
 D=A
 @SP
@@ -112,6 +114,7 @@ M=-1
 (JUMP_END_2)
 @SP
 M=M+1
+
 @892
 D=A
 @SP
@@ -150,6 +153,7 @@ M=-1
 (JUMP_END_3)
 @SP
 M=M+1
+
 @891
 D=A
 @SP
@@ -188,6 +192,7 @@ M=-1
 (JUMP_END_4)
 @SP
 M=M+1
+
 @891
 D=A
 @SP
@@ -226,6 +231,7 @@ M=-1
 (JUMP_END_5)
 @SP
 M=M+1
+
 @32767
 D=A
 @SP
@@ -264,6 +270,7 @@ M=-1
 (JUMP_END_6)
 @SP
 M=M+1
+
 @32766
 D=A
 @SP
@@ -302,6 +309,7 @@ M=-1
 (JUMP_END_7)
 @SP
 M=M+1
+
 @32766
 D=A
 @SP
@@ -340,6 +348,7 @@ M=-1
 (JUMP_END_8)
 @SP
 M=M+1
+
 @57
 D=A
 @SP
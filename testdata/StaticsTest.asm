@@ -3,7 +3,6 @@ D=A
 @SP
 M=D
 
-
 @Sys.init$ret.0
 D=A
 @SP
@@ -55,91 +54,45 @@ M=D
 0;JMP
 (Sys.init$ret.0)
 
-(Sys.init)
+(Class1.set)
 
-@6
+@0
 D=A
+@ARG
+A=D+M
+D=M
 @SP
 A=M
 M=D
 @SP
 M=M+1
 
-@8
-D=A
 @SP
+M=M-1
 A=M
+D=M
+@16
 M=D
-@SP
-M=M+1
 
-@Class1.set$ret.1
+@1
 D=A
-@SP
-A=M
-M=D
-@SP
-M=M+1
-@LCL
-D=M
-@SP
-A=M
-M=D
-@SP
-M=M+1
 @ARG
+A=D+M
 D=M
 @SP
 A=M
 M=D
 @SP
 M=M+1
-@THIS
-D=M
-@SP
-A=M
-M=D
-@SP
-M=M+1
-@THAT
-D=M
-@SP
-A=M
-M=D
-@SP
-M=M+1
-@SP
-D=M
-@LCL
-M=D
-@SP
-D=M
-@2
-D=D-A
-@5
-D=D-A
-@ARG
-M=D
-@Class1.set
-0;JMP
-(Class1.set$ret.1)
 
 @SP
 M=M-1
 A=M
 D=M
-@5
+@17
 M=D
 
-@23
-D=A
-@SP
-A=M
-M=D
-@SP
-M=M+1
-
-@15
+@0
 D=A
 @SP
 A=M
@@ -147,172 +100,150 @@ M=D
 @SP
 M=M+1
 
-@Class2.set$ret.2
-D=A
-@SP
-A=M
-M=D
-@SP
-M=M+1
 @LCL
 D=M
-@SP
-A=M
+@R13
 M=D
-@SP
-M=M+1
-@ARG
+@5
+D=D-A
+A=D
 D=M
-@SP
-A=M
+@R14
 M=D
 @SP
-M=M+1
-@THIS
-D=M
-@SP
+M=M-1
 A=M
-M=D
-@SP
-M=M+1
-@THAT
 D=M
-@SP
+@ARG
 A=M
 M=D
+@ARG
+D=M+1
 @SP
-M=M+1
-@SP
+M=D
+@R13
 D=M
-@LCL
+@1
+D=D-A
+A=D
+D=M
+@THAT
 M=D
-@SP
+@R13
 D=M
 @2
 D=D-A
-@5
+A=D
+D=M
+@THIS
+M=D
+@R13
+D=M
+@3
 D=D-A
+A=D
+D=M
 @ARG
 M=D
-@Class2.set
-0;JMP
-(Class2.set$ret.2)
-
-@SP
-M=M-1
-A=M
+@R13
 D=M
-@5
+@4
+D=D-A
+A=D
+D=M
+@LCL
 M=D
-
-@Class1.get$ret.3
-D=A
-@SP
+@R14
 A=M
-M=D
-@SP
-M=M+1
-@LCL
+0;JMP
+
+(Class1.get)
+
+@16
 D=M
 @SP
 A=M
 M=D
 @SP
 M=M+1
-@ARG
+
+@17
 D=M
 @SP
 A=M
 M=D
 @SP
 M=M+1
-@THIS
-D=M
+
 @SP
+M=M-1
 A=M
-M=D
-@SP
-M=M+1
-@THAT
 D=M
 @SP
+M=M-1
 A=M
-M=D
+M=M-D
 @SP
 M=M+1
-@SP
-D=M
+
 @LCL
-M=D
-@SP
 D=M
-@0
-D=D-A
+@R13
+M=D
 @5
 D=D-A
-@ARG
+A=D
+D=M
+@R14
 M=D
-@Class1.get
-0;JMP
-(Class1.get$ret.3)
-
-@Class2.get$ret.4
-D=A
 @SP
+M=M-1
 A=M
-M=D
-@SP
-M=M+1
-@LCL
 D=M
-@SP
+@ARG
 A=M
 M=D
-@SP
-M=M+1
 @ARG
-D=M
+D=M+1
 @SP
-A=M
 M=D
-@SP
-M=M+1
-@THIS
+@R13
 D=M
-@SP
-A=M
-M=D
-@SP
-M=M+1
-@THAT
+@1
+D=D-A
+A=D
 D=M
-@SP
-A=M
+@THAT
 M=D
-@SP
-M=M+1
-@SP
+@R13
 D=M
-@LCL
+@2
+D=D-A
+A=D
+D=M
+@THIS
 M=D
-@SP
+@R13
 D=M
-@0
-D=D-A
-@5
+@3
 D=D-A
+A=D
+D=M
 @ARG
 M=D
-@Class2.get
-0;JMP
-(Class2.get$ret.4)
-
-(END)
-
-@END
+@R13
+D=M
+@4
+D=D-A
+A=D
+D=M
+@LCL
+M=D
+@R14
+A=M
 0;JMP
 
-(Class1.set)
+(Class2.set)
 
 @0
 D=A
@@ -329,7 +260,7 @@ M=M+1
 M=M-1
 A=M
 D=M
-@Class1.0
+@18
 M=D
 
 @1
@@ -347,7 +278,7 @@ M=M+1
 M=M-1
 A=M
 D=M
-@Class1.1
+@19
 M=D
 
 @0
@@ -415,9 +346,9 @@ M=D
 A=M
 0;JMP
 
-(Class1.get)
+(Class2.get)
 
-@Class1.0
+@18
 D=M
 @SP
 A=M
@@ -425,7 +356,7 @@ M=D
 @SP
 M=M+1
 
-@Class1.1
+@19
 D=M
 @SP
 A=M
@@ -501,45 +432,91 @@ M=D
 A=M
 0;JMP
 
-(Class2.set)
+(Sys.init)
 
-@0
+@6
 D=A
-@ARG
-A=D+M
-D=M
 @SP
 A=M
 M=D
 @SP
 M=M+1
 
+@8
+D=A
 @SP
-M=M-1
 A=M
-D=M
-@Class2.0
 M=D
+@SP
+M=M+1
 
-@1
+@Class1.set$ret.1
 D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@LCL
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
 @ARG
-A=D+M
 D=M
 @SP
 A=M
 M=D
 @SP
 M=M+1
+@THIS
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@THAT
+D=M
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@SP
+D=M
+@LCL
+M=D
+@SP
+D=M
+@2
+D=D-A
+@5
+D=D-A
+@ARG
+M=D
+@Class1.set
+0;JMP
+(Class1.set$ret.1)
 
 @SP
 M=M-1
 A=M
 D=M
-@Class2.1
+@5
 M=D
 
-@0
+@23
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+
+@15
 D=A
 @SP
 A=M
@@ -547,146 +524,168 @@ M=D
 @SP
 M=M+1
 
+@Class2.set$ret.2
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
 @LCL
 D=M
-@R13
+@SP
+A=M
 M=D
-@5
-D=D-A
-A=D
+@SP
+M=M+1
+@ARG
 D=M
-@R14
-M=D
 @SP
-M=M-1
 A=M
+M=D
+@SP
+M=M+1
+@THIS
 D=M
-@ARG
+@SP
 A=M
 M=D
-@ARG
-D=M+1
 @SP
-M=D
-@R13
+M=M+1
+@THAT
 D=M
-@1
-D=D-A
-A=D
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@SP
 D=M
-@THAT
+@LCL
 M=D
-@R13
+@SP
 D=M
 @2
 D=D-A
-A=D
-D=M
-@THIS
-M=D
-@R13
-D=M
-@3
+@5
 D=D-A
-A=D
-D=M
 @ARG
 M=D
-@R13
-D=M
-@4
-D=D-A
-A=D
-D=M
-@LCL
-M=D
-@R14
-A=M
+@Class2.set
 0;JMP
+(Class2.set$ret.2)
 
-(Class2.get)
+@SP
+M=M-1
+A=M
+D=M
+@5
+M=D
 
-@Class2.0
+@Class1.get$ret.3
+D=A
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@LCL
 D=M
 @SP
 A=M
 M=D
 @SP
 M=M+1
-
-@Class2.1
+@ARG
 D=M
 @SP
 A=M
 M=D
 @SP
 M=M+1
-
+@THIS
+D=M
 @SP
-M=M-1
 A=M
+M=D
+@SP
+M=M+1
+@THAT
 D=M
 @SP
-M=M-1
 A=M
-M=M-D
+M=D
 @SP
 M=M+1
-
-@LCL
+@SP
 D=M
-@R13
+@LCL
 M=D
+@SP
+D=M
+@0
+D=D-A
 @5
 D=D-A
-A=D
-D=M
-@R14
+@ARG
 M=D
+@Class1.get
+0;JMP
+(Class1.get$ret.3)
+
+@Class2.get$ret.4
+D=A
 @SP
-M=M-1
 A=M
+M=D
+@SP
+M=M+1
+@LCL
 D=M
-@ARG
+@SP
 A=M
 M=D
+@SP
+M=M+1
 @ARG
-D=M+1
+D=M
 @SP
+A=M
 M=D
-@R13
-D=M
-@1
-D=D-A
-A=D
+@SP
+M=M+1
+@THIS
 D=M
-@THAT
+@SP
+A=M
 M=D
-@R13
-D=M
-@2
-D=D-A
-A=D
+@SP
+M=M+1
+@THAT
 D=M
-@THIS
+@SP
+A=M
 M=D
-@R13
-D=M
-@3
-D=D-A
-A=D
+@SP
+M=M+1
+@SP
 D=M
-@ARG
+@LCL
 M=D
-@R13
+@SP
 D=M
-@4
+@0
 D=D-A
-A=D
-D=M
-@LCL
+@5
+D=D-A
+@ARG
 M=D
-@R14
-A=M
+@Class2.get
+0;JMP
+(Class2.get$ret.4)
+
+(Sys.init$END)
+
+@Sys.init$END
 0;JMP
 
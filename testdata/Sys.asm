@@ -3,7 +3,6 @@ D=A
 @SP
 M=D
 
-
 @Sys.init$ret.0
 D=A
 @SP
@@ -145,9 +144,9 @@ D=M
 @6
 M=D
 
-(LOOP)
+(Sys.init$LOOP)
 
-@LOOP
+@Sys.init$LOOP
 0;JMP
 
 (Sys.main)
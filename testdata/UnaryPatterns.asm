@@ -0,0 +1,103 @@
+D=0
+@16
+M=D
+
+D=1
+@LCL
+A=M
+M=D
+
+D=0
+
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@1
+D=A
+@LCL
+A=D+M
+D=M
+
+@SP
+M=M-1
+A=M
+M=D+M
+
+@SP
+M=M+1
+D=1
+
+A=M
+M=D
+@SP
+M=M+1
+@2
+D=A
+@LCL
+A=D+M
+D=M
+
+@SP
+M=M-1
+A=M
+M=M-D
+
+@SP
+M=M+1
+@3
+D=A
+@LCL
+A=D+M
+D=M
+
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@4
+D=A
+@LCL
+A=D+M
+D=M
+
+@SP
+A=M
+M=D
+@SP
+M=M+1
+@5
+D=A
+@LCL
+A=D+M
+D=M
+
+@SP
+A=M
+M=D
+@SP
+A=M
+M=!M
+
+@SP
+A=M
+D=M
+@17
+M=D
+
+@6
+D=A
+@LCL
+A=D+M
+D=M
+@18
+M=D
+
+@ARG
+A=M
+D=M
+@19
+M=D
+
@@ -0,0 +1,60 @@
+use std::{fs, path::Path, process::Command};
+
+/* 20000 < -20000 is false, but 20000 - -20000 overflows to a negative number */
+const OVERFLOW: &str = "\
+function Sys.init 0
+push constant 20000
+push constant 20000
+neg
+lt
+pop static 0
+push constant 20000
+neg
+push constant 20000
+gt
+pop static 1
+label END
+goto END
+";
+
+fn run(input: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_hackvm"))
+        .arg("run")
+        .args(args)
+        .args(["--print-ram", "16..18"])
+        .arg(input)
+        .output()
+        .expect("hackvm runs");
+    assert!(
+        output.status.success(),
+        "hackvm run {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // the step count and time go to stderr
+    String::from_utf8(output.stdout).expect("output is UTF-8")
+}
+
+/* the interpreter compares like the translated code, folded or not */
+#[test]
+fn comparisons_overflow_the_same_on_every_backend() {
+    let dir = std::env::temp_dir().join(format!("hackvm-backends-{}", std::process::id()));
+    fs::create_dir_all(&dir).expect("temp dir is writable");
+    let input = dir.join("Sys.vm");
+    fs::write(&input, OVERFLOW).expect("temp dir is writable");
+
+    let vm = run(&input, &["--backend", "vm"]);
+    assert!(vm.contains("RAM[16] = -1"), "{}", vm);
+    assert!(vm.contains("RAM[17] = -1"), "{}", vm);
+    for level in ["-O0", "-O2"] {
+        assert_eq!(
+            run(&input, &["--backend", "asm", level]),
+            vm,
+            "asm {}",
+            level
+        );
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+}
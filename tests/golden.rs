@@ -0,0 +1,95 @@
+use std::{fs, path::Path, process::Command};
+
+/*
+ * Every .asm in testdata is what translate makes of its .vm file or directory
+ * with the options next to it. HACKVM_BLESS=1 rewrites them from the current
+ * output instead of comparing, for changes to the generated code on purpose
+ */
+const GOLDENS: &[(&str, &str, &[&str])] = &[
+    ("BasicLoop.asm", "BasicLoop.vm", &["--no-bootstrap"]),
+    ("BasicTest.asm", "BasicTest.vm", &["--no-bootstrap"]),
+    (
+        "FibonacciSeries.asm",
+        "FibonacciSeries.vm",
+        &["--no-bootstrap"],
+    ),
+    ("PointerTest.asm", "PointerTest.vm", &["--no-bootstrap"]),
+    (
+        "SimpleFunction.asm",
+        "SimpleFunction.vm",
+        &["--no-bootstrap"],
+    ),
+    ("StackTest.asm", "StackTest.vm", &["--no-bootstrap"]),
+    ("Sys.asm", "Sys.vm", &[]),
+    ("FibonacciElement.asm", "FibonacciElement", &[]),
+    ("StaticsTest.asm", "StaticsTest", &[]),
+    ("NestedLabels.asm", "NestedLabels", &[]),
+    ("InlineComments.asm", "InlineComments.vm", &[]),
+    ("BlockComments.asm", "BlockComments.vm", &[]),
+    // -O2 only adds whole program passes, which change nothing without calls
+    (
+        "ConstantFolding.asm",
+        "ConstantFolding.vm",
+        &["--no-bootstrap", "-O1"],
+    ),
+    (
+        "ConstantFolding.asm",
+        "ConstantFolding.vm",
+        &["--no-bootstrap", "-O2"],
+    ),
+    (
+        "UnaryPatterns.asm",
+        "UnaryPatterns.vm",
+        &["--no-bootstrap", "-O1"],
+    ),
+    (
+        "UnaryPatterns.asm",
+        "UnaryPatterns.vm",
+        &["--no-bootstrap", "-O2"],
+    ),
+];
+
+fn translate(input: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_hackvm"))
+        .arg("translate")
+        .args(["--quiet", "--output", "-"])
+        .args(args)
+        .arg(input)
+        .output()
+        .expect("hackvm runs");
+    assert!(
+        output.status.success(),
+        "translating {} failed: {}",
+        input.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8(output.stdout).expect("assembly is UTF-8")
+}
+
+#[test]
+fn translations_match_goldens() {
+    let testdata = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata");
+    let bless = std::env::var_os("HACKVM_BLESS").is_some();
+    let mut stale = Vec::new();
+
+    for (golden, input, args) in GOLDENS {
+        let actual = translate(&testdata.join(input), args);
+        let path = testdata.join(golden);
+        if bless {
+            fs::write(&path, &actual).expect("golden is writable");
+            continue;
+        }
+
+        let expected = fs::read_to_string(&path).expect("golden exists");
+        if actual != expected {
+            stale.push(format!("{} ({} {})", golden, input, args.join(" ")));
+        }
+    }
+
+    assert!(
+        stale.is_empty(),
+        "output differs from the goldens, rerun with HACKVM_BLESS=1 if that's intended:\n  {}",
+        stale.join("\n  ")
+    );
+}
@@ -0,0 +1,62 @@
+use std::{fs, path::Path, process::Command};
+
+use hackvm::scaffold::TEMPLATE_NAMES;
+
+fn hackvm(dir: &Path, args: &[&str]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_hackvm"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .expect("hackvm runs");
+    assert!(
+        output.status.success(),
+        "hackvm {} failed in {}: {}{}",
+        args.join(" "),
+        dir.display(),
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/* every template builds as created and its test script passes */
+#[test]
+fn templates_build_and_pass_their_tests() {
+    let root = std::env::temp_dir().join(format!("hackvm-scaffold-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).expect("temp dir is writable");
+
+    for template in TEMPLATE_NAMES {
+        // the '-' is part of the project's file names too
+        let project = format!("my-{}", template);
+        hackvm(&root, &["new", &project, "--template", template]);
+
+        let dir = root.join(&project);
+        hackvm(&dir, &["build"]);
+        hackvm(&dir, &["test", &format!("{}.tst", project)]);
+    }
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+/* [os] path builds with the OS files found there instead of the bundled ones */
+#[test]
+fn os_path_replaces_the_bundled_os() {
+    let root = std::env::temp_dir().join(format!("hackvm-os-path-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).expect("temp dir is writable");
+    hackvm(&root, &["new", "calc", "--template", "with-os"]);
+
+    let dir = root.join("calc");
+    let os = Path::new(env!("CARGO_MANIFEST_DIR")).join("os");
+    let config = fs::read_to_string(dir.join("hackvm.toml")).expect("config was written");
+    let config = config.replace(
+        "bundled = true",
+        &format!("path = {:?}", os.to_str().expect("path is UTF-8")),
+    );
+    fs::write(dir.join("hackvm.toml"), config).expect("config is writable");
+
+    hackvm(&dir, &["build"]);
+    hackvm(&dir, &["test", "calc.tst"]);
+
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,91 @@
+use std::{fs, process::Command};
+
+/*
+ * Main.main only reaches Zed.0 through Zed.set and Zed.get, which -O2 inlines,
+ * so the code uses Zed.0 before Main.0 although Main.vm comes first
+ */
+const FILES: &[(&str, &str)] = &[
+    (
+        "Main.vm",
+        "function Main.main 0\npush constant 22\ncall Zed.set 1\npop temp 0\n\
+         call Zed.get 0\npush constant 11\nadd\npop static 0\npush constant 0\nreturn\n",
+    ),
+    (
+        "Sys.vm",
+        "function Sys.init 0\ncall Main.main 0\npop temp 0\nlabel END\ngoto END\n",
+    ),
+    (
+        "Zed.vm",
+        "function Zed.get 0\npush static 0\nreturn\n\
+         function Zed.set 0\npush argument 0\npop static 0\npush constant 0\nreturn\n",
+    ),
+];
+
+fn hackvm(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_hackvm"))
+        .args(args)
+        .output()
+        .expect("hackvm runs");
+    assert!(
+        output.status.success(),
+        "hackvm {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8(output.stdout).expect("output is UTF-8")
+}
+
+// RAM[16]\tMain.0 -> (Main.0, 16)
+fn map_entry(line: &str) -> Option<(&str, &str)> {
+    let (address, symbol) = line.trim().split_once('\t')?;
+    let address = address.strip_prefix("RAM[")?.strip_suffix(']')?;
+    Some((symbol, address))
+}
+
+/* the addresses --static-map prints are where the running program keeps its statics */
+#[test]
+fn static_map_matches_the_code_at_o2() {
+    let dir = std::env::temp_dir().join(format!("hackvm-static-map-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("temp dir is writable");
+    for (name, source) in FILES {
+        fs::write(dir.join(name), source).expect("temp dir is writable");
+    }
+    let input = dir.to_str().expect("temp dir is UTF-8");
+    let asm = dir.join("out.asm");
+    let output = asm.to_str().expect("temp dir is UTF-8");
+
+    let map = hackvm(&[
+        "translate",
+        "-O2",
+        "--static-map",
+        "--output",
+        output,
+        input,
+    ]);
+    let addresses: Vec<_> = map.lines().filter_map(map_entry).collect();
+    assert_eq!(addresses.len(), 2, "{}", map);
+    let code = fs::read_to_string(&asm).expect("assembly was written");
+    assert!(!code.contains("@Zed.get"), "Zed.get wasn't inlined");
+
+    let ram = hackvm(&[
+        "run",
+        "-O2",
+        "--backend",
+        "asm",
+        "--print-ram",
+        "16..18",
+        input,
+    ]);
+    for (symbol, value) in [("Main.0", 33), ("Zed.0", 22)] {
+        let (_, address) = addresses
+            .iter()
+            .find(|(name, _)| *name == symbol)
+            .unwrap_or_else(|| panic!("{} is missing from the map:\n{}", symbol, map));
+        let expected = format!("RAM[{}] = {}", address, value);
+        assert!(ram.contains(&expected), "expected {} in\n{}", expected, ram);
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+}